@@ -1,83 +1,606 @@
+//! A two pass assembler for the Cowgod syntax that [`Instruction`] prints.
+//!
+//! Each instruction is a mnemonic followed by comma separated operands on
+//! the same line, so several can share a line. `name:` defines a label for
+//! the address of whatever follows it, which jumps, calls and `LD I` can
+//! refer to by name. `DB` emits bytes as they are. Numbers are decimal,
+//! `0x` hex or `0b` binary, and `;` starts a comment. The SUPER-CHIP and
+//! XO-CHIP extensions are spelled the way the disassembler prints them.
+//!
+//! Mistakes don't stop it: [`assemble_file`] skips what it can't make sense
+//! of and carries on, so one run reports every error. It also warns about
+//! values too big for their instruction and jumps into `DB` bytes.
+
 #[cfg(test)]
 mod tests;
 
-pub fn assemble(program: String) -> Vec<u8> {
-    let mut assembled_program: Vec<u8> = Vec::new();
-    let tokens: Vec<&str> = program.split(" ").collect();
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use chip8::opcode::Instruction;
+use core::cell::RefCell;
+use core::fmt::{self, Display, Formatter};
+use core::iter;
+use core::ops::Range;
+
+/// Where the assembled program is loaded, which labels count from.
+pub const ORIGIN: usize = 0x200;
+
+/// Mnemonics that never take operands, so whatever follows on the line is
+/// the next instruction.
+const BARE: [&str; 8] = ["CLS", "RET", "SCR", "SCL", "EXIT", "LOW", "HIGH", "AUDIO"];
+/// Operands that name something other than a register or a value.
+const KEYWORDS: [&str; 9] = ["I", "[I]", "DT", "ST", "K", "F", "HF", "B", "R"];
+
+/// How serious a [`Diagnostic`] is. Warnings still leave a usable ROM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// What is wrong with the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    /// The mnemonic isn't one the assembler knows.
+    UnknownInstruction(String),
+    /// The mnemonic is known but not with these operands.
+    InvalidOperands(String),
+    /// Something that is neither a register, a number nor a label.
+    InvalidOperand(String),
+    /// A number with a digit its base doesn't have, or too big for 32 bits.
+    InvalidNumber(String),
+    /// A label definition whose name isn't an identifier.
+    InvalidLabel(String),
+    /// A comma or `LONG` with nothing after it.
+    ExpectedOperand,
+    UndefinedLabel(String),
+    DuplicateLabel(String),
+    /// A value that doesn't fit in the bits the instruction has for it, so
+    /// only the low bits are used.
+    OutOfRange {
+        value: u32,
+        max: u32,
+    },
+    /// A `JP` or `CALL` to an address inside `DB` bytes.
+    JumpIntoData(u32),
+}
+
+impl DiagnosticKind {
+    pub fn severity(&self) -> Severity {
+        match *self {
+            DiagnosticKind::OutOfRange { .. } | DiagnosticKind::JumpIntoData(_) => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+}
+
+/// A problem found in the source, with the 1-based line and column it
+/// starts at.
+///
+/// `snippet` is the offending line with carets under the problem, and
+/// `Display` puts it under the message in the style of `rustc`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub kind: DiagnosticKind,
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub snippet: String,
+}
+
+/// The ROM and everything worth reporting about the source. The ROM is
+/// only worth loading if there are no errors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assembly {
+    pub rom: Vec<u8>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Assembly {
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.severity == Severity::Error)
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+impl Display for DiagnosticKind {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            DiagnosticKind::UnknownInstruction(ref name) => write!(f, "unknown instruction '{}'", name),
+            DiagnosticKind::InvalidOperands(ref name) => write!(f, "invalid operands for {}", name),
+            DiagnosticKind::InvalidOperand(ref text) => write!(f, "invalid operand '{}'", text),
+            DiagnosticKind::InvalidNumber(ref text) => write!(f, "invalid number '{}'", text),
+            DiagnosticKind::InvalidLabel(ref name) => write!(f, "invalid label name '{}'", name),
+            DiagnosticKind::ExpectedOperand => write!(f, "expected an operand"),
+            DiagnosticKind::UndefinedLabel(ref name) => write!(f, "undefined label '{}'", name),
+            DiagnosticKind::DuplicateLabel(ref name) => write!(f, "label '{}' is already defined", name),
+            DiagnosticKind::OutOfRange { value, max } => write!(
+                f,
+                "0x{:X} is out of range (at most 0x{:X}) and is truncated to 0x{:X}",
+                value,
+                max,
+                value & max
+            ),
+            DiagnosticKind::JumpIntoData(address) => write!(f, "0x{:03X} is data, not code", address),
+        }
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {}\n{:gutter$}--> {}:{}:{}\n{}",
+            self.severity,
+            self.kind,
+            "",
+            self.file,
+            self.line,
+            self.column,
+            self.snippet,
+            gutter = self.line.to_string().len()
+        )
+    }
+}
+
+/// Assembles `program` into a ROM to be loaded at [`ORIGIN`], or returns
+/// what is wrong with it. Use [`assemble_file`] to see warnings too.
+pub fn assemble(program: &str) -> Result<Vec<u8>, Vec<Diagnostic>> {
+    let assembly = assemble_file("<input>", program);
+    if assembly.has_errors() {
+        Err(assembly.diagnostics)
+    } else {
+        Ok(assembly.rom)
+    }
+}
+
+/// Assembles `program`, read from `file`, reporting every problem rather
+/// than stopping at the first. Statements with errors are left out, or
+/// zeroed once labels are known so later addresses don't move.
+pub fn assemble_file(file: &str, program: &str) -> Assembly {
+    let mut problems = Vec::new();
+    let statements = parse(&tokenize(program), &mut problems);
+
+    //First pass: find the address of every label, and of the data
+    let mut labels = BTreeMap::new();
+    let mut data = Vec::new();
+    let mut address = ORIGIN;
+    for statement in &statements {
+        match *statement {
+            Statement::Label(token) => {
+                let name = &token.text[..token.text.len() - 1];
+                if labels.contains_key(name) {
+                    problems.push(token.problem(DiagnosticKind::DuplicateLabel(name.to_string())));
+                } else {
+                    labels.insert(name, address);
+                }
+            }
+            Statement::Instruction { mnemonic, .. } => {
+                if mnemonic.text.eq_ignore_ascii_case("DB") {
+                    data.push(address..address + statement.size());
+                }
+                address += statement.size();
+            }
+        }
+    }
+
+    //Second pass: encode with every label known
+    let mut rom = Vec::new();
+    for statement in &statements {
+        if let Statement::Instruction { mnemonic, ref operands } = *statement {
+            let encoder = Encoder {
+                operands,
+                labels: &labels,
+                data: &data,
+                warnings: RefCell::new(Vec::new()),
+            };
+            match encoder.encode(mnemonic) {
+                Ok(bytes) => rom.extend(bytes),
+                Err(problem) => {
+                    problems.push(problem);
+                    rom.extend(iter::repeat_n(0, statement.size()));
+                }
+            }
+            problems.extend(encoder.warnings.into_inner());
+        }
+    }
+
+    problems.sort_by_key(|problem| (problem.token.line, problem.token.column));
+    let lines: Vec<&str> = program.lines().collect();
+    let diagnostics = problems
+        .into_iter()
+        .map(|problem| {
+            let line = lines[problem.token.line - 1];
+            problem.diagnostic(file, line)
+        })
+        .collect();
+    Assembly { rom, diagnostics }
+}
+
+/// A word, or a lone comma, and where it starts.
+#[derive(Debug, Clone, Copy)]
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Token<'a> {
+    fn problem(&self, kind: DiagnosticKind) -> Problem<'a> {
+        Problem { token: *self, kind }
+    }
+}
+
+/// A diagnostic before it has been given its file and snippet.
+#[derive(Debug, Clone)]
+struct Problem<'a> {
+    token: Token<'a>,
+    kind: DiagnosticKind,
+}
+
+impl<'a> Problem<'a> {
+    fn diagnostic(self, file: &str, line: &str) -> Diagnostic {
+        let number = self.token.line.to_string();
+        let blank = " ".repeat(number.len());
+        //Keep tabs so the carets line up however wide they are shown
+        let indent: String = line
+            .chars()
+            .take(self.token.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let carets = "^".repeat(self.token.text.chars().count());
+        Diagnostic {
+            severity: self.kind.severity(),
+            kind: self.kind,
+            file: file.to_string(),
+            line: self.token.line,
+            column: self.token.column,
+            snippet: format!("{} |\n{} | {}\n{} | {}{}", blank, number, line, blank, indent, carets),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Value<'a> {
+    Number(u32),
+    Label(&'a str),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Arg<'a> {
+    Register(usize),
+    Keyword(&'static str),
+    Value(Value<'a>),
+    /// A value after `LONG`, for the four byte `LD I`.
+    Long(Value<'a>),
+}
+
+/// An operand and the token it was read from, which for `LONG` is the
+/// value after it.
+#[derive(Debug, Clone, Copy)]
+struct Operand<'a> {
+    token: Token<'a>,
+    arg: Arg<'a>,
+}
+
+#[derive(Debug, Clone)]
+enum Statement<'a> {
+    Label(Token<'a>),
+    Instruction {
+        mnemonic: Token<'a>,
+        operands: Vec<Operand<'a>>,
+    },
+}
+
+impl<'a> Statement<'a> {
+    fn size(&self) -> usize {
+        match *self {
+            Statement::Label(_) => 0,
+            Statement::Instruction { mnemonic, ref operands } => {
+                if mnemonic.text.eq_ignore_ascii_case("DB") {
+                    operands.len()
+                } else if operands.iter().any(|operand| matches!(operand.arg, Arg::Long(_))) {
+                    4
+                } else {
+                    2
+                }
+            }
+        }
+    }
+}
+
+fn tokenize(program: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    for (number, line) in program.lines().enumerate() {
+        let code = line.split(';').next().unwrap_or("");
+        let mut start = None;
+        let token = |begin: usize, end: usize| Token {
+            text: &code[begin..end],
+            line: number + 1,
+            column: code[..begin].chars().count() + 1,
+        };
+        for (offset, c) in code.char_indices().chain(iter::once((code.len(), ' '))) {
+            if c.is_whitespace() || c == ',' {
+                if let Some(begin) = start.take() {
+                    tokens.push(token(begin, offset));
+                }
+                if c == ',' {
+                    tokens.push(token(offset, offset + 1));
+                }
+            } else if start.is_none() {
+                start = Some(offset);
+            }
+        }
+    }
+    tokens
+}
+
+/// Splits the tokens into statements. A statement with a bad operand is
+/// reported and dropped along with the rest of its line.
+fn parse<'a>(tokens: &[Token<'a>], problems: &mut Vec<Problem<'a>>) -> Vec<Statement<'a>> {
+    let mut statements = Vec::new();
     let mut i = 0;
     while i < tokens.len() {
-        let opcode = tokens[i];
-        let mut high: u8;
-        let mut low: u8;
-        match opcode {
-            "ADD" => {},
-            "AND" => {
-                i += 1;
-                let x = tokens[i];
-                high =  (0x8 << 8) | get_register_number(x);
-                i += 1;
-                let y = tokens[i];
-                low = (get_register_number(y) << 8) | 0x2;
-            },
-            "CALL" => {},
-            "CLS" => {
-                high = 0x00;
-                low = 0xE0;
-            },
-            "DRW" => {
-                
-            },
-            "JP" => {},
-            "LD" => {},
-            "OR" => {},
-            "RET" => {
-                high = 0x00;
-                low = 0xEE;
-            },
-            "RND" => {},
-            "SE" => {
-                i += 1;
-                let register_token = tokens[i];
-                high = (0x3 << 4) | get_register_number(register_token);
-                i += 1;
-                if tokens[i].starts_with("V") {
-                    low = get_register_number(tokens[i]) << 4;
+        let mnemonic = tokens[i];
+        i += 1;
+        if let Some(name) = mnemonic.text.strip_suffix(':') {
+            if is_identifier(name) {
+                statements.push(Statement::Label(mnemonic));
+            } else {
+                problems.push(mnemonic.problem(DiagnosticKind::InvalidLabel(name.to_string())));
+            }
+            continue;
+        }
+
+        //Operands follow on the same line, separated by commas
+        let mut operands = Vec::new();
+        let bare = BARE.iter().any(|name| mnemonic.text.eq_ignore_ascii_case(name));
+        if !bare && i < tokens.len() && tokens[i].line == mnemonic.line {
+            loop {
+                match parse_operand(&tokens[i - 1..]) {
+                    Ok((operand, used)) => {
+                        operands.push(operand);
+                        i += used;
+                    }
+                    Err(problem) => {
+                        problems.push(problem);
+                        while i < tokens.len() && tokens[i].line == mnemonic.line {
+                            i += 1;
+                        }
+                        break;
+                    }
+                }
+                if i < tokens.len() && tokens[i].text == "," && tokens[i].line == mnemonic.line {
+                    i += 1;
                 } else {
-                    low = u8::from_str_radix(tokens[i], 16).unwrap();
+                    statements.push(Statement::Instruction { mnemonic, operands });
+                    break;
                 }
-            },
-            "SHL" => {},
-            "SHR" => {},
-            "SKNP" => {},
-            "SKP" => {},
-            "SNE" => {},
-            "SUB" => {},
-            "SUBN" => {},
-            "SYS" => {},
-            "XOR" => {},
-            _ => {high = 0x00;
-                low = 0xE0;}//panic!("Unrecognised token."),
+            }
+        } else {
+            statements.push(Statement::Instruction { mnemonic, operands });
         }
+    }
+    statements
+}
 
-        // let operand = tokens[i + 1];
-        // assembled_program.push(high);
-        // assembled_program.push(low);
-        i += 1;
+/// Reads the operand after `tokens[0]`, returning it and how many tokens
+/// it took up.
+fn parse_operand<'a>(tokens: &[Token<'a>]) -> Result<(Operand<'a>, usize), Problem<'a>> {
+    let (before, token) = (tokens[0], tokens.get(1).filter(|token| token.line == tokens[0].line));
+    let token = *token.ok_or_else(|| before.problem(DiagnosticKind::ExpectedOperand))?;
+    let text = token.text;
+    if text.eq_ignore_ascii_case("LONG") {
+        let (value, _) = parse_operand(&tokens[1..])?;
+        return match value.arg {
+            Arg::Value(inner) => Ok((
+                Operand {
+                    token: value.token,
+                    arg: Arg::Long(inner),
+                },
+                2,
+            )),
+            _ => Err(value
+                .token
+                .problem(DiagnosticKind::InvalidOperand(value.token.text.to_string()))),
+        };
     }
-    assembled_program
+
+    let keyword = KEYWORDS.iter().find(|keyword| text.eq_ignore_ascii_case(keyword));
+    let register = match text.as_bytes() {
+        [b'V', digit] | [b'v', digit] => char::from(*digit).to_digit(16),
+        _ => None,
+    };
+    let arg = if let Some(keyword) = keyword {
+        Arg::Keyword(keyword)
+    } else if let Some(x) = register {
+        Arg::Register(x as usize)
+    } else if text.starts_with(|c: char| c.is_ascii_digit()) {
+        let number =
+            parse_number(text).ok_or_else(|| token.problem(DiagnosticKind::InvalidNumber(text.to_string())))?;
+        Arg::Value(Value::Number(number))
+    } else if is_identifier(text) {
+        Arg::Value(Value::Label(text))
+    } else {
+        return Err(token.problem(DiagnosticKind::InvalidOperand(text.to_string())));
+    };
+    Ok((Operand { token, arg }, 1))
 }
 
-fn assemble_opcode(opcode: &str, operand: &str) -> (u8, u8) {
-    match opcode {
-        "SYS" => {}
-        "JP" => {}
-        "CALL" => {}
-        _ => panic!("Unexpected token"),
+fn parse_number(text: &str) -> Option<u32> {
+    let lower = text.to_ascii_lowercase();
+    if let Some(hex) = lower.strip_prefix("0x") {
+        u32::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = lower.strip_prefix("0b") {
+        u32::from_str_radix(binary, 2).ok()
+    } else {
+        lower.parse().ok()
     }
-    (0, 0)
 }
 
-fn get_register_number(token: &str) -> u8 {
-    let digit: &str = &token[1..2];
-    u8::from_str_radix(digit, 16).unwrap()
+fn is_identifier(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && text.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Turns one statement's operands into bytes, once every label is known.
+struct Encoder<'a, 'b> {
+    operands: &'b [Operand<'a>],
+    labels: &'b BTreeMap<&'a str, usize>,
+    /// Where the `DB` statements are.
+    data: &'b [Range<usize>],
+    warnings: RefCell<Vec<Problem<'a>>>,
+}
+
+impl<'a, 'b> Encoder<'a, 'b> {
+    /// The operands in the form the instruction tables use, such as
+    /// `V, N` for a register and a value.
+    fn shape(&self) -> String {
+        let kinds: Vec<&str> = self
+            .operands
+            .iter()
+            .map(|operand| match operand.arg {
+                Arg::Register(_) => "V",
+                Arg::Keyword(keyword) => keyword,
+                Arg::Value(_) => "N",
+                Arg::Long(_) => "LONG",
+            })
+            .collect();
+        kinds.join(", ")
+    }
+
+    fn register(&self, index: usize) -> usize {
+        match self.operands[index].arg {
+            Arg::Register(x) => x,
+            _ => unreachable!("the shape has a register here"),
+        }
+    }
+
+    fn value(&self, index: usize, max: u32) -> Result<u32, Problem<'a>> {
+        let operand = &self.operands[index];
+        let value = match operand.arg {
+            Arg::Value(value) | Arg::Long(value) => value,
+            _ => unreachable!("the shape has a value here"),
+        };
+        let number = match value {
+            Value::Number(number) => number,
+            Value::Label(name) => match self.labels.get(name) {
+                Some(&address) => address as u32,
+                None => return Err(operand.token.problem(DiagnosticKind::UndefinedLabel(name.to_string()))),
+            },
+        };
+        if number > max {
+            let warning = operand.token.problem(DiagnosticKind::OutOfRange { value: number, max });
+            self.warnings.borrow_mut().push(warning);
+        }
+        Ok(number & max)
+    }
+
+    fn encode(&self, mnemonic: Token<'a>) -> Result<Vec<u8>, Problem<'a>> {
+        let name = mnemonic.text.to_ascii_uppercase();
+        let shape = self.shape();
+        let x = || self.register(0);
+        let y = || self.register(1);
+        let n = |index| self.value(index, 0xF).map(|n| n as usize);
+        let nn = |index| self.value(index, 0xFF).map(|nn| nn as u8);
+        let nnn = |index| self.value(index, 0xFFF).map(|nnn| nnn as u16);
+
+        let instruction = match (name.as_str(), shape.as_str()) {
+            ("DB", _) => {
+                return (0..self.operands.len())
+                    .map(|index| match self.operands[index].arg {
+                        Arg::Value(_) => nn(index),
+                        _ => Err(mnemonic.problem(DiagnosticKind::InvalidOperands(name.clone()))),
+                    })
+                    .collect();
+            }
+            ("SYS", "N") => Instruction::Sys { nnn: nnn(0)? },
+            ("CLS", "") => Instruction::Clear,
+            ("RET", "") => Instruction::Return,
+            ("JP", "N") => Instruction::Jump { nnn: nnn(0)? },
+            ("JP", "V, N") if x() == 0 => Instruction::JumpV0 { nnn: nnn(1)? },
+            ("CALL", "N") => Instruction::Call { nnn: nnn(0)? },
+            ("SE", "V, N") => Instruction::SkipEqImm { x: x(), nn: nn(1)? },
+            ("SE", "V, V") => Instruction::SkipEqReg { x: x(), y: y() },
+            ("SNE", "V, N") => Instruction::SkipNeImm { x: x(), nn: nn(1)? },
+            ("SNE", "V, V") => Instruction::SkipNeReg { x: x(), y: y() },
+            ("LD", "V, N") => Instruction::LoadImm { x: x(), nn: nn(1)? },
+            ("LD", "V, V") => Instruction::LoadReg { x: x(), y: y() },
+            ("LD", "I, N") => Instruction::LoadI { nnn: nnn(1)? },
+            ("LD", "I, LONG") => Instruction::LoadILong {
+                nnnn: self.value(1, 0xFFFF)? as u16,
+            },
+            ("LD", "V, DT") => Instruction::LoadDelay { x: x() },
+            ("LD", "V, K") => Instruction::WaitKey { x: x() },
+            ("LD", "DT, V") => Instruction::SetDelay { x: self.register(1) },
+            ("LD", "ST, V") => Instruction::SetSound { x: self.register(1) },
+            ("LD", "F, V") => Instruction::LoadFont { x: self.register(1) },
+            ("LD", "HF, V") => Instruction::LoadBigFont { x: self.register(1) },
+            ("LD", "B, V") => Instruction::StoreBcd { x: self.register(1) },
+            ("LD", "[I], V") => Instruction::StoreRegisters { x: self.register(1) },
+            ("LD", "V, [I]") => Instruction::LoadRegisters { x: x() },
+            ("LD", "R, V") => Instruction::StoreFlags { x: self.register(1) },
+            ("LD", "V, R") => Instruction::LoadFlags { x: x() },
+            ("ADD", "V, N") => Instruction::AddImm { x: x(), nn: nn(1)? },
+            ("ADD", "V, V") => Instruction::AddRegReg { x: x(), y: y() },
+            ("ADD", "I, V") => Instruction::AddI { x: self.register(1) },
+            ("OR", "V, V") => Instruction::Or { x: x(), y: y() },
+            ("AND", "V, V") => Instruction::And { x: x(), y: y() },
+            ("XOR", "V, V") => Instruction::Xor { x: x(), y: y() },
+            ("SUB", "V, V") => Instruction::Sub { x: x(), y: y() },
+            ("SUBN", "V, V") => Instruction::SubN { x: x(), y: y() },
+            //Without VY, shift VX itself whichever shift quirk is in use
+            ("SHR", "V") => Instruction::ShiftRight { x: x(), y: x() },
+            ("SHR", "V, V") => Instruction::ShiftRight { x: x(), y: y() },
+            ("SHL", "V") => Instruction::ShiftLeft { x: x(), y: x() },
+            ("SHL", "V, V") => Instruction::ShiftLeft { x: x(), y: y() },
+            ("RND", "V, N") => Instruction::Random { x: x(), nn: nn(1)? },
+            ("DRW", "V, V, N") => Instruction::Draw {
+                x: x(),
+                y: y(),
+                n: n(2)?,
+            },
+            ("SKP", "V") => Instruction::SkipKey { x: x() },
+            ("SKNP", "V") => Instruction::SkipNotKey { x: x() },
+            ("SCD", "N") => Instruction::ScrollDown { n: n(0)? },
+            ("SCU", "N") => Instruction::ScrollUp { n: n(0)? },
+            ("SCR", "") => Instruction::ScrollRight,
+            ("SCL", "") => Instruction::ScrollLeft,
+            ("EXIT", "") => Instruction::Exit,
+            ("LOW", "") => Instruction::LowRes,
+            ("HIGH", "") => Instruction::HighRes,
+            ("SAVE", "V, V") => Instruction::SaveRange { x: x(), y: y() },
+            ("LOAD", "V, V") => Instruction::LoadRange { x: x(), y: y() },
+            ("PLANE", "N") => Instruction::SelectPlanes { n: n(0)? },
+            ("AUDIO", "") => Instruction::LoadAudio,
+            ("PITCH", "V") => Instruction::SetPitch { x: x() },
+            (
+                "SYS" | "JP" | "CALL" | "SE" | "SNE" | "LD" | "ADD" | "OR" | "AND" | "XOR" | "SUB" | "SUBN" | "SHR"
+                | "SHL" | "RND" | "DRW" | "SKP" | "SKNP" | "SCD" | "SCU" | "SAVE" | "LOAD" | "PLANE" | "PITCH",
+                _,
+            ) => return Err(mnemonic.problem(DiagnosticKind::InvalidOperands(name))),
+            _ => return Err(mnemonic.problem(DiagnosticKind::UnknownInstruction(mnemonic.text.to_string()))),
+        };
+        if let Instruction::Jump { nnn: target } | Instruction::Call { nnn: target } = instruction {
+            if self.data.iter().any(|range| range.contains(&usize::from(target))) {
+                let warning = self.operands[0]
+                    .token
+                    .problem(DiagnosticKind::JumpIntoData(u32::from(target)));
+                self.warnings.borrow_mut().push(warning);
+            }
+        }
+        Ok(instruction.to_bytes())
+    }
 }
@@ -1,7 +1,127 @@
 use assembler::*;
+use chip8::opcode::Instruction;
 
-// #[test]
+#[test]
 pub fn test() {
-    let program = assemble("SE V0, V1".to_string());
-    println!("{:X?}", program);
-}
\ No newline at end of file
+    let program = assemble("SE V0, V1").unwrap();
+    assert_eq!(program, vec![0x50, 0x10]);
+}
+
+#[test]
+fn test_and_and_return() {
+    let program = assemble("AND V3, VA RET").unwrap();
+    assert_eq!(program, vec![0x83, 0xA2, 0x00, 0xEE]);
+}
+
+#[test]
+fn test_labels_comments_and_literals() {
+    let program = "
+        ; Draw a sprite forever
+        LD I, sprite        ; forward reference
+    loop: CALL draw
+        JP loop
+    draw:
+        drw v0, v1, 0b11
+        RET
+    sprite: DB 0xF0, 144, 0b11110000";
+    assert_eq!(
+        assemble(program).unwrap(),
+        vec!(0xA2, 0x0A, 0x22, 0x06, 0x12, 0x02, 0xD0, 0x13, 0x00, 0xEE, 0xF0, 0x90, 0xF0)
+    );
+}
+
+#[test]
+fn test_every_instruction_round_trips() {
+    for opcode in 0..=0xFFFF {
+        if let Ok(instruction) = Instruction::decode(opcode) {
+            let source = instruction.to_string();
+            assert_eq!(assemble(&source), Ok(instruction.to_bytes()), "{}", source);
+        }
+    }
+    let long = Instruction::LoadILong { nnnn: 0xABCD };
+    assert_eq!(assemble(&long.to_string()), Ok(vec!(0xF0, 0x00, 0xAB, 0xCD)));
+    assert_eq!(assemble("SHR V4 SHL V5"), Ok(vec!(0x84, 0x46, 0x85, 0x5E)));
+}
+
+#[test]
+#[cfg(feature = "disassembler")]
+fn test_disassembly_round_trips() {
+    use chip8::Platform;
+    use disassembler::Disassembler;
+
+    let rom = include_bytes!("../../roms/pong");
+    let source = Disassembler::new(rom, Platform::Chip8).disassemble().to_string();
+    assert_eq!(assemble(&source), Ok(rom.to_vec()));
+}
+
+#[test]
+fn test_errors_point_at_the_problem() {
+    let error = |source: &str| {
+        let diagnostic = assemble(source).unwrap_err().remove(0);
+        (diagnostic.line, diagnostic.column, diagnostic.kind)
+    };
+    assert_eq!(error("CLS\n  FOO V0"), (2, 3, DiagnosticKind::UnknownInstruction("FOO".to_string())));
+    assert_eq!(error("JP nowhere"), (1, 4, DiagnosticKind::UndefinedLabel("nowhere".to_string())));
+    assert_eq!(error("ADD V0, 0x1G"), (1, 9, DiagnosticKind::InvalidNumber("0x1G".to_string())));
+    assert_eq!(error("DRW V0, V1"), (1, 1, DiagnosticKind::InvalidOperands("DRW".to_string())));
+    assert_eq!(error("SE V0,"), (1, 6, DiagnosticKind::ExpectedOperand));
+    assert_eq!(error("a: a: CLS"), (1, 4, DiagnosticKind::DuplicateLabel("a".to_string())));
+    assert_eq!(error("JP V1, 0x200"), (1, 1, DiagnosticKind::InvalidOperands("JP".to_string())));
+}
+
+#[test]
+fn test_every_error_is_reported() {
+    let program = "start:
+    LD V0, $1
+    FOO
+    JP missing
+    JP start
+1st: CLS";
+    let assembly = assemble_file("game.asm", program);
+    let kinds: Vec<DiagnosticKind> = assembly.diagnostics.iter().map(|diagnostic| diagnostic.kind.clone()).collect();
+    assert_eq!(
+        kinds,
+        vec!(
+            DiagnosticKind::InvalidOperand("$1".to_string()),
+            DiagnosticKind::UnknownInstruction("FOO".to_string()),
+            DiagnosticKind::UndefinedLabel("missing".to_string()),
+            DiagnosticKind::InvalidLabel("1st".to_string()),
+        )
+    );
+    assert!(assembly.has_errors());
+    //The bad LD is dropped and the rest keep their addresses
+    assert_eq!(assembly.rom, vec!(0x00, 0x00, 0x00, 0x00, 0x12, 0x00, 0x00, 0xE0));
+}
+
+#[test]
+fn test_warnings_still_assemble() {
+    let assembly = assemble_file("game.asm", "JP table\nLD V0, 0x1FF\ntable: DB 1, 2");
+    assert!(!assembly.has_errors());
+    assert_eq!(assembly.rom, vec!(0x12, 0x04, 0x60, 0xFF, 0x01, 0x02));
+    let warnings: Vec<(Severity, usize, DiagnosticKind)> = assembly
+        .diagnostics
+        .into_iter()
+        .map(|diagnostic| (diagnostic.severity, diagnostic.line, diagnostic.kind))
+        .collect();
+    assert_eq!(
+        warnings,
+        vec!(
+            (Severity::Warning, 1, DiagnosticKind::JumpIntoData(0x204)),
+            (Severity::Warning, 2, DiagnosticKind::OutOfRange { value: 0x1FF, max: 0xFF }),
+        )
+    );
+}
+
+#[test]
+fn test_diagnostic_display() {
+    let source = "CLS\n\tLD I, sprite ; draw it\n";
+    let diagnostic = assemble_file("game.asm", source).diagnostics.remove(0);
+    assert_eq!(
+        diagnostic.to_string(),
+        "error: undefined label 'sprite'
+ --> game.asm:2:8
+  |
+2 | \tLD I, sprite ; draw it
+  | \t      ^^^^^^"
+    );
+}
@@ -0,0 +1,48 @@
+//! Assembles a source file into a ROM.
+//!
+//! Usage: `chip8-asm SOURCE [-o ROM]`. Every error and warning is printed
+//! with the line it is on, and nothing is written if there are errors. The
+//! ROM goes next to the source with the extension `ch8` unless `-o` says
+//! otherwise.
+
+extern crate emu;
+
+use emu::assembler;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+const USAGE: &str = "Usage: chip8-asm SOURCE [-o ROM]";
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+fn main() {
+    let mut source = None;
+    let mut output = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" => output = Some(PathBuf::from(args.next().unwrap_or_else(|| usage()))),
+            _ => source = Some(arg),
+        }
+    }
+    let source = source.unwrap_or_else(|| usage());
+    let output = output.unwrap_or_else(|| PathBuf::from(&source).with_extension("ch8"));
+
+    let program = fs::read_to_string(&source).unwrap_or_else(|error| {
+        eprintln!("Could not read {}: {}", source, error);
+        process::exit(2);
+    });
+    let assembly = assembler::assemble_file(&source, &program);
+    for diagnostic in &assembly.diagnostics {
+        eprintln!("{}\n", diagnostic);
+    }
+    if assembly.has_errors() {
+        process::exit(1);
+    }
+    fs::write(&output, &assembly.rom).expect("Could not write the ROM.");
+}
@@ -0,0 +1,33 @@
+//! A Debug Adapter Protocol server for CHIP-8 programs.
+//!
+//! Editors start it and talk to it over stdin and stdout, or connect to it
+//! with `--port N`, which listens on localhost.
+
+extern crate emu;
+
+use emu::debugger::dap;
+use std::env;
+use std::io;
+use std::net::TcpListener;
+use std::process;
+
+const USAGE: &str = "Usage: chip8-dap [--port N]";
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+fn main() -> io::Result<()> {
+    let mut args = env::args().skip(1);
+    match (args.next().as_deref(), args.next()) {
+        (None, _) => dap::serve(io::stdin(), io::stdout()),
+        (Some("--port"), Some(port)) => {
+            let port: u16 = port.parse().unwrap_or_else(|_| usage());
+            let listener = TcpListener::bind(("127.0.0.1", port))?;
+            eprintln!("Listening on {}", listener.local_addr()?);
+            dap::serve_tcp(&listener)
+        }
+        _ => usage(),
+    }
+}
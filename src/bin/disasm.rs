@@ -0,0 +1,99 @@
+//! Prints a ROM as source that the assembler turns back into the same ROM.
+//!
+//! Usage: `chip8-disasm ROM [--platform P] [--entry ADDR]... [--coverage FILE] [--dot | --dot-dir DIR]`.
+//! The platform is plain CHIP-8 unless `--platform schip` or `xochip` says
+//! otherwise. Extra entry points and a coverage map recorded with
+//! `emu --coverage` help it find code that is only reached through `BNNN`.
+//!
+//! `--dot` prints the control-flow graph in Graphviz DOT instead, and
+//! `--dot-dir` writes it to `DIR/program.dot` along with one file per
+//! subroutine.
+
+extern crate emu;
+
+use emu::chip8::coverage::Coverage;
+use emu::chip8::Platform;
+use emu::disassembler::graph::Graph;
+use emu::disassembler::Disassembler;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+const USAGE: &str = "Usage: chip8-disasm ROM [--platform P] [--entry ADDR]... \
+    [--coverage FILE] [--dot | --dot-dir DIR]";
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+fn parse_address(text: &str) -> Option<usize> {
+    match text.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+fn main() {
+    let mut rom = None;
+    let mut platform = Platform::Chip8;
+    let mut entries = Vec::new();
+    let mut coverage = None;
+    let mut dot = false;
+    let mut dot_dir = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--platform" => {
+                platform = match args.next().as_deref() {
+                    Some("chip8") => Platform::Chip8,
+                    Some("schip") => Platform::SuperChip,
+                    Some("xochip") => Platform::XoChip,
+                    other => {
+                        eprintln!("Unknown platform {:?}, expected chip8, schip or xochip.", other);
+                        usage()
+                    }
+                }
+            }
+            "--entry" => {
+                let address = args.next().as_deref().and_then(parse_address);
+                entries.push(address.unwrap_or_else(|| usage()));
+            }
+            "--coverage" => coverage = Some(args.next().unwrap_or_else(|| usage())),
+            "--dot" => dot = true,
+            "--dot-dir" => dot_dir = Some(args.next().unwrap_or_else(|| usage())),
+            _ => rom = Some(arg),
+        }
+    }
+    let rom = rom.unwrap_or_else(|| usage());
+
+    let bytes = fs::read(&rom).unwrap_or_else(|error| {
+        eprintln!("Could not read {}: {}", rom, error);
+        process::exit(2);
+    });
+    let mut disassembler = Disassembler::new(&bytes, platform);
+    for address in entries {
+        disassembler.add_entry(address);
+    }
+    if let Some(path) = coverage {
+        let map = fs::read(&path).expect("Could not read the coverage map.");
+        disassembler.add_coverage(&Coverage::from_bytes(&map).expect("Could not load the coverage map."));
+    }
+    let disassembly = disassembler.disassemble();
+    let graph = Graph::new(&disassembly);
+    if let Some(dir) = dot_dir {
+        let dir = Path::new(&dir);
+        fs::create_dir_all(dir).expect("Could not create the directory.");
+        fs::write(dir.join("program.dot"), graph.to_dot()).expect("Could not write the graph.");
+        for &entry in &graph.subroutines {
+            let name = format!("sub_{:03X}.dot", entry);
+            let source = graph.subroutine_to_dot(entry).unwrap();
+            fs::write(dir.join(name), source).expect("Could not write the graph.");
+        }
+    } else if dot {
+        println!("{}", graph.to_dot());
+    } else {
+        println!("{}", disassembly);
+    }
+}
@@ -0,0 +1,92 @@
+//! Runs a ROM for a number of frames without a window and reports where its
+//! instructions went.
+//!
+//! Usage: `chip8-profile ROM [--frames N] [--platform P] [--ipf N] [--seed N] [--json]`.
+//! No keys are pressed, so a ROM waiting on `FX0A` stays there.
+
+extern crate emu;
+
+use emu::chip8::movie::Movie;
+use emu::chip8::profile::Profiler;
+use emu::chip8::Platform;
+use std::env;
+use std::fs;
+use std::process;
+
+const DEFAULT_FRAMES: u64 = 600;
+const USAGE: &str = "Usage: chip8-profile ROM [--frames N] [--platform P] [--ipf N] [--seed N] [--json]";
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+fn main() {
+    let mut rom = None;
+    let mut frames = DEFAULT_FRAMES;
+    let mut platform = Platform::Chip8;
+    let mut instructions_per_frame = None;
+    let mut seed = 0;
+    let mut json = false;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--frames" => {
+                let value = args.next().and_then(|value| value.parse().ok());
+                frames = value.unwrap_or_else(|| usage());
+            }
+            "--platform" => {
+                platform = match args.next().as_deref() {
+                    Some("chip8") => Platform::Chip8,
+                    Some("schip") => Platform::SuperChip,
+                    Some("xochip") => Platform::XoChip,
+                    other => {
+                        eprintln!("Unknown platform {:?}, expected chip8, schip or xochip.", other);
+                        usage()
+                    }
+                }
+            }
+            "--ipf" => {
+                let value = args.next().and_then(|value| value.parse().ok());
+                instructions_per_frame = Some(value.unwrap_or_else(|| usage()));
+            }
+            "--seed" => {
+                let value = args.next().and_then(|value| value.parse().ok());
+                seed = value.unwrap_or_else(|| usage());
+            }
+            "--json" => json = true,
+            _ => rom = Some(arg),
+        }
+    }
+    let rom = rom.unwrap_or_else(|| usage());
+
+    let bytes = fs::read(&rom).unwrap_or_else(|error| {
+        eprintln!("Could not read {}: {}", rom, error);
+        process::exit(2);
+    });
+    let instructions_per_frame = instructions_per_frame.unwrap_or_else(|| platform.instructions_per_frame());
+    let movie = Movie::new(&bytes, seed, platform, platform.quirks(), instructions_per_frame);
+    let mut chip8 = movie.start(&bytes).expect("Could not start the ROM.");
+    let mut profiler = Profiler::new();
+    let mut failed = false;
+    for _ in 0..frames {
+        if let Err(error) = profiler.run_frame(&mut chip8, instructions_per_frame) {
+            eprintln!("Execution halted: {}", error);
+            failed = true;
+            break;
+        }
+        if chip8.exited {
+            break;
+        }
+    }
+
+    let profile = profiler.profile();
+    if json {
+        println!("{}", profile.to_json());
+    } else {
+        println!("{}", profile);
+    }
+    if failed {
+        process::exit(1);
+    }
+}
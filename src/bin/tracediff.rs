@@ -0,0 +1,61 @@
+//! Finds the first cycle where two traces written with `--trace` disagree.
+//!
+//! Usage: `chip8-tracediff LEFT RIGHT [--context N]`. Exits with 1 when the
+//! traces diverge and 2 when they can't be compared, so it can be used in
+//! scripts.
+
+extern crate emu;
+
+use emu::chip8::trace::compare::{compare, Comparison};
+use std::env;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::process;
+
+const DEFAULT_CONTEXT: usize = 5;
+const USAGE: &str = "Usage: chip8-tracediff LEFT RIGHT [--context N]";
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+fn lines(path: &str) -> impl Iterator<Item = String> {
+    let file = File::open(path).unwrap_or_else(|error| {
+        eprintln!("Could not open {}: {}", path, error);
+        process::exit(2);
+    });
+    BufReader::new(file)
+        .lines()
+        .map(|line| line.expect("Could not read the trace."))
+}
+
+fn main() {
+    let mut paths = Vec::new();
+    let mut context = DEFAULT_CONTEXT;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--context" => {
+                let value = args.next().and_then(|value| value.parse().ok());
+                context = value.unwrap_or_else(|| usage());
+            }
+            _ => paths.push(arg),
+        }
+    }
+    if paths.len() != 2 {
+        usage();
+    }
+
+    match compare(lines(&paths[0]), lines(&paths[1]), context) {
+        Ok(Comparison::Identical { compared }) => println!("No differences in {} cycles", compared),
+        Ok(Comparison::Diverged(divergence)) => {
+            println!("{}", divergence);
+            process::exit(1);
+        }
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(2);
+        }
+    }
+}
@@ -0,0 +1,201 @@
+#[cfg(test)]
+mod tests;
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+/// Why the machine touched memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// Reading an instruction to execute.
+    Fetch,
+    /// Reading data, such as sprites, `FX65` or audio patterns.
+    Read,
+    /// Storing data, such as `FX33` or `FX55`.
+    Write,
+}
+
+/// The machine's view of memory. Every fetch, read and write made while
+/// executing goes through it.
+///
+/// The machine checks addresses against [`Bus::len`] before using them, so
+/// implementations only ever see addresses in range.
+pub trait Bus {
+    /// The size of the address space.
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Reads a byte for the machine, either a `Fetch` or a `Read`.
+    fn read(&mut self, address: usize, access: Access) -> u8;
+
+    /// Writes a byte for the machine.
+    fn write(&mut self, address: usize, value: u8);
+
+    /// Reads a byte without it counting as an access. Used by debuggers
+    /// and save states.
+    fn peek(&self, address: usize) -> u8;
+
+    /// Writes a byte without it counting as an access, ignoring any
+    /// protection. Used to load fonts, ROMs and save states.
+    fn poke(&mut self, address: usize, value: u8);
+
+    /// Copies out the whole address space.
+    fn dump(&self) -> Vec<u8> {
+        (0..self.len()).map(|address| self.peek(address)).collect()
+    }
+}
+
+impl<B: Bus + ?Sized> Bus for Box<B> {
+    fn len(&self) -> usize {
+        (**self).len()
+    }
+
+    fn read(&mut self, address: usize, access: Access) -> u8 {
+        (**self).read(address, access)
+    }
+
+    fn write(&mut self, address: usize, value: u8) {
+        (**self).write(address, value)
+    }
+
+    fn peek(&self, address: usize) -> u8 {
+        (**self).peek(address)
+    }
+
+    fn poke(&mut self, address: usize, value: u8) {
+        (**self).poke(address, value)
+    }
+}
+
+/// Plain memory, what every machine starts with.
+pub struct Ram {
+    bytes: Vec<u8>,
+}
+
+impl Ram {
+    pub fn new(size: usize) -> Self {
+        Ram { bytes: vec![0; size] }
+    }
+}
+
+impl Bus for Ram {
+    fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    fn read(&mut self, address: usize, _access: Access) -> u8 {
+        self.bytes[address]
+    }
+
+    fn write(&mut self, address: usize, value: u8) {
+        self.bytes[address] = value;
+    }
+
+    fn peek(&self, address: usize) -> u8 {
+        self.bytes[address]
+    }
+
+    fn poke(&mut self, address: usize, value: u8) {
+        self.bytes[address] = value;
+    }
+
+    fn dump(&self) -> Vec<u8> {
+        self.bytes.clone()
+    }
+}
+
+/// Drops writes to some regions, the way ROM would. Protecting the font
+/// area keeps a stray `FX55` from corrupting the digits.
+pub struct ReadOnly<B> {
+    inner: B,
+    regions: Vec<Range<usize>>,
+}
+
+impl<B: Bus> ReadOnly<B> {
+    pub fn new(inner: B, regions: Vec<Range<usize>>) -> Self {
+        ReadOnly { inner, regions }
+    }
+
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+}
+
+impl<B: Bus> Bus for ReadOnly<B> {
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn read(&mut self, address: usize, access: Access) -> u8 {
+        self.inner.read(address, access)
+    }
+
+    fn write(&mut self, address: usize, value: u8) {
+        if !self.regions.iter().any(|region| region.contains(&address)) {
+            self.inner.write(address, value);
+        }
+    }
+
+    fn peek(&self, address: usize) -> u8 {
+        self.inner.peek(address)
+    }
+
+    fn poke(&mut self, address: usize, value: u8) {
+        self.inner.poke(address, value)
+    }
+
+    fn dump(&self) -> Vec<u8> {
+        self.inner.dump()
+    }
+}
+
+/// Reports every access to `observer` once it has happened, with the byte
+/// that was read or written. Watchpoints, heatmaps and logging hang off
+/// this.
+pub struct Observed<B, F> {
+    inner: B,
+    observer: F,
+}
+
+impl<B: Bus, F: FnMut(usize, u8, Access)> Observed<B, F> {
+    pub fn new(inner: B, observer: F) -> Self {
+        Observed { inner, observer }
+    }
+
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+}
+
+impl<B: Bus, F: FnMut(usize, u8, Access)> Bus for Observed<B, F> {
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn read(&mut self, address: usize, access: Access) -> u8 {
+        let value = self.inner.read(address, access);
+        (self.observer)(address, value, access);
+        value
+    }
+
+    fn write(&mut self, address: usize, value: u8) {
+        self.inner.write(address, value);
+        (self.observer)(address, value, Access::Write);
+    }
+
+    fn peek(&self, address: usize) -> u8 {
+        self.inner.peek(address)
+    }
+
+    fn poke(&mut self, address: usize, value: u8) {
+        self.inner.poke(address, value)
+    }
+
+    fn dump(&self) -> Vec<u8> {
+        self.inner.dump()
+    }
+}
@@ -0,0 +1,85 @@
+use chip8::bus::{Access, Bus, Observed, Ram, ReadOnly};
+use chip8::{Chip8, Quirks};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+type Log = Rc<RefCell<Vec<(usize, u8, Access)>>>;
+
+fn observed_cpu(program: Vec<u8>) -> (Chip8, Log) {
+    let mut cpu = Chip8::new(Quirks::vip());
+    cpu.load_program(program);
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let sink = log.clone();
+    cpu.wrap_memory(|memory| {
+        Box::new(Observed::new(memory, move |address, value, access| {
+            sink.borrow_mut().push((address, value, access))
+        }))
+    });
+    (cpu, log)
+}
+
+#[test]
+fn test_accesses_are_tagged() {
+    //Store V0 as BCD at 0x300, then load it back into V0-V2
+    let (mut cpu, log) = observed_cpu(vec!(0x60, 0x7B, 0xA3, 0x00, 0xF0, 0x33, 0xF2, 0x65));
+    for _ in 0..4 {
+        cpu.step().unwrap();
+    }
+    let log = log.borrow();
+    assert_eq!(log[..2], [(0x200, 0x60, Access::Fetch), (0x201, 0x7B, Access::Fetch)]);
+    assert_eq!(
+        log[4..9],
+        [
+            (0x204, 0xF0, Access::Fetch),
+            (0x205, 0x33, Access::Fetch),
+            (0x300, 1, Access::Write),
+            (0x301, 2, Access::Write),
+            (0x302, 3, Access::Write),
+        ]
+    );
+    assert_eq!(log[11..], [(0x300, 1, Access::Read), (0x301, 2, Access::Read), (0x302, 3, Access::Read)]);
+}
+
+#[test]
+fn test_sprite_data_is_read() {
+    //Draw the "0" glyph
+    let (mut cpu, log) = observed_cpu(vec!(0xD0, 0x05));
+    cpu.step().unwrap();
+    let reads: Vec<_> = log.borrow().iter().filter(|entry| entry.2 == Access::Read).map(|entry| entry.0).collect();
+    assert_eq!(reads, vec!(0, 1, 2, 3, 4));
+}
+
+#[test]
+fn test_read_only_regions_drop_writes() {
+    //Point I at the "0" glyph and store V0 over it, with both fonts protected
+    let mut cpu = Chip8::new(Quirks::vip());
+    cpu.load_program(vec!(0x60, 0x42, 0xA0, 0x00, 0xF0, 0x55));
+    cpu.wrap_memory(|memory| Box::new(ReadOnly::new(memory, vec!(0..0x50, 0x50..0xF0))));
+    for _ in 0..3 {
+        cpu.step().unwrap();
+    }
+    assert_eq!(cpu.memory.peek(0), 0xF0);
+
+    //Loading and saving states is not stopped by the protection
+    cpu.memory.poke(0, 0x11);
+    assert_eq!(cpu.memory.peek(0), 0x11);
+}
+
+#[test]
+fn test_loading_state_keeps_wrapper() {
+    let (mut cpu, log) = observed_cpu(vec!(0x12, 0x00));
+    let state = cpu.save_state();
+    cpu.load_state(&state).unwrap();
+    cpu.step().unwrap();
+    assert_eq!(log.borrow().len(), 2);
+}
+
+#[test]
+fn test_ram() {
+    let mut ram = Ram::new(4);
+    ram.write(1, 7);
+    ram.poke(2, 9);
+    assert_eq!(ram.read(1, Access::Read), 7);
+    assert_eq!(ram.dump(), vec!(0, 7, 9, 0));
+    assert_eq!(ram.len(), 4);
+}
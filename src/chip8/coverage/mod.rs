@@ -0,0 +1,236 @@
+#[cfg(test)]
+mod tests;
+
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use chip8::bus::{Access, Observed};
+use chip8::state::{write_u16, write_u32, Reader};
+use chip8::{Chip8, StateError};
+use core::cell::RefCell;
+use core::error::Error;
+use core::fmt::{self, Display, Formatter};
+use core::ops::Range;
+
+const MAGIC: &[u8; 4] = b"C8CV";
+
+/// The version written by [`Coverage::to_bytes`].
+pub const VERSION: u16 = 1;
+
+/// Set for bytes fetched as part of an instruction.
+pub const FETCHED: u8 = 0b001;
+/// Set for bytes read as data, by `DXYN`, `FX65` and the like.
+pub const READ: u8 = 0b010;
+/// Set for bytes written, by `FX33`, `FX55` and the like.
+pub const WRITTEN: u8 = 0b100;
+
+/// One character per combination of flags, for [`Display`].
+const LEGEND: &[u8; 8] = b".CRXWMD*";
+const BYTES_PER_ROW: usize = 64;
+
+/// Why a coverage map could not be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CoverageError {
+    /// The data does not start with the coverage signature.
+    NotCoverage,
+    /// The map was written by a newer, or unknown, format version.
+    UnsupportedVersion(u16),
+    /// The data ends before the map is complete.
+    Truncated,
+    /// A field holds a value no map can have.
+    Invalid(&'static str),
+}
+
+impl Display for CoverageError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            CoverageError::NotCoverage => write!(f, "not a CHIP-8 coverage map"),
+            CoverageError::UnsupportedVersion(version) => write!(
+                f,
+                "coverage map version {} is not supported (expected at most {})",
+                version, VERSION
+            ),
+            CoverageError::Truncated => write!(f, "coverage map is truncated"),
+            CoverageError::Invalid(field) => write!(f, "coverage map has an invalid {}", field),
+        }
+    }
+}
+
+impl Error for CoverageError {}
+
+impl From<StateError> for CoverageError {
+    fn from(error: StateError) -> Self {
+        match error {
+            StateError::Truncated => CoverageError::Truncated,
+            StateError::Invalid(field) => CoverageError::Invalid(field),
+            StateError::NotAState | StateError::UnsupportedVersion(_) => CoverageError::Invalid("data"),
+        }
+    }
+}
+
+/// What the machine has done with each byte of memory, which tells code
+/// apart from data better than decoding alone.
+///
+/// `Display` draws the map 64 bytes to a row, one character per byte:
+/// `.` untouched, `C` executed, `R` read as data, `W` written, `X` executed
+/// and read, `M` executed and written, `D` read and written, `*` all three.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coverage {
+    flags: Vec<u8>,
+}
+
+/// How much of a region has been covered, in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Summary {
+    pub size: usize,
+    pub executed: usize,
+    pub read: usize,
+    pub written: usize,
+    pub untouched: usize,
+}
+
+impl Coverage {
+    /// An empty map for `size` bytes of memory.
+    pub fn new(size: usize) -> Self {
+        Coverage { flags: vec![0; size] }
+    }
+
+    /// Records every access `chip8` makes from now on into this map, which
+    /// stays readable through the returned handle.
+    pub fn record(mut self, chip8: &mut Chip8) -> Rc<RefCell<Coverage>> {
+        if self.flags.len() < chip8.memory.len() {
+            self.flags.resize(chip8.memory.len(), 0);
+        }
+        let coverage = Rc::new(RefCell::new(self));
+        let map = coverage.clone();
+        chip8.wrap_memory(|memory| {
+            Box::new(Observed::new(memory, move |address, _, access| {
+                map.borrow_mut().mark(address, access)
+            }))
+        });
+        coverage
+    }
+
+    pub fn mark(&mut self, address: usize, access: Access) {
+        if let Some(flags) = self.flags.get_mut(address) {
+            *flags |= match access {
+                Access::Fetch => FETCHED,
+                Access::Read => READ,
+                Access::Write => WRITTEN,
+            };
+        }
+    }
+
+    /// The flags for `address`, 0 beyond the end of the map.
+    pub fn flags(&self, address: usize) -> u8 {
+        self.flags.get(address).cloned().unwrap_or(0)
+    }
+
+    pub fn is_code(&self, address: usize) -> bool {
+        self.flags(address) & FETCHED != 0
+    }
+
+    /// Whether the byte was only ever read or written, never executed.
+    pub fn is_data(&self, address: usize) -> bool {
+        let flags = self.flags(address);
+        flags & FETCHED == 0 && flags & (READ | WRITTEN) != 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.flags.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.flags.is_empty()
+    }
+
+    /// Adds everything `other` covered, such as another play session.
+    pub fn merge(&mut self, other: &Coverage) {
+        if self.flags.len() < other.flags.len() {
+            self.flags.resize(other.flags.len(), 0);
+        }
+        for (flags, &more) in self.flags.iter_mut().zip(other.flags.iter()) {
+            *flags |= more;
+        }
+    }
+
+    /// Counts the covered bytes in `range`, such as the span of a ROM.
+    pub fn summary(&self, range: Range<usize>) -> Summary {
+        let range = range.start.min(self.flags.len())..range.end.min(self.flags.len());
+        let flags = &self.flags[range];
+        let count = |flag: u8| flags.iter().filter(|&&flags| flags & flag != 0).count();
+        Summary {
+            size: flags.len(),
+            executed: count(FETCHED),
+            read: count(READ),
+            written: count(WRITTEN),
+            untouched: flags.iter().filter(|&&flags| flags == 0).count(),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.flags.len() + 10);
+        out.extend_from_slice(MAGIC);
+        write_u16(&mut out, VERSION);
+        write_u32(&mut out, self.flags.len() as u32);
+        out.extend_from_slice(&self.flags);
+        out
+    }
+
+    /// Reads a map written by [`Coverage::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Coverage, CoverageError> {
+        let mut reader = Reader::new(bytes);
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(CoverageError::NotCoverage);
+        }
+        let version = reader.u16()?;
+        if version != VERSION {
+            return Err(CoverageError::UnsupportedVersion(version));
+        }
+        let size = reader.u32()? as usize;
+        let flags = reader.take(size)?;
+        if !reader.is_finished() {
+            return Err(CoverageError::Invalid("length"));
+        }
+        Ok(Coverage {
+            flags: flags.iter().map(|&flags| flags & (FETCHED | READ | WRITTEN)).collect(),
+        })
+    }
+}
+
+impl Display for Coverage {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for (row, flags) in self.flags.chunks(BYTES_PER_ROW).enumerate() {
+            if row > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "0x{:03X} ", row * BYTES_PER_ROW)?;
+            for &flags in flags {
+                write!(f, "{}", LEGEND[usize::from(flags & 0b111)] as char)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let share = |count: usize| {
+            if self.size == 0 {
+                0.0
+            } else {
+                100.0 * count as f64 / self.size as f64
+            }
+        };
+        write!(
+            f,
+            "{} bytes: {} executed ({:.1}%), {} read as data, {} written, {} untouched",
+            self.size,
+            self.executed,
+            share(self.executed),
+            self.read,
+            self.written,
+            self.untouched
+        )
+    }
+}
@@ -0,0 +1,72 @@
+use chip8::bus::Access;
+use chip8::coverage::{Coverage, CoverageError, FETCHED, READ, WRITTEN};
+use chip8::{Chip8, Quirks};
+
+//Draw the byte at 0x206, then store V0 as BCD over it
+fn covered_cpu() -> Coverage {
+    let mut cpu = Chip8::new(Quirks::vip());
+    cpu.load_program(vec!(0xA2, 0x06, 0xD0, 0x01, 0xF0, 0x33, 0xFF));
+    let coverage = Coverage::new(cpu.memory.len()).record(&mut cpu);
+    for _ in 0..3 {
+        cpu.step().unwrap();
+    }
+    let coverage = coverage.borrow().clone();
+    coverage
+}
+
+#[test]
+fn test_accesses_are_recorded() {
+    let coverage = covered_cpu();
+    assert!((0x200..0x206).all(|address| coverage.flags(address) == FETCHED));
+    assert_eq!(coverage.flags(0x206), READ | WRITTEN);
+    assert_eq!(coverage.flags(0x207), WRITTEN);
+    assert!(coverage.is_code(0x204));
+    assert!(coverage.is_data(0x206));
+    assert!(!coverage.is_data(0x20A));
+    let map = coverage.to_string();
+    assert_eq!(map.lines().nth(8).unwrap()[..16], *"0x200 CCCCCCDWW.");
+}
+
+#[test]
+fn test_summary() {
+    let summary = covered_cpu().summary(0x200..0x210);
+    assert_eq!((summary.size, summary.executed, summary.read, summary.written, summary.untouched), (16, 6, 1, 3, 7));
+    assert_eq!(
+        summary.to_string(),
+        "16 bytes: 6 executed (37.5%), 1 read as data, 3 written, 7 untouched"
+    );
+}
+
+#[test]
+fn test_merge() {
+    let mut coverage = Coverage::new(0x10);
+    coverage.mark(0x4, Access::Fetch);
+    let mut other = Coverage::new(0x20);
+    other.mark(0x4, Access::Read);
+    other.mark(0x18, Access::Write);
+    coverage.merge(&other);
+    assert_eq!(coverage.len(), 0x20);
+    assert_eq!(coverage.flags(0x4), FETCHED | READ);
+    assert_eq!(coverage.flags(0x18), WRITTEN);
+}
+
+#[test]
+fn test_bytes_round_trip() {
+    let coverage = covered_cpu();
+    let bytes = coverage.to_bytes();
+    assert_eq!(Coverage::from_bytes(&bytes), Ok(coverage));
+    assert_eq!(Coverage::from_bytes(b"C8ST"), Err(CoverageError::NotCoverage));
+    assert_eq!(Coverage::from_bytes(&bytes[..100]), Err(CoverageError::Truncated));
+    let mut newer = bytes.clone();
+    newer[5] = 9;
+    assert_eq!(Coverage::from_bytes(&newer), Err(CoverageError::UnsupportedVersion(9)));
+    newer[5] = 0;
+    assert_eq!(Coverage::from_bytes(&newer), Err(CoverageError::UnsupportedVersion(0)));
+
+    let mut longer = bytes.clone();
+    longer.push(0);
+    assert_eq!(Coverage::from_bytes(&longer), Err(CoverageError::Invalid("length")));
+    let mut huge = bytes[..10].to_vec();
+    huge[6..10].copy_from_slice(&[0xFF; 4]);
+    assert_eq!(Coverage::from_bytes(&huge), Err(CoverageError::Truncated));
+}
@@ -0,0 +1,76 @@
+use core::error::Error;
+use core::fmt::{Display, Formatter, Result};
+
+/// The reason an instruction could not be executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The opcode does not decode to any supported instruction.
+    UnknownOpcode,
+    /// `2NNN` was executed with every stack slot already in use.
+    StackOverflow,
+    /// `00EE` was executed with an empty stack.
+    StackUnderflow,
+    /// The instruction tried to read or write outside of memory.
+    MemoryOutOfBounds,
+}
+
+/// An instruction that failed to execute.
+///
+/// The machine is left exactly as it was before the failing instruction,
+/// so `pc` still points at it and the error can be reported or the state
+/// inspected without anything having been half-applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExecutionError {
+    pub kind: ErrorKind,
+    pub pc: usize,
+    pub opcode: u16,
+    pub address: Option<usize>,
+}
+
+impl ExecutionError {
+    pub fn new(kind: ErrorKind, pc: usize, opcode: u16) -> Self {
+        ExecutionError {
+            kind,
+            pc,
+            opcode,
+            address: None,
+        }
+    }
+
+    pub fn at_address(kind: ErrorKind, pc: usize, opcode: u16, address: usize) -> Self {
+        ExecutionError {
+            kind,
+            pc,
+            opcode,
+            address: Some(address),
+        }
+    }
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let description = match *self {
+            ErrorKind::UnknownOpcode => "unrecognised instruction",
+            ErrorKind::StackOverflow => "stack overflow",
+            ErrorKind::StackUnderflow => "return with empty stack",
+            ErrorKind::MemoryOutOfBounds => "memory access out of bounds",
+        };
+        write!(f, "{}", description)
+    }
+}
+
+impl Display for ExecutionError {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(
+            f,
+            "{} at 0x{:03X} [0x{:04X}]",
+            self.kind, self.pc, self.opcode
+        )?;
+        if let Some(address) = self.address {
+            write!(f, " (address 0x{:03X})", address)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ExecutionError {}
@@ -1,22 +1,62 @@
 #[cfg(test)]
 mod tests;
 
-mod opcode;
+pub mod bus;
+pub mod coverage;
+mod error;
+pub mod movie;
+pub mod opcode;
+mod platform;
+pub mod profile;
+mod quirks;
+pub mod random;
+pub mod rewind;
+pub mod state;
+#[cfg(feature = "trace")]
+pub mod trace;
 
-extern crate rand;
-use rand::prelude::random;
+pub use self::bus::{Access, Bus};
+pub use self::coverage::Coverage;
+pub use self::error::{ErrorKind, ExecutionError};
+pub use self::platform::Platform;
+pub use self::quirks::{MemoryIncrement, Quirks};
+pub use self::random::RandomSource;
+pub use self::state::StateError;
+#[cfg(feature = "trace")]
+pub use self::trace::Tracer;
+use self::bus::Ram;
+use self::opcode::Instruction;
+use self::random::SplitMix;
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{self, Display, Formatter};
+use core::mem;
 use sprite;
-use std::fs::File;
-use std::io::prelude::Read;
-use std::fmt::{Display, Formatter, Result};
 
 const WIDTH: usize = 64;
 const HEIGHT: usize = 32;
+const HIRES_WIDTH: usize = 128;
+const HIRES_HEIGHT: usize = 64;
+const BIG_FONT_ADDRESS: usize = 0x50;
 const FIRST_ADDRESS: usize = 0x200;
-const MEM_SIZE: usize = 4096;
+const DEFAULT_PITCH: u8 = 64;
 
+/// Where `FX0A` is in waiting for a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum KeyWait {
+    Idle,
+    /// Halted until any key goes down.
+    Press { x: usize },
+    /// Halted until `key` comes back up, which stores it in VX.
+    Release { x: usize, key: usize },
+}
+
+#[allow(non_snake_case)]
 pub struct Chip8 {
-    pub memory: [u8; MEM_SIZE],
+    /// Every access made while executing goes through the bus. See
+    /// [`Chip8::wrap_memory`] to watch or protect it.
+    pub memory: Box<dyn Bus>,
     pub V: [u8; 16],
     pub I: u16,
     pub pc: usize,
@@ -24,14 +64,36 @@ pub struct Chip8 {
     pub delay: u8,
     pub sound: u8,
     pub stack: [u16; 16],
-    pub keyboard: [bool; 16],
-    pub graphics: [bool; WIDTH * HEIGHT],
+    keyboard: [bool; 16],
+    /// One bit per bitplane for each pixel. Only XO-CHIP uses the second
+    /// plane.
+    pub graphics: [u8; HIRES_WIDTH * HIRES_HEIGHT],
+    pub hires: bool,
+    pub planes: u8,
+    /// The XO-CHIP sample loaded by `F002` and its pitch from `FX3A`. The
+    /// windowed frontend has no audio output and only prints `BEEP`.
+    pub audio_pattern: [u8; 16],
+    pub pitch: u8,
+    pub flags: [u8; 16],
+    pub exited: bool,
+    pub platform: Platform,
+    pub quirks: Quirks,
+    pub rng: Box<dyn RandomSource>,
+    /// Sees every instruction before it runs, when set.
+    #[cfg(feature = "trace")]
+    pub tracer: Option<Tracer>,
+    vblank: bool,
+    key_wait: KeyWait,
 }
 
 impl Chip8 {
-    pub fn new() -> Self {
+    pub fn new(quirks: Quirks) -> Self {
+        Chip8::with_platform(Platform::Chip8, quirks)
+    }
+
+    pub fn with_platform(platform: Platform, quirks: Quirks) -> Self {
         let mut cpu = Chip8 {
-            memory: [0; MEM_SIZE],
+            memory: Box::new(Ram::new(platform.memory_size())),
             V: [0; 16],
             I: 0,
             pc: FIRST_ADDRESS,
@@ -40,82 +102,247 @@ impl Chip8 {
             sound: 0,
             stack: [0; 16],
             keyboard: [false; 16],
-            graphics: [false; WIDTH * HEIGHT],
+            graphics: [0; HIRES_WIDTH * HIRES_HEIGHT],
+            hires: false,
+            planes: 0b01,
+            audio_pattern: [0; 16],
+            pitch: DEFAULT_PITCH,
+            flags: [0; 16],
+            exited: false,
+            platform,
+            quirks,
+            rng: Box::new(SplitMix::default()),
+            #[cfg(feature = "trace")]
+            tracer: None,
+            vblank: true,
+            key_wait: KeyWait::Idle,
         };
         cpu.init();
         cpu
     }
 
     pub fn load_program(&mut self, program: Vec<u8>) {
-        if program.len() > self.memory.len() {
+        if program.len() > self.memory.len() - FIRST_ADDRESS {
             panic!("Program is too large for memory.");
         }
 
-        for i in 0..program.len() {
-            self.memory[FIRST_ADDRESS + i] = program[i];
-        }
+        self.poke_bytes(FIRST_ADDRESS, &program);
+    }
+
+    /// Copies a ROM into memory at 0x200, dropping whatever does not fit.
+    pub fn load_rom(&mut self, rom: &[u8]) {
+        let size = rom.len().min(self.memory.len() - FIRST_ADDRESS);
+        self.poke_bytes(FIRST_ADDRESS, &rom[..size]);
+    }
+
+    /// Replaces the memory bus with a wrapper around it, such as
+    /// [`bus::ReadOnly`] or [`bus::Observed`].
+    pub fn wrap_memory<F>(&mut self, wrap: F)
+    where
+        F: FnOnce(Box<dyn Bus>) -> Box<dyn Bus>,
+    {
+        let memory = mem::replace(&mut self.memory, Box::new(Ram::new(0)));
+        self.memory = wrap(memory);
     }
 
+    #[cfg(feature = "std")]
     pub fn load(&mut self, rom: String) {
-        let mut file = File::open(rom).unwrap();
-        let mut buffer: [u8; MEM_SIZE - FIRST_ADDRESS] = [0; MEM_SIZE - FIRST_ADDRESS];
-        if let Ok(size) = file.read(&mut buffer) {
-            for i in 0..size {
-                self.memory[FIRST_ADDRESS + i] = buffer[i];
-            }
-        }
+        let buffer = ::std::fs::read(rom).unwrap();
+        self.load_rom(&buffer);
     }
 
+    #[cfg(feature = "debugger")]
     pub fn debug_memory(&self) {
+        self.debug_memory_covered(&Coverage::new(0));
+    }
+
+    /// Like [`Chip8::debug_memory`], but words that `coverage` saw used as
+    /// data and never executed are printed as data instead of decoded.
+    #[cfg(feature = "debugger")]
+    pub fn debug_memory_covered(&self, coverage: &Coverage) {
         let mut x = 0x200;
-        while x < MEM_SIZE {
-            let high_order: u16 = u16::from(self.memory[x]) << 8;
-            let low_order: u16 = u16::from(self.memory[x + 1]);
-            let opcode = high_order | low_order;
+        while x + 1 < self.memory.len() {
+            let opcode = self.word_at(x);
             if opcode == 0x0000 {
                 x += 2;
                 continue;
             }
+            let is_data = !coverage.is_code(x)
+                && !coverage.is_code(x + 1)
+                && (coverage.is_data(x) || coverage.is_data(x + 1));
             println!(
                 "0x{:03X}-0x{:03X} [0x{:02X}{:02X}] - {}",
                 x,
                 x + 1,
-                self.memory[x],
-                self.memory[x + 1],
-                self.print_opcode(opcode)
+                self.memory.peek(x),
+                self.memory.peek(x + 1),
+                if is_data { "data".to_string() } else { self.print_opcode(opcode) }
             );
             x += 2;
         }
-        println!("");
+        println!();
     }
 
+    #[cfg(feature = "debugger")]
     pub fn print_display(&self) {
-        for y in 0..HEIGHT {
-            for x in 0..WIDTH {
+        for y in 0..self.height() {
+            for x in 0..self.width() {
                 print!("{}", if self.pixel_at(x, y) { 1 } else { 0 });
             }
-            println!("");
+            println!();
         }
-        println!("");
+        println!();
+    }
+
+    /// Snapshots the whole machine, see [`state::save`].
+    pub fn save_state(&self) -> Vec<u8> {
+        state::save(self)
     }
 
-    pub fn emulate_cycle(&mut self) {
-        let opcode: u16 = self.fetch_opcode();
-        // println!("Opcode: {:04X}", opcode);
-        self.decode_opcode(opcode);
+    /// Restores a snapshot taken by [`Chip8::save_state`], leaving the
+    /// machine untouched if it cannot be read.
+    pub fn load_state(&mut self, bytes: &[u8]) -> Result<(), StateError> {
+        state::load(self, bytes)
+    }
+
+    /// Executes a single instruction. Timers are left alone, see
+    /// [`Chip8::run_frame`].
+    pub fn step(&mut self) -> Result<(), ExecutionError> {
+        if self.exited || self.is_waiting_for_key() {
+            return Ok(());
+        }
+        let pc = self.pc;
+        let opcode: u16 = self.fetch_opcode()?;
+        #[cfg(feature = "trace")]
+        self.trace(opcode);
         self.increment_program_counter();
-        self.decrement_timers();
+        let result = self.decode_opcode(pc, opcode);
+        #[cfg(feature = "trace")]
+        if let Some(ref mut tracer) = self.tracer {
+            tracer.end();
+        }
+        if let Err(error) = result {
+            self.pc = pc;
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    /// Executes a single instruction, the same as [`Chip8::step`]. Timers now
+    /// tick in [`Chip8::run_frame`] rather than after every instruction.
+    pub fn emulate_cycle(&mut self) -> Result<(), ExecutionError> {
+        self.step()
+    }
+
+    #[cfg(feature = "trace")]
+    fn trace(&mut self, opcode: u16) {
+        if let Some(mut tracer) = self.tracer.take() {
+            tracer.begin(self, opcode);
+            self.tracer = Some(tracer);
+        }
+    }
+
+    /// Runs one 60 Hz frame: executes `instructions_per_frame` instructions
+    /// and then ticks the timers once. Stops early if an instruction fails,
+    /// in which case the timers are not ticked.
+    pub fn run_frame(&mut self, instructions_per_frame: usize) -> Result<(), ExecutionError> {
+        for _ in 0..instructions_per_frame {
+            if self.exited || self.is_waiting_for_key() {
+                break;
+            }
+            self.step()?;
+        }
+        self.tick_timers();
+        Ok(())
+    }
+
+    /// Presses keypad key `key`, 0x0 to 0xF. Anything else is ignored.
+    pub fn key_down(&mut self, key: usize) {
+        if key >= self.keyboard.len() {
+            return;
+        }
+        self.keyboard[key] = true;
+        if let KeyWait::Press { x } = self.key_wait {
+            self.key_wait = KeyWait::Release { x, key };
+        }
+    }
+
+    /// Releases keypad key `key`. Completes an `FX0A` waiting on it.
+    pub fn key_up(&mut self, key: usize) {
+        if key >= self.keyboard.len() {
+            return;
+        }
+        self.keyboard[key] = false;
+        if let KeyWait::Release { x, key: awaited } = self.key_wait {
+            if awaited == key {
+                self.V[x] = key as u8;
+                self.key_wait = KeyWait::Idle;
+            }
+        }
+    }
+
+    pub fn is_key_down(&self, key: usize) -> bool {
+        self.keyboard.get(key).is_some_and(|&down| down)
+    }
+
+    /// Whether execution is halted on `FX0A` until a key is pressed and
+    /// released. Timers keep running meanwhile.
+    pub fn is_waiting_for_key(&self) -> bool {
+        self.key_wait != KeyWait::Idle
+    }
+
+    /// Decrements the delay and sound timers and marks the start of a new
+    /// frame. Called at 60 Hz.
+    pub fn tick_timers(&mut self) {
+        self.vblank = true;
+
+        if self.sound > 0 {
+            self.sound -= 1;
+        }
+
+        if self.delay > 0 {
+            self.delay -= 1;
+        }
+    }
+
+    /// Width of the display in the current resolution mode.
+    pub fn width(&self) -> usize {
+        if self.hires {
+            HIRES_WIDTH
+        } else {
+            WIDTH
+        }
+    }
+
+    /// Height of the display in the current resolution mode.
+    pub fn height(&self) -> usize {
+        if self.hires {
+            HIRES_HEIGHT
+        } else {
+            HEIGHT
+        }
     }
 
     pub fn pixel_at(&self, x: usize, y: usize) -> bool {
-        self.graphics[x + y * WIDTH]
+        self.graphics[x + y * self.width()] != 0
+    }
+
+    /// The colour index of a pixel, combining its bits from each plane.
+    pub fn pixel_color_at(&self, x: usize, y: usize) -> u8 {
+        self.graphics[x + y * self.width()]
+    }
+
+    /// Audio playback rate in Hz for the current pitch.
+    #[cfg(feature = "std")]
+    pub fn playback_rate(&self) -> f64 {
+        4000.0 * 2f64.powf((f64::from(self.pitch) - 64.0) / 48.0)
     }
 
     pub fn pixel_byte_at(&self, x: usize, y: usize) -> [bool; 8] {
         let mut pixel_byte: [bool; 8] = [false; 8];
-        for i in 0..8 {
-            let x_shifted = (x + i) % WIDTH;
-            pixel_byte[i] = self.pixel_at(x_shifted, y);
+        for (i, pixel) in pixel_byte.iter_mut().enumerate() {
+            let x_shifted = (x + i) % self.width();
+            *pixel = self.pixel_at(x_shifted, y);
         }
         pixel_byte
     }
@@ -125,248 +352,339 @@ impl Chip8 {
 impl Chip8 {
     fn init(&mut self) {
         let fonts = sprite::get_font_set();
-        for i in 0..fonts.len() {
-            for byte in 0..fonts[i].len() {
-                self.memory[byte + i * fonts[i].len()] = fonts[i][byte];
-            }
+        for (i, font) in fonts.iter().enumerate() {
+            self.poke_bytes(i * font.len(), font);
+        }
+        let big_fonts = sprite::get_big_font_set();
+        for (i, font) in big_fonts.iter().enumerate() {
+            self.poke_bytes(BIG_FONT_ADDRESS + i * font.len(), font);
         }
     }
 
-    fn fetch_opcode(&self) -> u16 {
-        let high_order: u16 = u16::from(self.memory[self.pc]) << 8;
-        let low_order: u16 = u16::from(self.memory[self.pc + 1]);
-        high_order | low_order
+    fn poke_bytes(&mut self, start: usize, bytes: &[u8]) {
+        for (offset, &byte) in bytes.iter().enumerate() {
+            self.memory.poke(start + offset, byte);
+        }
     }
 
-    fn decode_opcode(&mut self, opcode: u16) {
-        // println!("Executing: {}", self.print_opcode(opcode));
-        
-        let opcode = opcode::Opcode::from(opcode);
-        let high_byte = opcode.high_byte;
-        let low_byte = opcode.low_byte;
-        let instruction = opcode.instruction;
-        let x = opcode.x;
-        let y = opcode.y;
-        let n = opcode.n;
-        let nnn = opcode.nnn;
+    fn fetch_opcode(&mut self) -> Result<u16, ExecutionError> {
+        if self.pc + 1 >= self.memory.len() {
+            return Err(ExecutionError::at_address(
+                ErrorKind::MemoryOutOfBounds,
+                self.pc,
+                0,
+                self.pc + 1,
+            ));
+        }
+        Ok(self.fetch_word(self.pc))
+    }
+
+    fn decode_opcode(&mut self, pc: usize, opcode: u16) -> Result<(), ExecutionError> {
+        let fault = |kind: ErrorKind| ExecutionError::new(kind, pc, opcode);
+        let out_of_bounds =
+            |address: usize| ExecutionError::at_address(ErrorKind::MemoryOutOfBounds, pc, opcode, address);
+
+        let decoded = if opcode == 0xF000 && self.platform >= Platform::XoChip {
+            if self.pc + 1 >= self.memory.len() {
+                return Err(out_of_bounds(self.pc + 1));
+            }
+            let next = self.fetch_word(self.pc);
+            Instruction::decode_pair(opcode, next)
+        } else {
+            Instruction::decode(opcode)
+        };
+        let instruction = match decoded {
+            Ok(instruction) => instruction,
+            Err(_) => return Err(fault(ErrorKind::UnknownOpcode)),
+        };
+        if instruction.platform() > self.platform {
+            return Err(fault(ErrorKind::UnknownOpcode));
+        }
 
         match instruction {
-            0x0 => {
-                match low_byte {
-                    0xE0 => {
-                        //Clear display
-                        for y in 0..HEIGHT {
-                            for x in 0..WIDTH {
-                                self.graphics[x + y * 64] = false;
-                            }
-                        }
-                    }
-                    0xEE => {
-                        //Return from subroutine
-                        self.sp -= 1;
-                        self.pc = usize::from(self.stack[self.sp]);
-                    }
-                    _ => panic!("Unrecognised instruction."),
+            Instruction::Sys { .. } => return Err(fault(ErrorKind::UnknownOpcode)),
+            Instruction::Clear => {
+                self.clear_display();
+            }
+            Instruction::Return => {
+                if self.sp == 0 {
+                    return Err(fault(ErrorKind::StackUnderflow));
                 }
+                self.sp -= 1;
+                self.pc = usize::from(self.stack[self.sp]);
             }
-            0x1 => {
-                //Jump to address
+            Instruction::Jump { nnn } => {
                 self.pc = usize::from(nnn);
-                self.decrement_program_counter();
             }
-            0x2 => {
-                //Call subroutine at address
+            Instruction::Call { nnn } => {
+                if self.sp >= self.stack.len() {
+                    return Err(fault(ErrorKind::StackOverflow));
+                }
                 self.stack[self.sp] = self.pc as u16;
                 self.sp += 1;
                 self.pc = usize::from(nnn);
-                self.decrement_program_counter();
             }
-            0x3 => {
-                //Conditional next instruction skip
-                if self.V[x] == low_byte {
-                    self.increment_program_counter();
+            Instruction::SkipEqImm { x, nn } => {
+                if self.V[x] == nn {
+                    self.skip_next_instruction();
                 }
             }
-            0x4 => {
-                if self.V[x] != low_byte {
-                    self.increment_program_counter();
+            Instruction::SkipNeImm { x, nn } => {
+                if self.V[x] != nn {
+                    self.skip_next_instruction();
                 }
             }
-            0x5 => {
+            Instruction::SkipEqReg { x, y } => {
                 if self.V[x] == self.V[y] {
-                    self.increment_program_counter();
+                    self.skip_next_instruction();
                 }
             }
-            0x6 => {
-                self.V[x] = low_byte;
+            Instruction::LoadImm { x, nn } => {
+                self.V[x] = nn;
             }
-            0x7 => {
-                let mut vx = self.V[x] as u16;
-                vx += low_byte as u16;
-                self.V[x] = vx as u8;
+            Instruction::AddImm { x, nn } => {
+                self.V[x] = self.V[x].wrapping_add(nn);
             }
-            0x8 => {
-                match n {
-                    0x0 => {
-                        self.V[x] = self.V[y];
-                    }
-                    0x1 => {
-                        self.V[x] = self.V[x] | self.V[y];
-                    }
-                    0x2 => {
-                        self.V[x] = self.V[x] & self.V[y];
-                    }
-                    0x3 => {
-                        self.V[x] = self.V[x] ^ self.V[y];
-                    }
-                    0x4 => {
-                        let result: u16 = u16::from(self.V[x] as u16 + self.V[y] as u16);
-                        let mut vf: u8 = 0;
-                        if result > 0xFF {
-                            vf = 1;
-                        }
-                        self.V[0xF as usize] = vf;
-                        self.V[x] += (result & 0x00FF) as u8;
-                    }
-                    0x5 => {
-                        let mut vf: u8 = 0;
-                        if self.V[x] > self.V[y] {
-                            vf = 1;
-                            self.V[x] -= self.V[y]; //Should this be conditional?
-                        }
-                        self.V[0xF as usize] = vf;
-                    }
-                    0x6 => {
-                        let mut vf: u8 = 0;
-                        if self.V[x] & 0x1 == 1 {
-                            vf = 1;
-                        }
-                        self.V[0xF as usize] = vf;
-                        self.V[x] = self.V[x] >> 1;
-                    }
-                    0x7 => {
-                        let mut vf: u8 = 0;
-                        if self.V[y] > self.V[x] {
-                            vf = 1;
-                            self.V[x] = self.V[y] - self.V[x]; //Should this be conditional?
-                        }
-                        self.V[0xF as usize] = vf;
-                    }
-                    0xE => {
-                        let mut vf: u8 = 0;
-                        if self.V[x] & 0x80 == 1 {
-                            vf = 1;
-                        }
-                        self.V[0xF as usize] = vf;
-                        self.V[x] = self.V[x] << 1;
-                    }
-                    _ => panic!("Unrecognised instruction."),
-                }
+            Instruction::LoadReg { x, y } => {
+                self.V[x] = self.V[y];
+            }
+            Instruction::Or { x, y } => {
+                self.V[x] |= self.V[y];
+                self.reset_flag_after_logic();
+            }
+            Instruction::And { x, y } => {
+                self.V[x] &= self.V[y];
+                self.reset_flag_after_logic();
+            }
+            Instruction::Xor { x, y } => {
+                self.V[x] ^= self.V[y];
+                self.reset_flag_after_logic();
+            }
+            //VF is always written last so that it wins when X is F
+            Instruction::AddRegReg { x, y } => {
+                let (result, carry) = self.V[x].overflowing_add(self.V[y]);
+                self.V[x] = result;
+                self.V[0xF] = carry as u8;
+            }
+            Instruction::Sub { x, y } => {
+                let (result, borrow) = self.V[x].overflowing_sub(self.V[y]);
+                self.V[x] = result;
+                self.V[0xF] = !borrow as u8;
+            }
+            Instruction::ShiftRight { x, y } => {
+                let source = if self.quirks.shift_uses_vy { self.V[y] } else { self.V[x] };
+                self.V[x] = source >> 1;
+                self.V[0xF] = source & 0x1;
+            }
+            Instruction::SubN { x, y } => {
+                let (result, borrow) = self.V[y].overflowing_sub(self.V[x]);
+                self.V[x] = result;
+                self.V[0xF] = !borrow as u8;
             }
-            0x9 => {
+            Instruction::ShiftLeft { x, y } => {
+                let source = if self.quirks.shift_uses_vy { self.V[y] } else { self.V[x] };
+                self.V[x] = source << 1;
+                self.V[0xF] = (source & 0x80) >> 7;
+            }
+            Instruction::SkipNeReg { x, y } => {
                 if self.V[x] != self.V[y] {
-                    self.increment_program_counter();
+                    self.skip_next_instruction();
                 }
             }
-            0xA => {
+            Instruction::LoadI { nnn } => {
                 self.I = nnn;
             }
-            0xB => {
-                self.pc = usize::from(u16::from(self.V[0]) + nnn);
-                self.decrement_program_counter()
-            }
-            0xC => {
-                //Random
-                let random_byte: u8 = random();
-                self.V[x] = random_byte & low_byte;
-            }
-            0xD => {
-                let (x, y) = (usize::from(self.V[x]), usize::from(self.V[y]));
-                for i in 0..n {
-                    let byte: u8 = self.memory[usize::from(self.I) + i];
-                    // println!("New display byte {:b}", byte);
-                    let pixels: u8 = Chip8::byte_from_bool_array(self.pixel_byte_at(x, y + i));
-                    // println!("Current pixels {:b}", pixels);
-                    let new_pixels = byte ^ pixels;
-                    // println!("New pixels {:b}", new_pixels);
-                    if pixels != new_pixels {
-                        self.V[0xF] = 1;
-                    } else {
-                        self.V[0xF] = 0;
+            Instruction::JumpV0 { nnn } => {
+                let offset = if self.quirks.jump_uses_vx {
+                    self.V[usize::from(nnn >> 8)]
+                } else {
+                    self.V[0]
+                };
+                self.pc = usize::from(u16::from(offset) + nnn);
+            }
+            Instruction::Random { x, nn } => {
+                let random_byte: u8 = self.rng.next_byte();
+                self.V[x] = random_byte & nn;
+            }
+            Instruction::Draw { x, y, n } => {
+                //DXY0 draws a 16x16 sprite, two bytes per row
+                let (rows, sprite_width) = if n == 0 && self.platform >= Platform::SuperChip {
+                    (16, 16)
+                } else {
+                    (n, 8)
+                };
+                //Each selected plane takes its own copy of the sprite data
+                let length = rows * sprite_width / 8;
+                let total_length = length * self.planes.count_ones() as usize;
+                let start = usize::from(self.I);
+                if total_length > 0 && start + total_length > self.memory.len() {
+                    return Err(out_of_bounds(start + total_length - 1));
+                }
+                if self.quirks.display_wait {
+                    if !self.vblank {
+                        //Try again once the next frame has started
+                        self.pc = pc;
+                        return Ok(());
+                    }
+                    self.vblank = false;
+                }
+                let x = usize::from(self.V[x]) % self.width();
+                let y = usize::from(self.V[y]) % self.height();
+                let mut collision = false;
+                let mut data = start;
+                let selected = self.planes;
+                for plane in [0b01, 0b10].iter().filter(|&&plane| selected & plane != 0) {
+                    for row in 0..rows {
+                        let bits = if sprite_width == 16 {
+                            u16::from(self.memory.read(data + row * 2, Access::Read)) << 8
+                                | u16::from(self.memory.read(data + row * 2 + 1, Access::Read))
+                        } else {
+                            u16::from(self.memory.read(data + row, Access::Read)) << 8
+                        };
+                        collision |= self.draw_sprite_row(x, y + row, bits, *plane);
                     }
-                    self.update_pixels_at(x, y + i, Chip8::bool_array_from_byte(new_pixels));
+                    data += length;
                 }
+                self.V[0xF] = collision as u8;
             }
-            0xE => {
-                let key = self.V[x] as usize;
-                match low_byte {
-                    0x9E => {
-                        if self.keyboard[key] {
-                            self.increment_program_counter();
-                        }
-                    }
-                    0xA1 => {
-                        if !self.keyboard[key] {
-                            self.increment_program_counter();
-                        }
-                    }
-                    _ => panic!("Unrecognised instruction."),
+            Instruction::SkipKey { x } => {
+                if self.keyboard[(self.V[x] & 0xF) as usize] {
+                    self.skip_next_instruction();
                 }
             }
-            0xF => match low_byte {
-                0x07 => {
-                    self.V[x] = self.delay;
+            Instruction::SkipNotKey { x } => {
+                if !self.keyboard[(self.V[x] & 0xF) as usize] {
+                    self.skip_next_instruction();
                 }
-                0x0A => {
-                    let mut key_pressed = false;
-                    for i in 0..self.keyboard.len() {
-                        if self.keyboard[i] {
-                            self.V[x] = i as u8;
-                            key_pressed = true;
-                            break;
-                        }
-                    }
-                    if !key_pressed {
-                        self.increment_program_counter();
-                    }
+            }
+            Instruction::LoadDelay { x } => {
+                self.V[x] = self.delay;
+            }
+            Instruction::WaitKey { x } => {
+                //Like the VIP, a key already held counts as the press
+                self.key_wait = match self.keyboard.iter().position(|&pressed| pressed) {
+                    Some(key) => KeyWait::Release { x, key },
+                    None => KeyWait::Press { x },
+                };
+            }
+            Instruction::SetDelay { x } => {
+                self.delay = self.V[x];
+            }
+            Instruction::SetSound { x } => {
+                self.sound = self.V[x];
+            }
+            Instruction::AddI { x } => {
+                self.I = self.I.wrapping_add(u16::from(self.V[x]));
+            }
+            Instruction::LoadFont { x } => {
+                let character = u16::from(self.V[x]);
+                self.I = character * 5;
+            }
+            Instruction::StoreBcd { x } => {
+                let index = self.I as usize;
+                if index + 2 >= self.memory.len() {
+                    return Err(out_of_bounds(index + 2));
+                }
+                let value = self.V[x];
+                self.store(index, value / 100);
+                self.store(index + 1, (value / 10) % 10);
+                self.store(index + 2, value % 10);
+            }
+            Instruction::StoreRegisters { x } => {
+                let start = usize::from(self.I);
+                if start + x >= self.memory.len() {
+                    return Err(out_of_bounds(start + x));
+                }
+                for register in 0..=x {
+                    self.store(start + register, self.V[register]);
+                }
+                self.increment_index_after_transfer(x);
+            }
+            Instruction::LoadRegisters { x } => {
+                let start = usize::from(self.I);
+                if start + x >= self.memory.len() {
+                    return Err(out_of_bounds(start + x));
                 }
-                0x15 => {
-                    self.delay = self.V[x];
+                for register in 0..=x {
+                    self.V[register] = self.memory.read(start + register, Access::Read);
                 }
-                0x18 => {
-                    self.sound = self.V[x];
+                self.increment_index_after_transfer(x);
+            }
+            Instruction::ScrollDown { n } => {
+                self.scroll(0, n as isize);
+            }
+            Instruction::ScrollRight => {
+                self.scroll(4, 0);
+            }
+            Instruction::ScrollLeft => {
+                self.scroll(-4, 0);
+            }
+            Instruction::Exit => {
+                self.exited = true;
+            }
+            Instruction::LowRes => {
+                self.hires = false;
+                self.clear_display();
+            }
+            Instruction::HighRes => {
+                self.hires = true;
+                self.clear_display();
+            }
+            Instruction::LoadBigFont { x } => {
+                let character = usize::from(self.V[x] & 0xF);
+                self.I = (BIG_FONT_ADDRESS + character * 10) as u16;
+            }
+            Instruction::StoreFlags { x } => {
+                self.flags[..=x].copy_from_slice(&self.V[..=x]);
+            }
+            Instruction::LoadFlags { x } => {
+                self.V[..=x].copy_from_slice(&self.flags[..=x]);
+            }
+            Instruction::ScrollUp { n } => {
+                self.scroll(0, -(n as isize));
+            }
+            Instruction::SaveRange { x, y } => {
+                let start = usize::from(self.I);
+                let count = x.abs_diff(y);
+                if start + count >= self.memory.len() {
+                    return Err(out_of_bounds(start + count));
                 }
-                0x1E => {
-                    self.I += u16::from(self.V[x]);
+                for (offset, register) in Chip8::register_range(x, y).enumerate() {
+                    self.store(start + offset, self.V[register]);
                 }
-                0x29 => {
-                    let character = u16::from(self.V[x]);
-                    self.I = character * 5;
+            }
+            Instruction::LoadRange { x, y } => {
+                let start = usize::from(self.I);
+                let count = x.abs_diff(y);
+                if start + count >= self.memory.len() {
+                    return Err(out_of_bounds(start + count));
                 }
-                0x33 => {
-                    let value = self.V[x];
-                    let index = self.I as usize;
-                    let hundreds = value / 100;
-                    let tens = (value - (hundreds * 100)) / 10;
-                    let units = value - (hundreds * 100) - (tens * 10);
-                    self.memory[index] = hundreds;
-                    self.memory[index + 1] = tens;
-                    self.memory[index + 2] = units;
+                for (offset, register) in Chip8::register_range(x, y).enumerate() {
+                    self.V[register] = self.memory.read(start + offset, Access::Read);
                 }
-                0x55 => {
-                    for i in 0..x {
-                        self.memory[usize::from(self.I) + i] = self.V[i];
-                    }
+            }
+            Instruction::LoadILong { nnnn } => {
+                self.I = nnnn;
+                self.increment_program_counter();
+            }
+            Instruction::SelectPlanes { n } => {
+                self.planes = n as u8 & 0b11;
+            }
+            Instruction::LoadAudio => {
+                let start = usize::from(self.I);
+                let length = self.audio_pattern.len();
+                if start + length > self.memory.len() {
+                    return Err(out_of_bounds(start + length - 1));
                 }
-                0x65 => {
-                    for i in 0..x {
-                        self.V[i] = self.memory[usize::from(self.I) + i];
-                    }
+                for offset in 0..length {
+                    self.audio_pattern[offset] = self.memory.read(start + offset, Access::Read);
                 }
-                _ => panic!("Unrecognised instruction."),
-            },
-            _ => panic!("Unrecognised instruction."),
+            }
+            Instruction::SetPitch { x } => {
+                self.pitch = self.V[x];
+            }
         }
+        Ok(())
     }
 
     fn increment_program_counter(&mut self) {
@@ -376,198 +694,133 @@ impl Chip8 {
         }
     }
 
-    fn decrement_program_counter(&mut self) {
-        self.pc -= 2;
+    /// Skips over the next instruction, which on XO-CHIP may be four bytes
+    /// long.
+    fn skip_next_instruction(&mut self) {
+        let long = self.platform >= Platform::XoChip
+            && self.pc + 1 < self.memory.len()
+            && self.word_at(self.pc) == 0xF000;
+        self.increment_program_counter();
+        if long {
+            self.increment_program_counter();
+        }
     }
 
-    fn decrement_timers(&mut self) {
-        if self.sound > 0 {
-            println!("BEEP");
-            self.sound -= 1;
+    /// Reads the instruction word at `address` for execution.
+    fn fetch_word(&mut self, address: usize) -> u16 {
+        u16::from(self.memory.read(address, Access::Fetch)) << 8
+            | u16::from(self.memory.read(address + 1, Access::Fetch))
+    }
+
+    /// Writes a byte for an instruction, letting the tracer know.
+    fn store(&mut self, address: usize, value: u8) {
+        self.memory.write(address, value);
+        #[cfg(feature = "trace")]
+        if let Some(ref mut tracer) = self.tracer {
+            tracer.write(address, value);
         }
+    }
 
-        if self.delay > 0 {
-            self.delay -= 1;
+    /// Looks at the word at `address` without it counting as an access.
+    fn word_at(&self, address: usize) -> u16 {
+        u16::from(self.memory.peek(address)) << 8 | u16::from(self.memory.peek(address + 1))
+    }
+
+    /// The registers from VX to VY inclusive, counting down if X > Y.
+    fn register_range(x: usize, y: usize) -> Box<dyn Iterator<Item = usize>> {
+        if x <= y {
+            Box::new(x..=y)
+        } else {
+            Box::new((y..=x).rev())
         }
     }
 
     pub fn bytes_from_opcode(opcode: u16) -> (u8, u8) {
         let high_order: u8 = ((opcode & 0xFF00) >> 8) as u8;
-        // println!("High Order: {:X}", high_order);
         let low_order: u8 = (opcode & 0x00FF) as u8;
-        // println!("Low Order: {:X}", low_order);
         (high_order, low_order)
     }
 
-    fn high_nibble_from_byte(byte: u8) -> u8 {
-        byte & 0xF0
+    fn reset_flag_after_logic(&mut self) {
+        if self.quirks.logic_resets_vf {
+            self.V[0xF] = 0;
+        }
     }
 
-    fn low_nibble_from_byte(byte: u8) -> u8 {
-        byte & 0x0F
+    fn increment_index_after_transfer(&mut self, x: usize) {
+        match self.quirks.memory_increment {
+            MemoryIncrement::ByXPlusOne => self.I = self.I.wrapping_add(x as u16 + 1),
+            MemoryIncrement::ByX => self.I = self.I.wrapping_add(x as u16),
+            MemoryIncrement::Unchanged => {}
+        }
     }
 
-    fn byte_from_bool_array(array: [bool; 8]) -> u8 {
-        let mut result: u8 = 0b00000000;
-        for i in 0..8 {
-            if array[i] {
-                result = result | (0b10000000 >> i)
+    /// XORs one row of sprite data, most significant bit leftmost, onto a
+    /// plane of the screen, clipping or wrapping it at the edges. Returns
+    /// whether any pixel was switched off.
+    fn draw_sprite_row(&mut self, x: usize, y: usize, bits: u16, plane: u8) -> bool {
+        let (width, height) = (self.width(), self.height());
+        let clip = self.quirks.clip_sprites;
+        if y >= height && clip {
+            return false;
+        }
+        let y = y % height;
+        let mut collision = false;
+        for i in 0..16 {
+            if bits & (0x8000 >> i) == 0 {
+                continue;
             }
+            if x + i >= width && clip {
+                break;
+            }
+            let index = (x + i) % width + y * width;
+            collision |= self.graphics[index] & plane != 0;
+            self.graphics[index] ^= plane;
         }
-        result
+        collision
     }
 
-    fn bool_array_from_byte(byte: u8) -> [bool; 8] {
-        let mut array: [bool; 8] = [false; 8];
-        for i in 0..8 {
-            let temp_byte = byte;
-            array[i] = temp_byte & (0b10000000 >> i) != 0;
+    fn clear_display(&mut self) {
+        let planes = self.planes;
+        for pixel in self.graphics.iter_mut() {
+            *pixel &= !planes;
         }
-        array
-    }
-
-    fn update_pixel_at(&mut self, x: usize, y: usize, pixel: bool) {
-        self.graphics[x + y * WIDTH] = pixel;
     }
 
-    fn update_pixels_at(&mut self, x: usize, y: usize, pixels: [bool; 8]) {
-        for i in 0..8 {
-            let x_shifted = (x + i) % WIDTH;
-            self.update_pixel_at(x_shifted, y, pixels[i]);
+    /// Moves the contents of the selected planes by (dx, dy) pixels, filling
+    /// the uncovered area with blank pixels.
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let (width, height) = (self.width() as isize, self.height() as isize);
+        let previous = self.graphics;
+        for y in 0..height {
+            for x in 0..width {
+                let (from_x, from_y) = (x - dx, y - dy);
+                let pixel = if from_x >= 0 && from_x < width && from_y >= 0 && from_y < height {
+                    previous[(from_x + from_y * width) as usize]
+                } else {
+                    0
+                };
+                let index = (x + y * width) as usize;
+                self.graphics[index] = (self.graphics[index] & !self.planes) | (pixel & self.planes);
+            }
         }
     }
 
     fn print_opcode(&self, opcode: u16) -> String {
-        let opcode = opcode::Opcode::from(opcode);
-        let high_byte = opcode.high_byte;
-        let low_byte = opcode.low_byte;
-        let instruction = opcode.instruction;
-        let x = opcode.x;
-        let y = opcode.y;
-        let n = opcode.n;
-        let nnn = opcode.nnn;
-        match instruction {
-            0x0 => match low_byte {
-                0xE0 => {
-                    format!("CLR - Clear Display")
-                }
-                0xEE => {
-                    format!("RET - Return from sub")
-                }
-                _ => String::from("Unrecognised instruction.")
-            },
-            0x1 => {
-                format!("JP 0x{:03X}", nnn)
-            }
-            0x2 => {
-                format!("CALL 0x{:03X}", nnn)
-            }
-            0x3 => {
-                format!("SE V{} 0x{:04X}", x, low_byte)
-            }
-            0x4 => {
-                format!("SNE V{} 0x{:04X}", x, low_byte)
-            }
-            0x5 => {
-                format!("SE V{} V{}", x, y)
-            }
-            0x6 => {
-                format!("LD V{} 0x{:04X}", x, low_byte)
-            }
-            0x7 => {
-                format!("ADD V{} 0x{:04X}", x, low_byte)
-            }
-            0x8 => match n {
-                0x0 => {
-                    format!("LD V{} V{}", x, y)
-                }
-                0x1 => {
-                    format!("OR V{} V{}", x, y)
-                }
-                0x2 => {
-                    format!("AND V{} V{}", x, y)
-                }
-                0x3 => {
-                    format!("XOR V{} V{}", x, y)
-                }
-                0x4 => {
-                    format!("ADD V{} V{}", x, y)
-                }
-                0x5 => {
-                    format!("SUB V{} V{}", x, y)
-                }
-                0x6 => {
-                    format!("SHR V{} V{}", x, y)
-                }
-                0x7 => {
-                    format!("SUBN V{} V{}", x, y)
-                }
-                0xE => {
-                    format!("SHL V{} V{}", x, y)
-                }
-                _ => String::from("Unrecognised instruction.")
-            },
-            0x9 => {
-                format!("SNE V{} V{}", x, y)
-            }
-            0xA => {
-                format!("LD I 0x{:03X}", nnn)
-            }
-            0xB => {
-                format!("JP V0 0x{:03X}", nnn)
-            }
-            0xC => {
-                format!("RND V{} 0x{:04X}", x, low_byte)
-            }
-            0xD => {
-                format!("DRW V{} V{} 0x{:02X}", x, y, n)
-            }
-            0xE => match low_byte {
-                0x9E => {
-                    format!("SKP V{}", x)
-                }
-                0xA1 => {
-                    format!("SKNP V{}", x)
-                }
-                _ => String::from("Unrecognised instruction.")
-            },
-            0xF => match low_byte {
-                0x07 => {
-                    format!("LD V{} DT", x)
-                }
-                0x0A => {
-                    format!("LD V{} K", x)
-                }
-                0x15 => {
-                    format!("LD DT V{}", x)
-                }
-                0x18 => {
-                    format!("LD ST V{}", x)
-                }
-                0x1E => {
-                    format!("ADD I V{}", x)
-                }
-                0x29 => {
-                    format!("LD F V{}", x)
-                }
-                0x33 => {
-                    format!("LD B V{}", x)
-                }
-                0x55 => {
-                    format!("LD [I] V{}", x)
-                }
-                0x65 => {
-                    format!("LD V{} [I]", x)
-                }
-                _ => String::from("Unrecognised instruction.")
-            },
-            _ => String::from("Unrecognised instruction.")
+        match Instruction::decode(opcode) {
+            Ok(instruction) => instruction.to_string(),
+            Err(error) => error.to_string(),
         }
     }
 }
 
 impl Display for Chip8 {
-    fn fmt(&self, f: &mut Formatter) -> Result {
-        write!(f, "PC: {:03X}\nSP: {:X}\nStack: {:03X?}\nI: {:X}\nRegisters: {:?}\nNext Instruction: {}", self.pc, self.sp, self.stack, self.I, self.V, self.print_opcode(self.fetch_opcode()))
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let next_instruction = if self.pc + 1 < self.memory.len() {
+            self.print_opcode(self.word_at(self.pc))
+        } else {
+            "out of memory".to_string()
+        };
+        write!(f, "PC: {:03X}\nSP: {:X}\nStack: {:03X?}\nI: {:X}\nRegisters: {:?}\nNext Instruction: {}", self.pc, self.sp, self.stack, self.I, self.V, next_instruction)
     }
 }
\ No newline at end of file
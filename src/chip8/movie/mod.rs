@@ -0,0 +1,346 @@
+#[cfg(test)]
+mod tests;
+
+use chip8::random::SplitMix;
+use chip8::state::{read_settings, write_settings, write_u16, write_u32, write_u64, Reader};
+use chip8::{Chip8, ExecutionError, Platform, Quirks, StateError};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::error::Error;
+use core::fmt::{self, Display, Formatter};
+
+const MAGIC: &[u8; 4] = b"C8MV";
+
+/// The version written by [`Movie::to_bytes`].
+pub const VERSION: u16 = 1;
+
+/// A keypad key changing state, applied just before `frame` runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub frame: u32,
+    pub key: u8,
+    pub pressed: bool,
+}
+
+/// The hash of the machine's saved state once `frame` frames have run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub frame: u32,
+    pub hash: u64,
+}
+
+/// A recorded session.
+///
+/// Holds everything needed to power on the same machine again, plus every
+/// keypad press and release tagged with its frame. Emulation is
+/// deterministic, so feeding the same input back reproduces the session
+/// exactly. The checkpoints catch playback drifting away from the
+/// recording.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    pub rom_hash: u64,
+    pub seed: u64,
+    pub platform: Platform,
+    pub quirks: Quirks,
+    pub instructions_per_frame: u32,
+    /// The number of frames recorded.
+    pub frames: u32,
+    /// Events for frame `frames` came after the last recorded frame. They
+    /// are kept so that recording can carry on from the same key state.
+    pub events: Vec<KeyEvent>,
+    pub checkpoints: Vec<Checkpoint>,
+}
+
+/// Why a movie could not be read or played back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MovieError {
+    /// The data does not start with the movie signature.
+    NotAMovie,
+    /// The movie was written by a newer, or unknown, format version.
+    UnsupportedVersion(u16),
+    /// The data ends before the movie is complete.
+    Truncated,
+    /// A field holds a value no recording can produce.
+    Invalid(&'static str),
+    /// The ROM is not the one the movie was recorded with.
+    WrongRom,
+    /// The machine no longer matches the recording at this checkpoint.
+    Desync { frame: u32 },
+    /// The machine failed while playing back.
+    Execution(ExecutionError),
+}
+
+impl Display for MovieError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            MovieError::NotAMovie => write!(f, "not a CHIP-8 movie"),
+            MovieError::UnsupportedVersion(version) => write!(
+                f,
+                "movie version {} is not supported (expected at most {})",
+                version, VERSION
+            ),
+            MovieError::Truncated => write!(f, "movie is truncated"),
+            MovieError::Invalid(field) => write!(f, "movie has an invalid {}", field),
+            MovieError::WrongRom => write!(f, "movie was recorded with a different ROM"),
+            MovieError::Desync { frame } => write!(f, "playback desynced at frame {}", frame),
+            MovieError::Execution(ref error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl Error for MovieError {}
+
+impl From<StateError> for MovieError {
+    fn from(error: StateError) -> Self {
+        match error {
+            StateError::Truncated => MovieError::Truncated,
+            StateError::Invalid(field) => MovieError::Invalid(field),
+            StateError::NotAState | StateError::UnsupportedVersion(_) => MovieError::Invalid("settings"),
+        }
+    }
+}
+
+impl From<ExecutionError> for MovieError {
+    fn from(error: ExecutionError) -> Self {
+        MovieError::Execution(error)
+    }
+}
+
+/// FNV-1a, used for the ROM and checkpoint hashes.
+pub fn hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xCBF2_9CE4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01B3)
+    })
+}
+
+impl Movie {
+    /// An empty movie of `rom` run with these settings.
+    pub fn new(rom: &[u8], seed: u64, platform: Platform, quirks: Quirks, instructions_per_frame: usize) -> Self {
+        Movie {
+            rom_hash: hash(rom),
+            seed,
+            platform,
+            quirks,
+            instructions_per_frame: instructions_per_frame as u32,
+            frames: 0,
+            events: Vec::new(),
+            checkpoints: Vec::new(),
+        }
+    }
+
+    /// Powers on the machine the movie starts from, with `rom` loaded.
+    pub fn start(&self, rom: &[u8]) -> Result<Chip8, MovieError> {
+        if hash(rom) != self.rom_hash {
+            return Err(MovieError::WrongRom);
+        }
+        let mut chip8 = Chip8::with_platform(self.platform, self.quirks);
+        chip8.rng = Box::new(SplitMix::new(self.seed));
+        chip8.load_rom(rom);
+        Ok(chip8)
+    }
+
+    /// Serializes the movie. All multi-byte values are big endian.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(48 + self.events.len() * 5 + self.checkpoints.len() * 12);
+        out.extend_from_slice(MAGIC);
+        write_u16(&mut out, VERSION);
+        write_u64(&mut out, self.rom_hash);
+        write_u64(&mut out, self.seed);
+        write_settings(&mut out, self.platform, self.quirks);
+        write_u32(&mut out, self.instructions_per_frame);
+        write_u32(&mut out, self.frames);
+
+        write_u32(&mut out, self.events.len() as u32);
+        for event in &self.events {
+            write_u32(&mut out, event.frame);
+            out.push(event.key | (event.pressed as u8) << 7);
+        }
+        write_u32(&mut out, self.checkpoints.len() as u32);
+        for checkpoint in &self.checkpoints {
+            write_u32(&mut out, checkpoint.frame);
+            write_u64(&mut out, checkpoint.hash);
+        }
+        out
+    }
+
+    /// Reads a movie written by [`Movie::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MovieError> {
+        let mut reader = Reader::new(bytes);
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(MovieError::NotAMovie);
+        }
+        let version = reader.u16()?;
+        if version != VERSION {
+            return Err(MovieError::UnsupportedVersion(version));
+        }
+        let rom_hash = reader.u64()?;
+        let seed = reader.u64()?;
+        let (platform, quirks) = read_settings(&mut reader)?;
+        let instructions_per_frame = reader.u32()?;
+        let frames = reader.u32()?;
+
+        let mut events = Vec::new();
+        for _ in 0..reader.u32()? {
+            let frame = reader.u32()?;
+            let key = reader.u8()?;
+            if key & 0x7F > 0xF {
+                return Err(MovieError::Invalid("key"));
+            }
+            if frame > frames || events.last().is_some_and(|last: &KeyEvent| last.frame > frame) {
+                return Err(MovieError::Invalid("event frame"));
+            }
+            events.push(KeyEvent {
+                frame,
+                key: key & 0x7F,
+                pressed: key & 0x80 != 0,
+            });
+        }
+        let mut checkpoints = Vec::new();
+        for _ in 0..reader.u32()? {
+            let frame = reader.u32()?;
+            let hash = reader.u64()?;
+            if frame > frames || checkpoints.last().is_some_and(|last: &Checkpoint| last.frame >= frame) {
+                return Err(MovieError::Invalid("checkpoint frame"));
+            }
+            checkpoints.push(Checkpoint { frame, hash });
+        }
+        if !reader.is_finished() {
+            return Err(MovieError::Invalid("length"));
+        }
+
+        Ok(Movie {
+            rom_hash,
+            seed,
+            platform,
+            quirks,
+            instructions_per_frame,
+            frames,
+            events,
+            checkpoints,
+        })
+    }
+}
+
+/// Records a movie while the machine runs.
+///
+/// Start the machine with [`Movie::start`], then send every key press and
+/// release and every frame through the recorder so no input is missed.
+pub struct Recorder {
+    movie: Movie,
+    checkpoint_interval: u32,
+}
+
+impl Recorder {
+    /// Records onto the end of `movie`, adding a checkpoint every
+    /// `checkpoint_interval` frames.
+    pub fn new(movie: Movie, checkpoint_interval: u32) -> Self {
+        Recorder {
+            movie,
+            checkpoint_interval: checkpoint_interval.max(1),
+        }
+    }
+
+    /// Presses `key` on the machine and notes it down for the coming frame.
+    pub fn key_down(&mut self, chip8: &mut Chip8, key: usize) {
+        chip8.key_down(key);
+        self.push_event(key, true);
+    }
+
+    /// Releases `key` on the machine and notes it down for the coming frame.
+    pub fn key_up(&mut self, chip8: &mut Chip8, key: usize) {
+        chip8.key_up(key);
+        self.push_event(key, false);
+    }
+
+    fn push_event(&mut self, key: usize, pressed: bool) {
+        //The machine ignores keys off the keypad, so there is nothing to replay
+        if key > 0xF {
+            return;
+        }
+        self.movie.events.push(KeyEvent {
+            frame: self.movie.frames,
+            key: key as u8,
+            pressed,
+        });
+    }
+
+    /// Runs one frame, checkpointing the machine every so often.
+    pub fn run_frame(&mut self, chip8: &mut Chip8) -> Result<(), ExecutionError> {
+        chip8.run_frame(self.movie.instructions_per_frame as usize)?;
+        self.movie.frames += 1;
+        if self.movie.frames.is_multiple_of(self.checkpoint_interval) {
+            self.movie.checkpoints.push(Checkpoint {
+                frame: self.movie.frames,
+                hash: hash(&chip8.save_state()),
+            });
+        }
+        Ok(())
+    }
+
+    pub fn movie(&self) -> &Movie {
+        &self.movie
+    }
+
+    pub fn finish(self) -> Movie {
+        self.movie
+    }
+}
+
+/// Feeds a movie's input back into a machine started with
+/// [`Movie::start`].
+pub struct Player {
+    movie: Movie,
+    frame: u32,
+    next_event: usize,
+    next_checkpoint: usize,
+}
+
+impl Player {
+    pub fn new(movie: Movie) -> Self {
+        Player {
+            movie,
+            frame: 0,
+            next_event: 0,
+            next_checkpoint: 0,
+        }
+    }
+
+    /// The number of frames played so far.
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.frame >= self.movie.frames
+    }
+
+    /// Presses and releases keys in the order they were recorded and runs
+    /// one frame, then compares the machine against any checkpoint for it.
+    ///
+    /// Any other input sent to the machine during playback will desync it.
+    pub fn run_frame(&mut self, chip8: &mut Chip8) -> Result<(), MovieError> {
+        while let Some(&event) = self.movie.events.get(self.next_event) {
+            if event.frame != self.frame {
+                break;
+            }
+            if event.pressed {
+                chip8.key_down(event.key as usize);
+            } else {
+                chip8.key_up(event.key as usize);
+            }
+            self.next_event += 1;
+        }
+
+        chip8.run_frame(self.movie.instructions_per_frame as usize)?;
+        self.frame += 1;
+        if let Some(checkpoint) = self.movie.checkpoints.get(self.next_checkpoint) {
+            if checkpoint.frame == self.frame {
+                self.next_checkpoint += 1;
+                if checkpoint.hash != hash(&chip8.save_state()) {
+                    return Err(MovieError::Desync { frame: self.frame });
+                }
+            }
+        }
+        Ok(())
+    }
+}
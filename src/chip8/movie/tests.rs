@@ -0,0 +1,135 @@
+use chip8::movie::{hash, KeyEvent, Movie, MovieError, Player, Recorder};
+use chip8::{Chip8, Platform};
+
+//V0 = 5, then loop: V1 = random, V2 += 1 unless key 5 is up, store V0-V2 at 0x300
+const ROM: [u8; 14] = [0x60, 0x05, 0xC1, 0xFF, 0xE0, 0xA1, 0x72, 0x01, 0xA3, 0x00, 0xF2, 0x55, 0x12, 0x02];
+
+fn record(frames: u32) -> (Movie, Chip8) {
+    let platform = Platform::Chip8;
+    let movie = Movie::new(&ROM, 1234, platform, platform.quirks(), 20);
+    let mut cpu = movie.start(&ROM).unwrap();
+    let mut recorder = Recorder::new(movie, 10);
+    for frame in 0..frames {
+        match frame % 7 {
+            0 => recorder.key_down(&mut cpu, 5),
+            3 => recorder.key_up(&mut cpu, 5),
+            _ => {}
+        }
+        recorder.run_frame(&mut cpu).unwrap();
+    }
+    (recorder.finish(), cpu)
+}
+
+#[test]
+fn test_records_key_changes() {
+    let (movie, _) = record(15);
+    assert_eq!(movie.frames, 15);
+    assert_eq!(
+        movie.events,
+        vec!(
+            KeyEvent { frame: 0, key: 5, pressed: true },
+            KeyEvent { frame: 3, key: 5, pressed: false },
+            KeyEvent { frame: 7, key: 5, pressed: true },
+            KeyEvent { frame: 10, key: 5, pressed: false },
+            KeyEvent { frame: 14, key: 5, pressed: true },
+        )
+    );
+    assert_eq!(movie.checkpoints.len(), 1);
+    assert_eq!(movie.checkpoints[0].frame, 10);
+}
+
+#[test]
+fn test_playback_reproduces_session() {
+    let (movie, recorded) = record(50);
+    let movie = Movie::from_bytes(&movie.to_bytes()).unwrap();
+
+    let mut cpu = movie.start(&ROM).unwrap();
+    let mut player = Player::new(movie);
+    while !player.is_finished() {
+        player.run_frame(&mut cpu).unwrap();
+    }
+    assert_eq!(player.frame(), 50);
+    assert_eq!(cpu.save_state(), recorded.save_state());
+}
+
+#[test]
+fn test_keeps_input_after_the_last_frame() {
+    let (movie, mut cpu) = record(5);
+    let mut recorder = Recorder::new(movie, 10);
+    recorder.key_down(&mut cpu, 0xA);
+    let movie = recorder.finish();
+    assert_eq!(movie.events.last(), Some(&KeyEvent { frame: 5, key: 0xA, pressed: true }));
+    assert_eq!(Movie::from_bytes(&movie.to_bytes()), Ok(movie));
+}
+
+#[test]
+fn test_ignores_keys_off_the_keypad() {
+    let (movie, mut cpu) = record(1);
+    let mut recorder = Recorder::new(movie, 10);
+    recorder.key_down(&mut cpu, 0x10);
+    recorder.key_up(&mut cpu, 0x10);
+    assert_eq!(recorder.finish().events.len(), 1);
+}
+
+#[test]
+fn test_detects_desync() {
+    let (movie, _) = record(30);
+    let mut cpu = movie.start(&ROM).unwrap();
+    let mut player = Player::new(movie);
+    for _ in 0..5 {
+        player.run_frame(&mut cpu).unwrap();
+    }
+    cpu.V[0xA] = 1;
+    for _ in 0..4 {
+        player.run_frame(&mut cpu).unwrap();
+    }
+    assert_eq!(player.run_frame(&mut cpu), Err(MovieError::Desync { frame: 10 }));
+}
+
+#[test]
+fn test_rejects_wrong_rom() {
+    let (movie, _) = record(1);
+    assert_eq!(movie.start(&ROM[..4]).err(), Some(MovieError::WrongRom));
+}
+
+#[test]
+fn test_rejects_bad_movies() {
+    let (movie, _) = record(20);
+    let bytes = movie.to_bytes();
+    assert_eq!(Movie::from_bytes(b"C8ST"), Err(MovieError::NotAMovie));
+    assert_eq!(Movie::from_bytes(&bytes[..bytes.len() - 1]), Err(MovieError::Truncated));
+
+    let mut bad_key = bytes.clone();
+    //The first event's key follows the header and event count
+    bad_key[4 + 2 + 8 + 8 + 3 + 4 + 4 + 4 + 4] = 0x1F;
+    assert_eq!(Movie::from_bytes(&bad_key), Err(MovieError::Invalid("key")));
+}
+
+#[test]
+fn test_hash_is_fnv1a() {
+    assert_eq!(hash(b""), 0xCBF2_9CE4_8422_2325);
+    assert_eq!(hash(b"a"), 0xAF63_DC4C_8601_EC8C);
+}
+
+#[test]
+fn test_replays_taps_within_a_frame() {
+    //Wait for a key, then count up in V1
+    let rom = [0xF0, 0x0A, 0x71, 0x01, 0x12, 0x02];
+    let platform = Platform::Chip8;
+    let movie = Movie::new(&rom, 0, platform, platform.quirks(), 10);
+    let mut cpu = movie.start(&rom).unwrap();
+    let mut recorder = Recorder::new(movie, 1);
+    recorder.run_frame(&mut cpu).unwrap();
+    recorder.key_down(&mut cpu, 0xB);
+    recorder.key_up(&mut cpu, 0xB);
+    recorder.run_frame(&mut cpu).unwrap();
+    assert_eq!(cpu.V[0], 0xB);
+
+    let movie = recorder.finish();
+    let mut replayed = movie.start(&rom).unwrap();
+    let mut player = Player::new(movie);
+    while !player.is_finished() {
+        player.run_frame(&mut replayed).unwrap();
+    }
+    assert_eq!(replayed.save_state(), cpu.save_state());
+}
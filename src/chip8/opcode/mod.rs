@@ -1,4 +1,9 @@
-use chip8::Chip8;
+#[cfg(test)]
+mod tests;
+
+use alloc::vec::Vec;
+use chip8::{Chip8, Platform};
+use core::fmt::{self, Display, Formatter};
 
 pub struct Opcode {
     pub high_byte: u8,
@@ -28,14 +33,453 @@ impl From<u16> for Opcode {
 impl From<(u8, u8)> for Opcode {
     fn from(bytes: (u8, u8)) -> Self {
         let (high_byte, low_byte) = bytes;
-        Opcode {
-            high_byte,
+        Opcode::from(u16::from(high_byte) << 8 | u16::from(low_byte))
+    }
+}
+
+/// A fully decoded instruction.
+///
+/// `x` and `y` are register indices, `n` is a nibble, `nn` a byte and `nnn`
+/// a 12-bit address, matching the usual `8XY4`/`DXYN` notation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// `0NNN` - Call machine code routine
+    Sys { nnn: u16 },
+    /// `00E0` - Clear the display
+    Clear,
+    /// `00EE` - Return from subroutine
+    Return,
+    /// `1NNN` - Jump to address
+    Jump { nnn: u16 },
+    /// `2NNN` - Call subroutine at address
+    Call { nnn: u16 },
+    /// `3XNN` - Skip next instruction if VX == NN
+    SkipEqImm { x: usize, nn: u8 },
+    /// `4XNN` - Skip next instruction if VX != NN
+    SkipNeImm { x: usize, nn: u8 },
+    /// `5XY0` - Skip next instruction if VX == VY
+    SkipEqReg { x: usize, y: usize },
+    /// `6XNN` - VX = NN
+    LoadImm { x: usize, nn: u8 },
+    /// `7XNN` - VX += NN
+    AddImm { x: usize, nn: u8 },
+    /// `8XY0` - VX = VY
+    LoadReg { x: usize, y: usize },
+    /// `8XY1` - VX |= VY
+    Or { x: usize, y: usize },
+    /// `8XY2` - VX &= VY
+    And { x: usize, y: usize },
+    /// `8XY3` - VX ^= VY
+    Xor { x: usize, y: usize },
+    /// `8XY4` - VX += VY, VF = carry
+    AddRegReg { x: usize, y: usize },
+    /// `8XY5` - VX -= VY, VF = not borrow
+    Sub { x: usize, y: usize },
+    /// `8XY6` - Shift right, VF = shifted out bit
+    ShiftRight { x: usize, y: usize },
+    /// `8XY7` - VX = VY - VX, VF = not borrow
+    SubN { x: usize, y: usize },
+    /// `8XYE` - Shift left, VF = shifted out bit
+    ShiftLeft { x: usize, y: usize },
+    /// `9XY0` - Skip next instruction if VX != VY
+    SkipNeReg { x: usize, y: usize },
+    /// `ANNN` - I = NNN
+    LoadI { nnn: u16 },
+    /// `BNNN` - Jump to NNN + V0
+    JumpV0 { nnn: u16 },
+    /// `CXNN` - VX = random byte & NN
+    Random { x: usize, nn: u8 },
+    /// `DXYN` - Draw N byte sprite from I at (VX, VY)
+    Draw { x: usize, y: usize, n: usize },
+    /// `EX9E` - Skip next instruction if key VX is pressed
+    SkipKey { x: usize },
+    /// `EXA1` - Skip next instruction if key VX is not pressed
+    SkipNotKey { x: usize },
+    /// `FX07` - VX = delay timer
+    LoadDelay { x: usize },
+    /// `FX0A` - Wait for a key press and store it in VX
+    WaitKey { x: usize },
+    /// `FX15` - Delay timer = VX
+    SetDelay { x: usize },
+    /// `FX18` - Sound timer = VX
+    SetSound { x: usize },
+    /// `FX1E` - I += VX
+    AddI { x: usize },
+    /// `FX29` - I = address of font sprite for VX
+    LoadFont { x: usize },
+    /// `FX33` - Store BCD of VX at I, I + 1, I + 2
+    StoreBcd { x: usize },
+    /// `FX55` - Store V0 to VX in memory starting at I
+    StoreRegisters { x: usize },
+    /// `FX65` - Load V0 to VX from memory starting at I
+    LoadRegisters { x: usize },
+    /// `00CN` - Scroll the display down N pixels
+    ScrollDown { n: usize },
+    /// `00FB` - Scroll the display right 4 pixels
+    ScrollRight,
+    /// `00FC` - Scroll the display left 4 pixels
+    ScrollLeft,
+    /// `00FD` - Exit the interpreter
+    Exit,
+    /// `00FE` - Switch to 64x32 low resolution mode
+    LowRes,
+    /// `00FF` - Switch to 128x64 high resolution mode
+    HighRes,
+    /// `FX30` - I = address of big font sprite for VX
+    LoadBigFont { x: usize },
+    /// `FX75` - Store V0 to VX in the RPL flags
+    StoreFlags { x: usize },
+    /// `FX85` - Load V0 to VX from the RPL flags
+    LoadFlags { x: usize },
+    /// `00DN` - Scroll the selected planes up N pixels
+    ScrollUp { n: usize },
+    /// `5XY2` - Store VX to VY in memory starting at I
+    SaveRange { x: usize, y: usize },
+    /// `5XY3` - Load VX to VY from memory starting at I
+    LoadRange { x: usize, y: usize },
+    /// `F000 NNNN` - I = NNNN, taking up four bytes
+    LoadILong { nnnn: u16 },
+    /// `FN01` - Select the bitplanes that drawing, clearing and scrolling affect
+    SelectPlanes { n: usize },
+    /// `F002` - Load the 16 byte audio pattern buffer from I
+    LoadAudio,
+    /// `FX3A` - Set the audio playback pitch to VX
+    SetPitch { x: usize },
+}
+
+/// A word that does not correspond to any instruction.
+///
+/// `F000` is also reported here by [`Instruction::decode`], as it is the
+/// first half of a four byte instruction and needs [`Instruction::decode_pair`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeError {
+    pub opcode: u16,
+}
+
+impl Instruction {
+    /// Decodes an instruction whose operand follows it in the next word. Any
+    /// other instruction is decoded from `opcode` alone and `next` is ignored.
+    pub fn decode_pair(opcode: u16, next: u16) -> Result<Instruction, DecodeError> {
+        if opcode == 0xF000 {
+            Ok(Instruction::LoadILong { nnnn: next })
+        } else {
+            Instruction::decode(opcode)
+        }
+    }
+
+    pub fn decode(opcode: u16) -> Result<Instruction, DecodeError> {
+        let Opcode {
             low_byte,
-            instruction: (high_byte & 0xF0) >> 4,
-            x: (high_byte & 0x0F) as usize,
-            y: ((low_byte & 0xF0) >> 4) as usize,
-            n: (low_byte & 0x0F) as usize,
-            nnn: 0//opcode & 0x0FFF,
+            instruction,
+            x,
+            y,
+            n,
+            nnn,
+            ..
+        } = Opcode::from(opcode);
+        let nn = low_byte;
+
+        let decoded = match instruction {
+            0x0 => match nnn {
+                0x0E0 => Instruction::Clear,
+                0x0EE => Instruction::Return,
+                0x0C0..=0x0CF => Instruction::ScrollDown { n },
+                0x0D0..=0x0DF => Instruction::ScrollUp { n },
+                0x0FB => Instruction::ScrollRight,
+                0x0FC => Instruction::ScrollLeft,
+                0x0FD => Instruction::Exit,
+                0x0FE => Instruction::LowRes,
+                0x0FF => Instruction::HighRes,
+                _ => Instruction::Sys { nnn },
+            },
+            0x1 => Instruction::Jump { nnn },
+            0x2 => Instruction::Call { nnn },
+            0x3 => Instruction::SkipEqImm { x, nn },
+            0x4 => Instruction::SkipNeImm { x, nn },
+            0x5 => match n {
+                0x0 => Instruction::SkipEqReg { x, y },
+                0x2 => Instruction::SaveRange { x, y },
+                0x3 => Instruction::LoadRange { x, y },
+                _ => return Err(DecodeError { opcode }),
+            },
+            0x6 => Instruction::LoadImm { x, nn },
+            0x7 => Instruction::AddImm { x, nn },
+            0x8 => match n {
+                0x0 => Instruction::LoadReg { x, y },
+                0x1 => Instruction::Or { x, y },
+                0x2 => Instruction::And { x, y },
+                0x3 => Instruction::Xor { x, y },
+                0x4 => Instruction::AddRegReg { x, y },
+                0x5 => Instruction::Sub { x, y },
+                0x6 => Instruction::ShiftRight { x, y },
+                0x7 => Instruction::SubN { x, y },
+                0xE => Instruction::ShiftLeft { x, y },
+                _ => return Err(DecodeError { opcode }),
+            },
+            0x9 if n == 0 => Instruction::SkipNeReg { x, y },
+            0xA => Instruction::LoadI { nnn },
+            0xB => Instruction::JumpV0 { nnn },
+            0xC => Instruction::Random { x, nn },
+            0xD => Instruction::Draw { x, y, n },
+            0xE => match nn {
+                0x9E => Instruction::SkipKey { x },
+                0xA1 => Instruction::SkipNotKey { x },
+                _ => return Err(DecodeError { opcode }),
+            },
+            0xF if nn == 0x00 => return Err(DecodeError { opcode }),
+            0xF if opcode == 0xF002 => Instruction::LoadAudio,
+            0xF => match nn {
+                0x01 => Instruction::SelectPlanes { n: x },
+                0x07 => Instruction::LoadDelay { x },
+                0x0A => Instruction::WaitKey { x },
+                0x15 => Instruction::SetDelay { x },
+                0x18 => Instruction::SetSound { x },
+                0x1E => Instruction::AddI { x },
+                0x29 => Instruction::LoadFont { x },
+                0x30 => Instruction::LoadBigFont { x },
+                0x3A => Instruction::SetPitch { x },
+                0x33 => Instruction::StoreBcd { x },
+                0x55 => Instruction::StoreRegisters { x },
+                0x65 => Instruction::LoadRegisters { x },
+                0x75 => Instruction::StoreFlags { x },
+                0x85 => Instruction::LoadFlags { x },
+                _ => return Err(DecodeError { opcode }),
+            },
+            _ => return Err(DecodeError { opcode }),
+        };
+        Ok(decoded)
+    }
+
+    /// Encodes the first word of the instruction. Only `LoadILong` has a
+    /// second, see [`Instruction::to_bytes`].
+    pub fn encode(self) -> u16 {
+        fn xnn(prefix: u16, x: usize, nn: u8) -> u16 {
+            prefix << 12 | (x as u16 & 0xF) << 8 | u16::from(nn)
+        }
+        fn xyn(prefix: u16, x: usize, y: usize, n: usize) -> u16 {
+            prefix << 12 | (x as u16 & 0xF) << 8 | (y as u16 & 0xF) << 4 | (n as u16 & 0xF)
+        }
+        fn nnn(prefix: u16, nnn: u16) -> u16 {
+            prefix << 12 | (nnn & 0x0FFF)
         }
+
+        match self {
+            Instruction::Sys { nnn: address } => nnn(0x0, address),
+            Instruction::Clear => 0x00E0,
+            Instruction::Return => 0x00EE,
+            Instruction::Jump { nnn: address } => nnn(0x1, address),
+            Instruction::Call { nnn: address } => nnn(0x2, address),
+            Instruction::SkipEqImm { x, nn } => xnn(0x3, x, nn),
+            Instruction::SkipNeImm { x, nn } => xnn(0x4, x, nn),
+            Instruction::SkipEqReg { x, y } => xyn(0x5, x, y, 0x0),
+            Instruction::LoadImm { x, nn } => xnn(0x6, x, nn),
+            Instruction::AddImm { x, nn } => xnn(0x7, x, nn),
+            Instruction::LoadReg { x, y } => xyn(0x8, x, y, 0x0),
+            Instruction::Or { x, y } => xyn(0x8, x, y, 0x1),
+            Instruction::And { x, y } => xyn(0x8, x, y, 0x2),
+            Instruction::Xor { x, y } => xyn(0x8, x, y, 0x3),
+            Instruction::AddRegReg { x, y } => xyn(0x8, x, y, 0x4),
+            Instruction::Sub { x, y } => xyn(0x8, x, y, 0x5),
+            Instruction::ShiftRight { x, y } => xyn(0x8, x, y, 0x6),
+            Instruction::SubN { x, y } => xyn(0x8, x, y, 0x7),
+            Instruction::ShiftLeft { x, y } => xyn(0x8, x, y, 0xE),
+            Instruction::SkipNeReg { x, y } => xyn(0x9, x, y, 0x0),
+            Instruction::LoadI { nnn: address } => nnn(0xA, address),
+            Instruction::JumpV0 { nnn: address } => nnn(0xB, address),
+            Instruction::Random { x, nn } => xnn(0xC, x, nn),
+            Instruction::Draw { x, y, n } => xyn(0xD, x, y, n),
+            Instruction::SkipKey { x } => xnn(0xE, x, 0x9E),
+            Instruction::SkipNotKey { x } => xnn(0xE, x, 0xA1),
+            Instruction::LoadDelay { x } => xnn(0xF, x, 0x07),
+            Instruction::WaitKey { x } => xnn(0xF, x, 0x0A),
+            Instruction::SetDelay { x } => xnn(0xF, x, 0x15),
+            Instruction::SetSound { x } => xnn(0xF, x, 0x18),
+            Instruction::AddI { x } => xnn(0xF, x, 0x1E),
+            Instruction::LoadFont { x } => xnn(0xF, x, 0x29),
+            Instruction::StoreBcd { x } => xnn(0xF, x, 0x33),
+            Instruction::StoreRegisters { x } => xnn(0xF, x, 0x55),
+            Instruction::LoadRegisters { x } => xnn(0xF, x, 0x65),
+            Instruction::ScrollDown { n } => xyn(0x0, 0x0, 0xC, n),
+            Instruction::ScrollRight => 0x00FB,
+            Instruction::ScrollLeft => 0x00FC,
+            Instruction::Exit => 0x00FD,
+            Instruction::LowRes => 0x00FE,
+            Instruction::HighRes => 0x00FF,
+            Instruction::LoadBigFont { x } => xnn(0xF, x, 0x30),
+            Instruction::StoreFlags { x } => xnn(0xF, x, 0x75),
+            Instruction::LoadFlags { x } => xnn(0xF, x, 0x85),
+            Instruction::ScrollUp { n } => xyn(0x0, 0x0, 0xD, n),
+            Instruction::SaveRange { x, y } => xyn(0x5, x, y, 0x2),
+            Instruction::LoadRange { x, y } => xyn(0x5, x, y, 0x3),
+            Instruction::LoadILong { .. } => 0xF000,
+            Instruction::SelectPlanes { n } => xnn(0xF, n, 0x01),
+            Instruction::LoadAudio => 0xF002,
+            Instruction::SetPitch { x } => xnn(0xF, x, 0x3A),
+        }
+    }
+
+    /// Number of bytes the instruction takes up in memory.
+    pub fn size(self) -> usize {
+        match self {
+            Instruction::LoadILong { .. } => 4,
+            _ => 2,
+        }
+    }
+
+    /// The instruction as it is stored in memory.
+    pub fn to_bytes(self) -> Vec<u8> {
+        let (high, low) = Chip8::bytes_from_opcode(self.encode());
+        match self {
+            Instruction::LoadILong { nnnn } => {
+                let (address_high, address_low) = Chip8::bytes_from_opcode(nnnn);
+                vec![high, low, address_high, address_low]
+            }
+            _ => vec![high, low],
+        }
+    }
+
+    /// The first platform whose instruction set includes this instruction.
+    pub fn platform(self) -> Platform {
+        match self {
+            Instruction::ScrollDown { .. }
+            | Instruction::ScrollRight
+            | Instruction::ScrollLeft
+            | Instruction::Exit
+            | Instruction::LowRes
+            | Instruction::HighRes
+            | Instruction::LoadBigFont { .. }
+            | Instruction::StoreFlags { .. }
+            | Instruction::LoadFlags { .. } => Platform::SuperChip,
+            Instruction::ScrollUp { .. }
+            | Instruction::SaveRange { .. }
+            | Instruction::LoadRange { .. }
+            | Instruction::LoadILong { .. }
+            | Instruction::SelectPlanes { .. }
+            | Instruction::LoadAudio
+            | Instruction::SetPitch { .. } => Platform::XoChip,
+            _ => Platform::Chip8,
+        }
+    }
+
+    /// The usual notation for the instruction, such as `8XY4`, to group
+    /// instructions by what they do rather than by their operands.
+    pub fn pattern(self) -> &'static str {
+        match self {
+            Instruction::Sys { .. } => "0NNN",
+            Instruction::Clear => "00E0",
+            Instruction::Return => "00EE",
+            Instruction::Jump { .. } => "1NNN",
+            Instruction::Call { .. } => "2NNN",
+            Instruction::SkipEqImm { .. } => "3XNN",
+            Instruction::SkipNeImm { .. } => "4XNN",
+            Instruction::SkipEqReg { .. } => "5XY0",
+            Instruction::LoadImm { .. } => "6XNN",
+            Instruction::AddImm { .. } => "7XNN",
+            Instruction::LoadReg { .. } => "8XY0",
+            Instruction::Or { .. } => "8XY1",
+            Instruction::And { .. } => "8XY2",
+            Instruction::Xor { .. } => "8XY3",
+            Instruction::AddRegReg { .. } => "8XY4",
+            Instruction::Sub { .. } => "8XY5",
+            Instruction::ShiftRight { .. } => "8XY6",
+            Instruction::SubN { .. } => "8XY7",
+            Instruction::ShiftLeft { .. } => "8XYE",
+            Instruction::SkipNeReg { .. } => "9XY0",
+            Instruction::LoadI { .. } => "ANNN",
+            Instruction::JumpV0 { .. } => "BNNN",
+            Instruction::Random { .. } => "CXNN",
+            Instruction::Draw { .. } => "DXYN",
+            Instruction::SkipKey { .. } => "EX9E",
+            Instruction::SkipNotKey { .. } => "EXA1",
+            Instruction::LoadDelay { .. } => "FX07",
+            Instruction::WaitKey { .. } => "FX0A",
+            Instruction::SetDelay { .. } => "FX15",
+            Instruction::SetSound { .. } => "FX18",
+            Instruction::AddI { .. } => "FX1E",
+            Instruction::LoadFont { .. } => "FX29",
+            Instruction::StoreBcd { .. } => "FX33",
+            Instruction::StoreRegisters { .. } => "FX55",
+            Instruction::LoadRegisters { .. } => "FX65",
+            Instruction::ScrollDown { .. } => "00CN",
+            Instruction::ScrollRight => "00FB",
+            Instruction::ScrollLeft => "00FC",
+            Instruction::Exit => "00FD",
+            Instruction::LowRes => "00FE",
+            Instruction::HighRes => "00FF",
+            Instruction::LoadBigFont { .. } => "FX30",
+            Instruction::StoreFlags { .. } => "FX75",
+            Instruction::LoadFlags { .. } => "FX85",
+            Instruction::ScrollUp { .. } => "00DN",
+            Instruction::SaveRange { .. } => "5XY2",
+            Instruction::LoadRange { .. } => "5XY3",
+            Instruction::LoadILong { .. } => "F000",
+            Instruction::SelectPlanes { .. } => "FN01",
+            Instruction::LoadAudio => "F002",
+            Instruction::SetPitch { .. } => "FX3A",
+        }
+    }
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Instruction::Sys { nnn } => write!(f, "SYS 0x{:03X}", nnn),
+            Instruction::Clear => write!(f, "CLS"),
+            Instruction::Return => write!(f, "RET"),
+            Instruction::Jump { nnn } => write!(f, "JP 0x{:03X}", nnn),
+            Instruction::Call { nnn } => write!(f, "CALL 0x{:03X}", nnn),
+            Instruction::SkipEqImm { x, nn } => write!(f, "SE V{:X}, 0x{:02X}", x, nn),
+            Instruction::SkipNeImm { x, nn } => write!(f, "SNE V{:X}, 0x{:02X}", x, nn),
+            Instruction::SkipEqReg { x, y } => write!(f, "SE V{:X}, V{:X}", x, y),
+            Instruction::LoadImm { x, nn } => write!(f, "LD V{:X}, 0x{:02X}", x, nn),
+            Instruction::AddImm { x, nn } => write!(f, "ADD V{:X}, 0x{:02X}", x, nn),
+            Instruction::LoadReg { x, y } => write!(f, "LD V{:X}, V{:X}", x, y),
+            Instruction::Or { x, y } => write!(f, "OR V{:X}, V{:X}", x, y),
+            Instruction::And { x, y } => write!(f, "AND V{:X}, V{:X}", x, y),
+            Instruction::Xor { x, y } => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Instruction::AddRegReg { x, y } => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Instruction::Sub { x, y } => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Instruction::ShiftRight { x, y } => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Instruction::SubN { x, y } => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Instruction::ShiftLeft { x, y } => write!(f, "SHL V{:X}, V{:X}", x, y),
+            Instruction::SkipNeReg { x, y } => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Instruction::LoadI { nnn } => write!(f, "LD I, 0x{:03X}", nnn),
+            Instruction::JumpV0 { nnn } => write!(f, "JP V0, 0x{:03X}", nnn),
+            Instruction::Random { x, nn } => write!(f, "RND V{:X}, 0x{:02X}", x, nn),
+            Instruction::Draw { x, y, n } => write!(f, "DRW V{:X}, V{:X}, 0x{:X}", x, y, n),
+            Instruction::SkipKey { x } => write!(f, "SKP V{:X}", x),
+            Instruction::SkipNotKey { x } => write!(f, "SKNP V{:X}", x),
+            Instruction::LoadDelay { x } => write!(f, "LD V{:X}, DT", x),
+            Instruction::WaitKey { x } => write!(f, "LD V{:X}, K", x),
+            Instruction::SetDelay { x } => write!(f, "LD DT, V{:X}", x),
+            Instruction::SetSound { x } => write!(f, "LD ST, V{:X}", x),
+            Instruction::AddI { x } => write!(f, "ADD I, V{:X}", x),
+            Instruction::LoadFont { x } => write!(f, "LD F, V{:X}", x),
+            Instruction::StoreBcd { x } => write!(f, "LD B, V{:X}", x),
+            Instruction::StoreRegisters { x } => write!(f, "LD [I], V{:X}", x),
+            Instruction::LoadRegisters { x } => write!(f, "LD V{:X}, [I]", x),
+            Instruction::ScrollDown { n } => write!(f, "SCD 0x{:X}", n),
+            Instruction::ScrollRight => write!(f, "SCR"),
+            Instruction::ScrollLeft => write!(f, "SCL"),
+            Instruction::Exit => write!(f, "EXIT"),
+            Instruction::LowRes => write!(f, "LOW"),
+            Instruction::HighRes => write!(f, "HIGH"),
+            Instruction::LoadBigFont { x } => write!(f, "LD HF, V{:X}", x),
+            Instruction::StoreFlags { x } => write!(f, "LD R, V{:X}", x),
+            Instruction::LoadFlags { x } => write!(f, "LD V{:X}, R", x),
+            Instruction::ScrollUp { n } => write!(f, "SCU 0x{:X}", n),
+            Instruction::SaveRange { x, y } => write!(f, "SAVE V{:X}, V{:X}", x, y),
+            Instruction::LoadRange { x, y } => write!(f, "LOAD V{:X}, V{:X}", x, y),
+            Instruction::LoadILong { nnnn } => write!(f, "LD I, LONG 0x{:04X}", nnnn),
+            Instruction::SelectPlanes { n } => write!(f, "PLANE 0x{:X}", n),
+            Instruction::LoadAudio => write!(f, "AUDIO"),
+            Instruction::SetPitch { x } => write!(f, "PITCH V{:X}", x),
+        }
+    }
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Unrecognised instruction 0x{:04X}.", self.opcode)
     }
 }
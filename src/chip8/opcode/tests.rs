@@ -0,0 +1,60 @@
+use chip8::opcode::{DecodeError, Instruction, Opcode};
+
+#[test]
+fn test_decode_encode_round_trip() {
+    for word in 0..=0xFFFFu16 {
+        if let Ok(instruction) = Instruction::decode(word) {
+            assert_eq!(instruction.encode(), word, "{}", instruction);
+        }
+    }
+}
+
+#[test]
+fn test_decode() {
+    assert_eq!(Instruction::decode(0x00E0), Ok(Instruction::Clear));
+    assert_eq!(Instruction::decode(0x8124), Ok(Instruction::AddRegReg { x: 1, y: 2 }));
+    assert_eq!(Instruction::decode(0xD345), Ok(Instruction::Draw { x: 3, y: 4, n: 5 }));
+    assert_eq!(Instruction::decode(0xA2F0), Ok(Instruction::LoadI { nnn: 0x2F0 }));
+    assert_eq!(Instruction::decode(0x5121), Err(DecodeError { opcode: 0x5121 }));
+    assert_eq!(Instruction::decode(0xE0FF), Err(DecodeError { opcode: 0xE0FF }));
+}
+
+#[test]
+fn test_decode_long_load() {
+    assert_eq!(Instruction::decode(0xF000), Err(DecodeError { opcode: 0xF000 }));
+    let instruction = Instruction::decode_pair(0xF000, 0xABCD).unwrap();
+    assert_eq!(instruction, Instruction::LoadILong { nnnn: 0xABCD });
+    assert_eq!(instruction.size(), 4);
+    assert_eq!(instruction.to_bytes(), vec![0xF0, 0x00, 0xAB, 0xCD]);
+    assert_eq!(Instruction::decode_pair(0x00E0, 0xABCD), Ok(Instruction::Clear));
+}
+
+#[test]
+fn test_pattern_matches_every_opcode() {
+    for word in 0..=0xFFFFu16 {
+        if let Ok(instruction) = Instruction::decode_pair(word, 0) {
+            let digits = format!("{:04X}", word);
+            let matches = instruction
+                .pattern()
+                .chars()
+                .zip(digits.chars())
+                .all(|(pattern, digit)| "XYN".contains(pattern) || pattern == digit);
+            assert!(matches, "{} is not {}", digits, instruction.pattern());
+        }
+    }
+}
+
+#[test]
+fn test_display() {
+    assert_eq!(Instruction::Draw { x: 0xA, y: 0xB, n: 0xF }.to_string(), "DRW VA, VB, 0xF");
+    assert_eq!(Instruction::SkipEqImm { x: 2, nn: 0x1F }.to_string(), "SE V2, 0x1F");
+    assert_eq!(Instruction::StoreRegisters { x: 0xE }.to_string(), "LD [I], VE");
+}
+
+#[test]
+fn test_opcode_from_bytes_sets_address() {
+    let opcode = Opcode::from((0x1A, 0xBC));
+    assert_eq!(opcode.nnn, 0xABC);
+    assert_eq!(opcode.x, 0xA);
+    assert_eq!(opcode.n, 0xC);
+}
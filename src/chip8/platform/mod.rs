@@ -0,0 +1,48 @@
+use chip8::Quirks;
+
+/// The interpreter whose instruction set is being emulated.
+///
+/// Each platform is a superset of the ones before it, so platforms can be
+/// compared to check whether an instruction is available.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Platform {
+    /// The original CHIP-8 instruction set.
+    #[default]
+    Chip8,
+    /// SUPER-CHIP 1.1, adding a 128x64 high resolution mode, scrolling,
+    /// 16x16 sprites, a large font and the RPL flag registers.
+    SuperChip,
+    /// XO-CHIP, adding 64K of memory, a second bitplane for four colour
+    /// drawing and programmable audio on top of SUPER-CHIP.
+    XoChip,
+}
+
+impl Platform {
+    /// The quirks ROMs for this platform usually expect.
+    pub fn quirks(self) -> Quirks {
+        match self {
+            Platform::Chip8 => Quirks::vip(),
+            Platform::SuperChip => Quirks::schip(),
+            Platform::XoChip => Quirks::xochip(),
+        }
+    }
+
+    /// A typical number of instructions executed per 60 Hz frame for ROMs
+    /// written for this platform.
+    pub fn instructions_per_frame(self) -> usize {
+        match self {
+            Platform::Chip8 => 15,
+            Platform::SuperChip => 30,
+            Platform::XoChip => 1000,
+        }
+    }
+
+    /// Size of the address space in bytes.
+    pub fn memory_size(self) -> usize {
+        match self {
+            Platform::Chip8 | Platform::SuperChip => 0x1000,
+            Platform::XoChip => 0x10000,
+        }
+    }
+}
+
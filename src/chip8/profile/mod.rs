@@ -0,0 +1,344 @@
+#[cfg(test)]
+mod tests;
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use chip8::opcode::Instruction;
+use chip8::{Chip8, ExecutionError};
+use core::cmp::Reverse;
+use core::fmt::{self, Display, Formatter, Write};
+
+/// The longest loop, in instructions, around an `FX07` that is counted as
+/// waiting for the delay timer.
+const BUSY_LOOP_LENGTH: u64 = 4;
+/// How many of the busiest addresses the text report lists.
+const HOT_SPOTS: usize = 20;
+
+/// Counts what a program spends its instructions on, to tune it to an
+/// instructions-per-frame budget.
+///
+/// Drive the machine through [`Profiler::run_frame`] instead of
+/// [`Chip8::run_frame`], pressing keys in between as usual, then take a
+/// [`Profile`] with [`Profiler::profile`].
+///
+/// Subroutines are found by following `2NNN` and `00EE`, so a program that
+/// manipulates the stack some other way confuses the call graph but not
+/// the other counts.
+#[derive(Debug, Default)]
+pub struct Profiler {
+    frames: u64,
+    instructions: u64,
+    addresses: BTreeMap<usize, u64>,
+    opcodes: BTreeMap<&'static str, u64>,
+    subroutines: BTreeMap<usize, Subroutine>,
+    calls: BTreeMap<(usize, usize), u64>,
+    stack: Vec<Frame>,
+    waits: Waits,
+    /// Where and when the delay timer was last read while still running.
+    delay_read: Option<(usize, u64)>,
+}
+
+/// A subroutine being executed, from its entry point.
+#[derive(Debug)]
+struct Frame {
+    entry: usize,
+    /// The instruction count when it was called.
+    start: u64,
+}
+
+/// Instructions counted against one subroutine.
+///
+/// Inclusive counts include the subroutines it calls, exclusive counts
+/// don't. A recursive subroutine's inclusive count only covers its
+/// outermost calls.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Subroutine {
+    pub calls: u64,
+    pub inclusive: u64,
+    pub exclusive: u64,
+}
+
+/// Instruction slots spent not getting anything done.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Waits {
+    /// Slots left in each frame while `FX0A` waits for a key.
+    pub key: u64,
+    /// `DXYN`s retried because the display wait quirk holds them until the
+    /// next frame.
+    pub display: u64,
+    /// Instructions in short loops that read the delay timer until it runs
+    /// out.
+    pub delay: u64,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Profiler::default()
+    }
+
+    /// Runs one frame like [`Chip8::run_frame`], counting each instruction
+    /// and the slots left over while waiting for a key.
+    pub fn run_frame(&mut self, chip8: &mut Chip8, instructions_per_frame: usize) -> Result<(), ExecutionError> {
+        self.frames += 1;
+        for slot in 0..instructions_per_frame {
+            if chip8.exited {
+                break;
+            }
+            if chip8.is_waiting_for_key() {
+                self.waits.key += (instructions_per_frame - slot) as u64;
+                break;
+            }
+            self.step(chip8)?;
+        }
+        chip8.tick_timers();
+        Ok(())
+    }
+
+    /// Executes and counts a single instruction. Instructions that fail are
+    /// not counted.
+    pub fn step(&mut self, chip8: &mut Chip8) -> Result<(), ExecutionError> {
+        let (pc, sp) = (chip8.pc, chip8.sp);
+        let word = |address: usize| {
+            if address + 1 < chip8.memory.len() {
+                u16::from(chip8.memory.peek(address)) << 8 | u16::from(chip8.memory.peek(address + 1))
+            } else {
+                0
+            }
+        };
+        let decoded = Instruction::decode_pair(word(pc), word(pc + 2));
+        chip8.step()?;
+        if let Ok(instruction) = decoded {
+            self.count(chip8, pc, sp, instruction);
+        }
+        Ok(())
+    }
+
+    fn count(&mut self, chip8: &Chip8, pc: usize, sp: usize, instruction: Instruction) {
+        let index = self.instructions;
+        self.instructions += 1;
+        *self.addresses.entry(pc).or_insert(0) += 1;
+        *self.opcodes.entry(instruction.pattern()).or_insert(0) += 1;
+        if self.stack.is_empty() {
+            //Whatever runs first stands in for the program's main loop
+            self.stack.push(Frame {
+                entry: pc,
+                start: index,
+            });
+        }
+        let caller = self.stack[self.stack.len() - 1].entry;
+        self.subroutines.entry(caller).or_default().exclusive += 1;
+
+        match instruction {
+            Instruction::Call { nnn } if chip8.sp > sp => {
+                let entry = usize::from(nnn);
+                self.stack.push(Frame {
+                    entry,
+                    start: self.instructions,
+                });
+                self.subroutines.entry(entry).or_default().calls += 1;
+                *self.calls.entry((caller, entry)).or_insert(0) += 1;
+            }
+            Instruction::Return if chip8.sp < sp && self.stack.len() > 1 => {
+                let frame = self.stack.pop().unwrap();
+                if self.stack.iter().all(|outer| outer.entry != frame.entry) {
+                    self.subroutines.entry(frame.entry).or_default().inclusive += self.instructions - frame.start;
+                }
+            }
+            Instruction::Draw { .. } if chip8.pc == pc => self.waits.display += 1,
+            Instruction::LoadDelay { x } => {
+                if let Some((address, since)) = self.delay_read {
+                    if address == pc && index - since <= BUSY_LOOP_LENGTH {
+                        self.waits.delay += index - since;
+                    }
+                }
+                self.delay_read = if chip8.V[x] != 0 { Some((pc, index)) } else { None };
+            }
+            _ => {}
+        }
+    }
+
+    /// What has been counted so far. Subroutines that haven't returned yet
+    /// are counted up to now.
+    pub fn profile(&self) -> Profile {
+        let mut subroutines = self.subroutines.clone();
+        for (depth, frame) in self.stack.iter().enumerate() {
+            if self.stack[..depth].iter().all(|outer| outer.entry != frame.entry) {
+                subroutines.entry(frame.entry).or_default().inclusive += self.instructions - frame.start;
+            }
+        }
+        let entry = self.stack.first().map(|frame| frame.entry);
+
+        let mut addresses: Vec<(usize, u64)> = self
+            .addresses
+            .iter()
+            .map(|(&address, &count)| (address, count))
+            .collect();
+        addresses.sort_by_key(|&(_, count)| Reverse(count));
+        let mut subroutines: Vec<(usize, Subroutine)> = subroutines.into_iter().collect();
+        subroutines.sort_by_key(|&(_, subroutine)| Reverse(subroutine.inclusive));
+        let mut calls: Vec<Call> = self
+            .calls
+            .iter()
+            .map(|(&(caller, callee), &count)| Call { caller, callee, count })
+            .collect();
+        calls.sort_by_key(|call| Reverse(call.count));
+        let mut opcodes: Vec<(&'static str, u64)> =
+            self.opcodes.iter().map(|(&opcode, &count)| (opcode, count)).collect();
+        opcodes.sort_by_key(|&(_, count)| Reverse(count));
+        Profile {
+            frames: self.frames,
+            instructions: self.instructions,
+            entry,
+            addresses,
+            subroutines,
+            calls,
+            opcodes,
+            waits: self.waits,
+        }
+    }
+}
+
+/// How often one subroutine called another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Call {
+    pub caller: usize,
+    pub callee: usize,
+    pub count: u64,
+}
+
+/// A report from a [`Profiler`], busiest first throughout. `Display` gives
+/// the text report, [`Profile::to_json`] the same for other tools.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    pub frames: u64,
+    pub instructions: u64,
+    /// Where the program was when profiling started, which is counted as a
+    /// subroutine of its own.
+    pub entry: Option<usize>,
+    /// Instructions executed at each address.
+    pub addresses: Vec<(usize, u64)>,
+    pub subroutines: Vec<(usize, Subroutine)>,
+    pub calls: Vec<Call>,
+    /// Instructions executed of each kind, by [`Instruction::pattern`].
+    pub opcodes: Vec<(&'static str, u64)>,
+    pub waits: Waits,
+}
+
+impl Profile {
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        let _ = write!(
+            out,
+            "{{\"frames\":{},\"instructions\":{},\"entry\":",
+            self.frames, self.instructions
+        );
+        match self.entry {
+            Some(entry) => {
+                let _ = write!(out, "{}", entry);
+            }
+            None => out.push_str("null"),
+        }
+        let _ = write!(
+            out,
+            ",\"waits\":{{\"key\":{},\"display\":{},\"delay\":{}}}",
+            self.waits.key, self.waits.display, self.waits.delay
+        );
+        let addresses: Vec<String> = self
+            .addresses
+            .iter()
+            .map(|&(address, count)| format!("{{\"address\":{},\"count\":{}}}", address, count))
+            .collect();
+        let _ = write!(out, ",\"addresses\":[{}]", addresses.join(","));
+        let subroutines: Vec<String> = self
+            .subroutines
+            .iter()
+            .map(|&(address, subroutine)| {
+                format!(
+                    "{{\"address\":{},\"calls\":{},\"inclusive\":{},\"exclusive\":{}}}",
+                    address, subroutine.calls, subroutine.inclusive, subroutine.exclusive
+                )
+            })
+            .collect();
+        let _ = write!(out, ",\"subroutines\":[{}]", subroutines.join(","));
+        let calls: Vec<String> = self
+            .calls
+            .iter()
+            .map(|call| {
+                format!(
+                    "{{\"caller\":{},\"callee\":{},\"count\":{}}}",
+                    call.caller, call.callee, call.count
+                )
+            })
+            .collect();
+        let _ = write!(out, ",\"calls\":[{}]", calls.join(","));
+        let opcodes: Vec<String> = self
+            .opcodes
+            .iter()
+            .map(|&(opcode, count)| format!("{{\"opcode\":\"{}\",\"count\":{}}}", opcode, count))
+            .collect();
+        let _ = write!(out, ",\"opcodes\":[{}]}}", opcodes.join(","));
+        out
+    }
+
+    fn share(&self, count: u64) -> f64 {
+        if self.instructions == 0 {
+            0.0
+        } else {
+            100.0 * count as f64 / self.instructions as f64
+        }
+    }
+}
+
+impl Display for Profile {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let per_frame = if self.frames == 0 {
+            0.0
+        } else {
+            self.instructions as f64 / self.frames as f64
+        };
+        writeln!(
+            f,
+            "{} instructions over {} frames, {:.1} per frame",
+            self.instructions, self.frames, per_frame
+        )?;
+        writeln!(
+            f,
+            "Waiting: {} slots on FX0A, {} on the display, {} in delay timer loops",
+            self.waits.key, self.waits.display, self.waits.delay
+        )?;
+
+        writeln!(f, "\nHot spots:")?;
+        for &(address, count) in self.addresses.iter().take(HOT_SPOTS) {
+            writeln!(f, "  0x{:03X} {:>10} {:>5.1}%", address, count, self.share(count))?;
+        }
+        if self.addresses.len() > HOT_SPOTS {
+            writeln!(f, "  ...and {} more addresses", self.addresses.len() - HOT_SPOTS)?;
+        }
+
+        writeln!(f, "\nSubroutines:      calls  inclusive  exclusive")?;
+        for &(address, subroutine) in &self.subroutines {
+            let label = if Some(address) == self.entry {
+                " (entry)"
+            } else {
+                "        "
+            };
+            writeln!(
+                f,
+                "  0x{:03X}{} {:>8} {:>10} {:>10}",
+                address, label, subroutine.calls, subroutine.inclusive, subroutine.exclusive
+            )?;
+        }
+
+        writeln!(f, "\nCalls:")?;
+        for call in &self.calls {
+            writeln!(f, "  0x{:03X} -> 0x{:03X} {:>8}", call.caller, call.callee, call.count)?;
+        }
+
+        write!(f, "\nOpcodes:")?;
+        for &(opcode, count) in &self.opcodes {
+            write!(f, "\n  {} {:>10} {:>5.1}%", opcode, count, self.share(count))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,91 @@
+use chip8::profile::{Call, Profiler, Subroutine, Waits};
+use chip8::{Chip8, Quirks};
+
+fn profiled(program: Vec<u8>, quirks: Quirks) -> (Chip8, Profiler) {
+    let mut cpu = Chip8::new(quirks);
+    cpu.load_program(program);
+    (cpu, Profiler::new())
+}
+
+#[test]
+fn test_subroutines_and_calls() {
+    //Call 0x206 twice, which calls 0x20C, then spin on 0x204
+    let (mut cpu, mut profiler) = profiled(
+        vec!(
+            0x22, 0x06, 0x22, 0x06, 0x12, 0x04, 0x22, 0x0C, 0x71, 0x01, 0x00, 0xEE, 0x72, 0x01, 0x00, 0xEE,
+        ),
+        Quirks::vip(),
+    );
+    for _ in 0..14 {
+        profiler.step(&mut cpu).unwrap();
+    }
+    let profile = profiler.profile();
+    assert_eq!(profile.instructions, 14);
+    assert_eq!(profile.entry, Some(0x200));
+    assert_eq!(profile.addresses[0], (0x204, 2));
+    assert_eq!(
+        profile.subroutines,
+        vec!(
+            (0x200, Subroutine { calls: 0, inclusive: 14, exclusive: 4 }),
+            (0x206, Subroutine { calls: 2, inclusive: 10, exclusive: 6 }),
+            (0x20C, Subroutine { calls: 2, inclusive: 4, exclusive: 4 }),
+        )
+    );
+    assert_eq!(
+        profile.calls,
+        vec!(
+            Call { caller: 0x200, callee: 0x206, count: 2 },
+            Call { caller: 0x206, callee: 0x20C, count: 2 },
+        )
+    );
+    assert_eq!(profile.opcodes, vec!(("00EE", 4), ("2NNN", 4), ("7XNN", 4), ("1NNN", 2)));
+}
+
+#[test]
+fn test_key_waits_and_json() {
+    let (mut cpu, mut profiler) = profiled(vec!(0xF0, 0x0A), Quirks::vip());
+    profiler.run_frame(&mut cpu, 10).unwrap();
+    profiler.run_frame(&mut cpu, 10).unwrap();
+    let profile = profiler.profile();
+    assert_eq!(profile.waits, Waits { key: 19, display: 0, delay: 0 });
+    assert_eq!(
+        profile.to_json(),
+        "{\"frames\":2,\"instructions\":1,\"entry\":512,\"waits\":{\"key\":19,\"display\":0,\"delay\":0},\
+         \"addresses\":[{\"address\":512,\"count\":1}],\
+         \"subroutines\":[{\"address\":512,\"calls\":0,\"inclusive\":1,\"exclusive\":1}],\
+         \"calls\":[],\"opcodes\":[{\"opcode\":\"FX0A\",\"count\":1}]}"
+    );
+}
+
+#[test]
+fn test_delay_timer_loops() {
+    //Set the delay timer to 1 and loop at 0x204 until it runs out
+    let (mut cpu, mut profiler) = profiled(
+        vec!(0x60, 0x01, 0xF0, 0x15, 0xF0, 0x07, 0x30, 0x00, 0x12, 0x04, 0x12, 0x0A),
+        Quirks::vip(),
+    );
+    profiler.run_frame(&mut cpu, 7).unwrap();
+    profiler.run_frame(&mut cpu, 7).unwrap();
+    assert_eq!(profiler.profile().waits.delay, 6);
+}
+
+#[test]
+fn test_display_waits() {
+    let (mut cpu, mut profiler) = profiled(vec!(0xD0, 0x01, 0xD0, 0x01), Quirks::vip());
+    cpu.quirks.display_wait = true;
+    profiler.run_frame(&mut cpu, 5).unwrap();
+    let profile = profiler.profile();
+    assert_eq!(profile.waits.display, 4);
+    assert_eq!(profile.addresses[0], (0x202, 4));
+}
+
+#[test]
+fn test_text_report() {
+    let (mut cpu, mut profiler) = profiled(vec!(0x71, 0x01, 0x12, 0x00), Quirks::vip());
+    profiler.run_frame(&mut cpu, 4).unwrap();
+    let report = profiler.profile().to_string();
+    assert!(report.starts_with("4 instructions over 1 frames, 4.0 per frame\n"));
+    assert!(report.contains("\n  0x200          2  50.0%\n"));
+    assert!(report.contains("\n  0x200 (entry)        0          4          4\n"));
+    assert!(report.ends_with("\n  7XNN          2  50.0%"));
+}
@@ -0,0 +1,89 @@
+/// How `FX55`/`FX65` leave `I` once the registers have been transferred.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryIncrement {
+    /// `I` ends up pointing past the last register, `I += X + 1` (COSMAC VIP).
+    ByXPlusOne,
+    /// `I += X`, off by one from the VIP (CHIP-48).
+    ByX,
+    /// `I` is left untouched (SUPER-CHIP).
+    Unchanged,
+}
+
+/// Behaviours that differ between CHIP-8 interpreters.
+///
+/// ROMs tend to be written against one particular interpreter, so these are
+/// chosen per ROM. The presets cover the common platforms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// `8XY6`/`8XYE` shift VY and store the result in VX, rather than
+    /// shifting VX in place.
+    pub shift_uses_vy: bool,
+    /// What `FX55`/`FX65` do to `I`.
+    pub memory_increment: MemoryIncrement,
+    /// `BNNN` is read as `BXNN` and jumps to `XNN + VX` instead of `NNN + V0`.
+    pub jump_uses_vx: bool,
+    /// `8XY1`/`8XY2`/`8XY3` reset VF to 0.
+    pub logic_resets_vf: bool,
+    /// Sprites are clipped at the edges of the screen instead of wrapping
+    /// around. The starting position always wraps.
+    pub clip_sprites: bool,
+    /// `DXYN` waits for the next vertical blank before drawing, limiting
+    /// drawing to one sprite per frame.
+    pub display_wait: bool,
+}
+
+impl Quirks {
+    /// The original COSMAC VIP interpreter.
+    pub fn vip() -> Self {
+        Quirks {
+            shift_uses_vy: true,
+            memory_increment: MemoryIncrement::ByXPlusOne,
+            jump_uses_vx: false,
+            logic_resets_vf: true,
+            clip_sprites: true,
+            display_wait: true,
+        }
+    }
+
+    /// CHIP-48 on the HP-48 calculators.
+    pub fn chip48() -> Self {
+        Quirks {
+            shift_uses_vy: false,
+            memory_increment: MemoryIncrement::ByX,
+            jump_uses_vx: true,
+            logic_resets_vf: false,
+            clip_sprites: true,
+            display_wait: false,
+        }
+    }
+
+    /// SUPER-CHIP 1.1.
+    pub fn schip() -> Self {
+        Quirks {
+            shift_uses_vy: false,
+            memory_increment: MemoryIncrement::Unchanged,
+            jump_uses_vx: true,
+            logic_resets_vf: false,
+            clip_sprites: true,
+            display_wait: false,
+        }
+    }
+
+    /// XO-CHIP as implemented by Octo.
+    pub fn xochip() -> Self {
+        Quirks {
+            shift_uses_vy: true,
+            memory_increment: MemoryIncrement::ByXPlusOne,
+            jump_uses_vx: false,
+            logic_resets_vf: false,
+            clip_sprites: false,
+            display_wait: false,
+        }
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks::vip()
+    }
+}
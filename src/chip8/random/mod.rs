@@ -0,0 +1,116 @@
+#[cfg(test)]
+mod tests;
+
+use alloc::vec::Vec;
+use chip8::StateError;
+
+/// Where `CXNN` gets its random bytes from.
+///
+/// The generator's state is part of a saved machine, so implementations
+/// have to be able to write it out and read it back.
+///
+/// There is no emulation of the COSMAC VIP interpreter's own routine. It
+/// mixes a counter with bytes of the interpreter's machine code and 1802
+/// register state, none of which exist here.
+pub trait RandomSource {
+    /// Identifies the kind of generator in saved states, so a state is
+    /// never restored into a different kind.
+    fn name(&self) -> &'static str;
+
+    fn next_byte(&mut self) -> u8;
+
+    fn save(&self) -> Vec<u8>;
+
+    /// Restores state written by [`RandomSource::save`]. Leaves the
+    /// generator untouched if it cannot be read.
+    fn restore(&mut self, state: &[u8]) -> Result<(), StateError>;
+}
+
+/// The default generator, SplitMix64 started from a fixed seed so runs are
+/// reproducible.
+pub struct SplitMix {
+    state: u64,
+}
+
+impl SplitMix {
+    pub fn new(seed: u64) -> Self {
+        SplitMix { state: seed }
+    }
+}
+
+impl Default for SplitMix {
+    fn default() -> Self {
+        SplitMix::new(0x43_48_49_50_2D_38)
+    }
+}
+
+impl RandomSource for SplitMix {
+    fn name(&self) -> &'static str {
+        "splitmix64"
+    }
+
+    fn next_byte(&mut self) -> u8 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        (z >> 56) as u8
+    }
+
+    fn save(&self) -> Vec<u8> {
+        self.state.to_be_bytes().to_vec()
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), StateError> {
+        if state.len() != 8 {
+            return Err(StateError::Invalid("random source state"));
+        }
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(state);
+        self.state = u64::from_be_bytes(bytes);
+        Ok(())
+    }
+}
+
+/// Plays back a fixed list of bytes, starting over once it runs out. Makes
+/// tests that touch `CXNN` predictable.
+pub struct Sequence {
+    bytes: Vec<u8>,
+    position: usize,
+}
+
+impl Sequence {
+    pub fn new(bytes: Vec<u8>) -> Self {
+        assert!(!bytes.is_empty(), "A random sequence needs at least one byte.");
+        Sequence { bytes, position: 0 }
+    }
+}
+
+impl RandomSource for Sequence {
+    fn name(&self) -> &'static str {
+        "sequence"
+    }
+
+    fn next_byte(&mut self) -> u8 {
+        let byte = self.bytes[self.position];
+        self.position = (self.position + 1) % self.bytes.len();
+        byte
+    }
+
+    fn save(&self) -> Vec<u8> {
+        (self.position as u32).to_be_bytes().to_vec()
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), StateError> {
+        if state.len() != 4 {
+            return Err(StateError::Invalid("random source state"));
+        }
+        let position = u32::from_be_bytes([state[0], state[1], state[2], state[3]]) as usize;
+        if position >= self.bytes.len() {
+            return Err(StateError::Invalid("random source state"));
+        }
+        self.position = position;
+        Ok(())
+    }
+}
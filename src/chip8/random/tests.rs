@@ -0,0 +1,41 @@
+use chip8::random::{RandomSource, Sequence, SplitMix};
+use chip8::StateError;
+
+#[test]
+fn test_same_seed_same_bytes() {
+    let mut first = SplitMix::new(1234);
+    let mut second = SplitMix::new(1234);
+    let mut other = SplitMix::new(4321);
+    let first_bytes: Vec<u8> = (0..32).map(|_| first.next_byte()).collect();
+    let second_bytes: Vec<u8> = (0..32).map(|_| second.next_byte()).collect();
+    let other_bytes: Vec<u8> = (0..32).map(|_| other.next_byte()).collect();
+    assert_eq!(first_bytes, second_bytes);
+    assert_ne!(first_bytes, other_bytes);
+}
+
+#[test]
+fn test_split_mix_save_and_restore() {
+    let mut rng = SplitMix::new(99);
+    rng.next_byte();
+    let state = rng.save();
+    let expected: Vec<u8> = (0..8).map(|_| rng.next_byte()).collect();
+
+    let mut restored = SplitMix::default();
+    restored.restore(&state).unwrap();
+    let actual: Vec<u8> = (0..8).map(|_| restored.next_byte()).collect();
+    assert_eq!(actual, expected);
+    assert_eq!(restored.restore(&[1, 2]), Err(StateError::Invalid("random source state")));
+}
+
+#[test]
+fn test_sequence_repeats() {
+    let mut rng = Sequence::new(vec![1, 2, 3]);
+    let bytes: Vec<u8> = (0..7).map(|_| rng.next_byte()).collect();
+    assert_eq!(bytes, vec![1, 2, 3, 1, 2, 3, 1]);
+
+    let state = rng.save();
+    let mut restored = Sequence::new(vec![1, 2, 3]);
+    restored.restore(&state).unwrap();
+    assert_eq!(restored.next_byte(), 2);
+    assert!(Sequence::new(vec![1]).restore(&state).is_err());
+}
@@ -0,0 +1,206 @@
+#[cfg(test)]
+mod tests;
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use chip8::{Chip8, StateError};
+
+/// A bounded history of machine snapshots for stepping gameplay backwards.
+///
+/// Only the newest snapshot is kept whole. Each older one is stored as the
+/// run length encoded XOR against the snapshot after it, which is mostly
+/// zeroes from one frame to the next. Once the history grows past its
+/// memory budget the oldest snapshots are dropped. The newest snapshot is
+/// always kept, even when it alone is bigger than the budget.
+pub struct Rewind {
+    budget: usize,
+    interval: usize,
+    frames_since_snapshot: usize,
+    latest: Option<Vec<u8>>,
+    deltas: VecDeque<Delta>,
+    used: usize,
+}
+
+enum Delta {
+    /// RLE compressed XOR against the next newer snapshot.
+    Xor(Vec<u8>),
+    /// The snapshot itself, used when the sizes differ so XOR is impossible.
+    Full(Vec<u8>),
+}
+
+impl Delta {
+    fn len(&self) -> usize {
+        match *self {
+            Delta::Xor(ref bytes) | Delta::Full(ref bytes) => bytes.len(),
+        }
+    }
+}
+
+impl Rewind {
+    /// `budget` is the most memory in bytes the history may use, beyond the
+    /// newest snapshot which is always kept, and `interval` the number of
+    /// frames between snapshots.
+    pub fn new(budget: usize, interval: usize) -> Self {
+        Rewind {
+            budget,
+            interval: interval.max(1),
+            frames_since_snapshot: 0,
+            latest: None,
+            deltas: VecDeque::new(),
+            used: 0,
+        }
+    }
+
+    /// Call once per frame. Takes a snapshot every `interval` frames.
+    pub fn record(&mut self, chip8: &Chip8) {
+        self.frames_since_snapshot += 1;
+        if self.latest.is_some() && self.frames_since_snapshot < self.interval {
+            return;
+        }
+        self.frames_since_snapshot = 0;
+
+        let snapshot = chip8.save_state();
+        self.used -= self.latest_len();
+        if let Some(previous) = self.latest.take() {
+            let delta = if previous.len() == snapshot.len() {
+                Delta::Xor(encode_delta(&snapshot, &previous))
+            } else {
+                Delta::Full(previous)
+            };
+            self.used += delta.len();
+            self.deltas.push_back(delta);
+        }
+        self.used += snapshot.len();
+        self.latest = Some(snapshot);
+
+        while self.used > self.budget {
+            match self.deltas.pop_front() {
+                Some(oldest) => self.used -= oldest.len(),
+                None => break,
+            }
+        }
+    }
+
+    /// Steps `chip8` back by `frames` frames, rounded up to the next older
+    /// snapshot, and forgets everything after that point. Returns how many
+    /// frames it actually went back, which is less than asked for once the
+    /// history runs out. If the snapshot cannot be loaded the machine and the
+    /// history are left as they were.
+    pub fn rewind(&mut self, chip8: &mut Chip8, frames: usize) -> Result<usize, StateError> {
+        //The newest snapshot is itself frames_since_snapshot frames back
+        let steps = frames
+            .saturating_sub(self.frames_since_snapshot)
+            .div_ceil(self.interval)
+            .min(self.deltas.len());
+        let mut snapshot = match self.latest {
+            Some(ref latest) if frames > 0 && (steps > 0 || self.frames_since_snapshot > 0) => latest.clone(),
+            _ => return Ok(0),
+        };
+        for delta in self.deltas.iter().rev().take(steps) {
+            match *delta {
+                Delta::Xor(ref encoded) => apply_delta(&mut snapshot, encoded),
+                Delta::Full(ref previous) => snapshot = previous.clone(),
+            }
+        }
+        chip8.load_state(&snapshot)?;
+
+        let travelled = self.frames_since_snapshot + steps * self.interval;
+        for _ in 0..steps {
+            let delta = self.deltas.pop_back().expect("counted above");
+            self.used -= delta.len();
+        }
+        self.used -= self.latest_len();
+        self.used += snapshot.len();
+        self.latest = Some(snapshot);
+        self.frames_since_snapshot = 0;
+        Ok(travelled)
+    }
+
+    /// Number of frames that can currently be rewound.
+    pub fn available_frames(&self) -> usize {
+        match self.latest {
+            Some(_) => self.frames_since_snapshot + self.deltas.len() * self.interval,
+            None => 0,
+        }
+    }
+
+    /// Bytes currently used by the history.
+    pub fn memory_used(&self) -> usize {
+        self.used
+    }
+
+    pub fn clear(&mut self) {
+        self.latest = None;
+        self.deltas.clear();
+        self.used = 0;
+        self.frames_since_snapshot = 0;
+    }
+
+    fn latest_len(&self) -> usize {
+        self.latest.as_ref().map_or(0, Vec::len)
+    }
+}
+
+/// Encodes `older XOR newer` as alternating runs: a LEB128 count of zero
+/// bytes, then a LEB128 count of literal bytes followed by those bytes.
+fn encode_delta(newer: &[u8], older: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < newer.len() {
+        let zeroes_start = i;
+        while i < newer.len() && newer[i] == older[i] {
+            i += 1;
+        }
+        let literal_start = i;
+        while i < newer.len() && newer[i] != older[i] {
+            i += 1;
+        }
+        write_length(&mut out, literal_start - zeroes_start);
+        write_length(&mut out, i - literal_start);
+        for j in literal_start..i {
+            out.push(newer[j] ^ older[j]);
+        }
+    }
+    out
+}
+
+/// Turns `snapshot` into the older snapshot described by `encoded`.
+fn apply_delta(snapshot: &mut [u8], encoded: &[u8]) {
+    let mut position = 0;
+    let mut i = 0;
+    while position < encoded.len() {
+        i += read_length(encoded, &mut position);
+        let literals = read_length(encoded, &mut position);
+        for byte in &encoded[position..position + literals] {
+            snapshot[i] ^= byte;
+            i += 1;
+        }
+        position += literals;
+    }
+}
+
+fn write_length(out: &mut Vec<u8>, mut length: usize) {
+    loop {
+        let byte = (length & 0x7F) as u8;
+        length >>= 7;
+        if length == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn read_length(encoded: &[u8], position: &mut usize) -> usize {
+    let mut length = 0;
+    let mut shift = 0;
+    loop {
+        let byte = encoded[*position];
+        *position += 1;
+        length |= usize::from(byte & 0x7F) << shift;
+        if byte & 0x80 == 0 {
+            return length;
+        }
+        shift += 7;
+    }
+}
@@ -0,0 +1,119 @@
+use chip8::rewind::Rewind;
+use chip8::{Chip8, Quirks};
+
+//Count up in V0 and store it in memory every instruction
+fn counting_cpu() -> Chip8 {
+    let mut cpu = Chip8::new(Quirks::vip());
+    cpu.load_program(vec!(0xA3, 0x00, 0x70, 0x01, 0xF0, 0x55, 0x12, 0x02));
+    cpu
+}
+
+#[test]
+fn test_rewind_restores_earlier_frames() {
+    let mut cpu = counting_cpu();
+    let mut rewind = Rewind::new(1 << 20, 1);
+    let mut states = Vec::new();
+    for _ in 0..10 {
+        cpu.run_frame(6).unwrap();
+        rewind.record(&cpu);
+        states.push(cpu.save_state());
+    }
+    assert_eq!(rewind.available_frames(), 9);
+
+    assert_eq!(rewind.rewind(&mut cpu, 3).unwrap(), 3);
+    assert_eq!(cpu.save_state(), states[6]);
+    assert_eq!(rewind.rewind(&mut cpu, 1).unwrap(), 1);
+    assert_eq!(cpu.save_state(), states[5]);
+
+    //History after the rewind point is replaced by what happens next
+    cpu.run_frame(6).unwrap();
+    rewind.record(&cpu);
+    assert_eq!(rewind.rewind(&mut cpu, 1).unwrap(), 1);
+    assert_eq!(cpu.save_state(), states[5]);
+}
+
+#[test]
+fn test_rewind_stops_at_oldest_snapshot() {
+    let mut cpu = counting_cpu();
+    let mut rewind = Rewind::new(1 << 20, 1);
+    rewind.record(&cpu);
+    let first = cpu.save_state();
+    cpu.run_frame(6).unwrap();
+    rewind.record(&cpu);
+    assert_eq!(rewind.rewind(&mut cpu, 50).unwrap(), 1);
+    assert_eq!(cpu.save_state(), first);
+    assert_eq!(rewind.rewind(&mut cpu, 1).unwrap(), 0);
+}
+
+#[test]
+fn test_snapshot_interval() {
+    let mut cpu = counting_cpu();
+    let mut rewind = Rewind::new(1 << 20, 4);
+    let mut states = Vec::new();
+    for _ in 0..12 {
+        cpu.run_frame(6).unwrap();
+        rewind.record(&cpu);
+        states.push(cpu.save_state());
+    }
+    //Snapshots were taken on frames 0, 4 and 8, and this is frame 11
+    assert_eq!(rewind.available_frames(), 11);
+    assert_eq!(rewind.rewind(&mut cpu, 4).unwrap(), 7);
+    assert_eq!(cpu.save_state(), states[4]);
+    assert_eq!(rewind.rewind(&mut cpu, 1).unwrap(), 4);
+    assert_eq!(cpu.save_state(), states[0]);
+}
+
+#[test]
+fn test_rewind_to_newest_snapshot() {
+    let mut cpu = counting_cpu();
+    let mut rewind = Rewind::new(1 << 20, 4);
+    let mut states = Vec::new();
+    for _ in 0..7 {
+        cpu.run_frame(6).unwrap();
+        rewind.record(&cpu);
+        states.push(cpu.save_state());
+    }
+    assert_eq!(rewind.rewind(&mut cpu, 2).unwrap(), 2);
+    assert_eq!(cpu.save_state(), states[4]);
+}
+
+#[test]
+fn test_rewind_without_history() {
+    let mut cpu = counting_cpu();
+    let mut rewind = Rewind::new(1 << 20, 1);
+    cpu.run_frame(6).unwrap();
+    let state = cpu.save_state();
+    assert_eq!(rewind.rewind(&mut cpu, 5).unwrap(), 0);
+    assert_eq!(cpu.save_state(), state);
+    assert_eq!(rewind.memory_used(), 0);
+}
+
+#[test]
+fn test_deltas_are_compact_and_budget_is_respected() {
+    let mut cpu = counting_cpu();
+    let state_size = cpu.save_state().len();
+    let budget = state_size + 2000;
+    let mut rewind = Rewind::new(budget, 1);
+    for _ in 0..1000 {
+        cpu.run_frame(6).unwrap();
+        rewind.record(&cpu);
+        assert!(rewind.memory_used() <= budget);
+    }
+    assert!(rewind.available_frames() > 50);
+    assert!(rewind.available_frames() < 1000);
+}
+
+#[test]
+fn test_budget_smaller_than_a_snapshot_keeps_the_newest() {
+    let mut cpu = counting_cpu();
+    let mut rewind = Rewind::new(16, 1);
+    for _ in 0..5 {
+        cpu.run_frame(6).unwrap();
+        rewind.record(&cpu);
+        assert_eq!(rewind.memory_used(), cpu.save_state().len());
+    }
+    let state = cpu.save_state();
+    assert_eq!(rewind.available_frames(), 0);
+    assert_eq!(rewind.rewind(&mut cpu, 1).unwrap(), 0);
+    assert_eq!(cpu.save_state(), state);
+}
@@ -0,0 +1,341 @@
+#[cfg(test)]
+mod tests;
+
+use chip8::{Chip8, KeyWait, MemoryIncrement, Platform, Quirks, HIRES_HEIGHT, HIRES_WIDTH};
+use alloc::vec::Vec;
+use core::error::Error;
+use core::fmt::{self, Display, Formatter};
+
+const MAGIC: &[u8; 4] = b"C8ST";
+
+/// The version written by [`save`]. Bump it whenever the layout changes and
+/// teach [`load`] how to read the previous one.
+pub const VERSION: u16 = 3;
+
+/// Why a saved state could not be restored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateError {
+    /// The data does not start with the save state signature.
+    NotAState,
+    /// The state was written by a newer, or unknown, format version.
+    UnsupportedVersion(u16),
+    /// The data ends before the state is complete.
+    Truncated,
+    /// A field holds a value the machine can never be in.
+    Invalid(&'static str),
+}
+
+impl Display for StateError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            StateError::NotAState => write!(f, "not a CHIP-8 save state"),
+            StateError::UnsupportedVersion(version) => write!(
+                f,
+                "save state version {} is not supported (expected at most {})",
+                version, VERSION
+            ),
+            StateError::Truncated => write!(f, "save state is truncated"),
+            StateError::Invalid(field) => write!(f, "save state has an invalid {}", field),
+        }
+    }
+}
+
+impl Error for StateError {}
+
+/// Serializes the whole machine, including its platform and quirks.
+///
+/// All multi-byte values are big endian. The display is stored as one
+/// packed bitmap per plane.
+pub fn save(chip8: &Chip8) -> Vec<u8> {
+    let mut out = Vec::with_capacity(chip8.memory.len() + 2048 + 128);
+    out.extend_from_slice(MAGIC);
+    write_u16(&mut out, VERSION);
+
+    write_settings(&mut out, chip8.platform, chip8.quirks);
+    out.extend_from_slice(&chip8.V);
+    write_u16(&mut out, chip8.I);
+    write_u32(&mut out, chip8.pc as u32);
+    out.push(chip8.sp as u8);
+    for &address in chip8.stack.iter() {
+        write_u16(&mut out, address);
+    }
+    out.push(chip8.delay);
+    out.push(chip8.sound);
+    write_u16(&mut out, pack_keyboard(&chip8.keyboard));
+
+    out.push(chip8.hires as u8 | (chip8.exited as u8) << 1 | (chip8.vblank as u8) << 2);
+    out.push(chip8.planes);
+    out.extend_from_slice(&chip8.flags);
+    out.extend_from_slice(&chip8.audio_pattern);
+    out.push(chip8.pitch);
+    for plane in [0b01, 0b10].iter() {
+        for pixels in chip8.graphics.chunks(8) {
+            let mut byte = 0;
+            for (i, &pixel) in pixels.iter().enumerate() {
+                if pixel & plane != 0 {
+                    byte |= 0x80 >> i;
+                }
+            }
+            out.push(byte);
+        }
+    }
+
+    write_u32(&mut out, chip8.memory.len() as u32);
+    out.extend_from_slice(&chip8.memory.dump());
+
+    let name = chip8.rng.name().as_bytes();
+    out.push(name.len() as u8);
+    out.extend_from_slice(name);
+    let rng_state = chip8.rng.save();
+    write_u16(&mut out, rng_state.len() as u16);
+    out.extend_from_slice(&rng_state);
+
+    out.extend_from_slice(&match chip8.key_wait {
+        KeyWait::Idle => [0, 0, 0],
+        KeyWait::Press { x } => [1, x as u8, 0],
+        KeyWait::Release { x, key } => [2, x as u8, key as u8],
+    });
+    out
+}
+
+/// Restores a state written by [`save`] into `chip8`.
+///
+/// Nothing is changed unless the whole state is valid. Version 1 states
+/// predate the random source being saved, so they leave it as it is, and
+/// states before version 3 were never halted on `FX0A`.
+///
+/// Memory is written through the existing bus, keeping any wrappers on it,
+/// unless the state is for a platform with a different amount of memory.
+pub fn load(chip8: &mut Chip8, bytes: &[u8]) -> Result<(), StateError> {
+    let mut reader = Reader::new(bytes);
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(StateError::NotAState);
+    }
+    let version = reader.u16()?;
+    if version == 0 || version > VERSION {
+        return Err(StateError::UnsupportedVersion(version));
+    }
+    let mut restored = read_v1(&mut reader)?;
+    let mut rng_state = None;
+    if version >= 2 {
+        let name_length = reader.u8()? as usize;
+        if reader.take(name_length)? != chip8.rng.name().as_bytes() {
+            return Err(StateError::Invalid("random source"));
+        }
+        let state_length = reader.u16()? as usize;
+        rng_state = Some(reader.take(state_length)?);
+    }
+    if version >= 3 {
+        let (kind, x, key) = (reader.u8()?, reader.u8()? as usize, reader.u8()? as usize);
+        if x > 0xF || key > 0xF {
+            return Err(StateError::Invalid("key wait"));
+        }
+        restored.key_wait = match kind {
+            0 => KeyWait::Idle,
+            1 => KeyWait::Press { x },
+            2 => KeyWait::Release { x, key },
+            _ => return Err(StateError::Invalid("key wait")),
+        };
+    }
+    if !reader.is_finished() {
+        return Err(StateError::Invalid("length"));
+    }
+    if let Some(rng_state) = rng_state {
+        chip8.rng.restore(rng_state)?;
+    }
+
+    if chip8.memory.len() == restored.memory.len() {
+        chip8.poke_bytes(0, &restored.memory.dump());
+    } else {
+        chip8.memory = restored.memory;
+    }
+    chip8.V = restored.V;
+    chip8.I = restored.I;
+    chip8.pc = restored.pc;
+    chip8.sp = restored.sp;
+    chip8.delay = restored.delay;
+    chip8.sound = restored.sound;
+    chip8.stack = restored.stack;
+    chip8.keyboard = restored.keyboard;
+    chip8.graphics = restored.graphics;
+    chip8.hires = restored.hires;
+    chip8.planes = restored.planes;
+    chip8.audio_pattern = restored.audio_pattern;
+    chip8.pitch = restored.pitch;
+    chip8.flags = restored.flags;
+    chip8.exited = restored.exited;
+    chip8.platform = restored.platform;
+    chip8.quirks = restored.quirks;
+    chip8.vblank = restored.vblank;
+    chip8.key_wait = restored.key_wait;
+    Ok(())
+}
+
+/// Reads the machine itself, which is all a version 1 state holds and the
+/// start of every later version.
+fn read_v1(reader: &mut Reader) -> Result<Chip8, StateError> {
+    let (platform, quirks) = read_settings(reader)?;
+    let mut chip8 = Chip8::with_platform(platform, quirks);
+    chip8.V.copy_from_slice(reader.take(16)?);
+    chip8.I = reader.u16()?;
+    chip8.pc = reader.u32()? as usize;
+    chip8.sp = reader.u8()? as usize;
+    if chip8.sp > chip8.stack.len() {
+        return Err(StateError::Invalid("stack pointer"));
+    }
+    for address in chip8.stack.iter_mut() {
+        *address = reader.u16()?;
+    }
+    chip8.delay = reader.u8()?;
+    chip8.sound = reader.u8()?;
+    chip8.keyboard = unpack_keyboard(reader.u16()?);
+
+    let mode = reader.u8()?;
+    chip8.hires = mode & 0b1 != 0;
+    chip8.exited = mode & 0b10 != 0;
+    chip8.vblank = mode & 0b100 != 0;
+    chip8.planes = reader.u8()?;
+    chip8.flags.copy_from_slice(reader.take(16)?);
+    chip8.audio_pattern.copy_from_slice(reader.take(16)?);
+    chip8.pitch = reader.u8()?;
+    for plane in [0b01, 0b10].iter() {
+        let packed = reader.take(HIRES_WIDTH * HIRES_HEIGHT / 8)?;
+        for (i, pixel) in chip8.graphics.iter_mut().enumerate() {
+            if packed[i / 8] & (0x80 >> (i % 8)) != 0 {
+                *pixel |= plane;
+            }
+        }
+    }
+
+    let memory_size = reader.u32()? as usize;
+    if memory_size != platform.memory_size() {
+        return Err(StateError::Invalid("memory size"));
+    }
+    chip8.poke_bytes(0, reader.take(memory_size)?);
+    if chip8.pc >= memory_size {
+        return Err(StateError::Invalid("program counter"));
+    }
+    Ok(chip8)
+}
+
+/// Writes the platform and quirks. Shared with movies, which have to start
+/// the same machine again.
+pub(crate) fn write_settings(out: &mut Vec<u8>, platform: Platform, quirks: Quirks) {
+    out.push(match platform {
+        Platform::Chip8 => 0,
+        Platform::SuperChip => 1,
+        Platform::XoChip => 2,
+    });
+    out.push(
+        quirks.shift_uses_vy as u8
+            | (quirks.jump_uses_vx as u8) << 1
+            | (quirks.logic_resets_vf as u8) << 2
+            | (quirks.clip_sprites as u8) << 3
+            | (quirks.display_wait as u8) << 4,
+    );
+    out.push(match quirks.memory_increment {
+        MemoryIncrement::ByXPlusOne => 0,
+        MemoryIncrement::ByX => 1,
+        MemoryIncrement::Unchanged => 2,
+    });
+}
+
+pub(crate) fn read_settings(reader: &mut Reader) -> Result<(Platform, Quirks), StateError> {
+    let platform = match reader.u8()? {
+        0 => Platform::Chip8,
+        1 => Platform::SuperChip,
+        2 => Platform::XoChip,
+        _ => return Err(StateError::Invalid("platform")),
+    };
+    let quirk_bits = reader.u8()?;
+    let memory_increment = match reader.u8()? {
+        0 => MemoryIncrement::ByXPlusOne,
+        1 => MemoryIncrement::ByX,
+        2 => MemoryIncrement::Unchanged,
+        _ => return Err(StateError::Invalid("memory increment quirk")),
+    };
+    let quirks = Quirks {
+        shift_uses_vy: quirk_bits & 0b1 != 0,
+        jump_uses_vx: quirk_bits & 0b10 != 0,
+        logic_resets_vf: quirk_bits & 0b100 != 0,
+        clip_sprites: quirk_bits & 0b1000 != 0,
+        display_wait: quirk_bits & 0b10000 != 0,
+        memory_increment,
+    };
+    Ok((platform, quirks))
+}
+
+fn pack_keyboard(keyboard: &[bool; 16]) -> u16 {
+    keyboard
+        .iter()
+        .enumerate()
+        .filter(|&(_, &pressed)| pressed)
+        .fold(0, |bits, (key, _)| bits | 1 << key)
+}
+
+fn unpack_keyboard(bits: u16) -> [bool; 16] {
+    let mut keyboard = [false; 16];
+    for (key, pressed) in keyboard.iter_mut().enumerate() {
+        *pressed = bits & (1 << key) != 0;
+    }
+    keyboard
+}
+
+pub(crate) fn write_u16(out: &mut Vec<u8>, value: u16) {
+    out.push((value >> 8) as u8);
+    out.push(value as u8);
+}
+
+pub(crate) fn write_u32(out: &mut Vec<u8>, value: u32) {
+    write_u16(out, (value >> 16) as u16);
+    write_u16(out, value as u16);
+}
+
+pub(crate) fn write_u64(out: &mut Vec<u8>, value: u64) {
+    write_u32(out, (value >> 32) as u32);
+    write_u32(out, value as u32);
+}
+
+/// Reads big endian values, failing with [`StateError::Truncated`] when the
+/// data runs out.
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes, position: 0 }
+    }
+
+    pub(crate) fn is_finished(&self) -> bool {
+        self.position == self.bytes.len()
+    }
+
+    pub(crate) fn take(&mut self, length: usize) -> Result<&'a [u8], StateError> {
+        let end = match self.position.checked_add(length) {
+            Some(end) if end <= self.bytes.len() => end,
+            _ => return Err(StateError::Truncated),
+        };
+        let slice = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(slice)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16, StateError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from(bytes[0]) << 8 | u16::from(bytes[1]))
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, StateError> {
+        Ok(u32::from(self.u16()?) << 16 | u32::from(self.u16()?))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, StateError> {
+        Ok(u64::from(self.u32()?) << 32 | u64::from(self.u32()?))
+    }
+}
@@ -0,0 +1,142 @@
+use chip8::state::{save, VERSION};
+use chip8::random::{Sequence, SplitMix};
+use chip8::{Chip8, Platform, Quirks, StateError};
+
+#[test]
+fn test_round_trip() {
+    let mut cpu = Chip8::with_platform(Platform::XoChip, Platform::XoChip.quirks());
+    //Call a subroutine that switches to hires and draws the "8" glyph in both planes
+    cpu.load_program(vec!(0x22, 0x04, 0x00, 0x00, 0x00, 0xFF, 0xF3, 0x01, 0x60, 0x08, 0xF0, 0x29, 0xD0, 0x05, 0xF0, 0x75));
+    for _ in 0..7 {
+        cpu.step().unwrap();
+    }
+    cpu.delay = 12;
+    cpu.keyboard[0xA] = true;
+    cpu.memory.poke(0xFFFF, 0x99);
+
+    let state = cpu.save_state();
+    let mut restored = Chip8::new(Quirks::vip());
+    restored.load_state(&state).unwrap();
+
+    assert_eq!(restored.platform, Platform::XoChip);
+    assert_eq!(restored.quirks, Platform::XoChip.quirks());
+    assert_eq!(restored.memory.dump(), cpu.memory.dump());
+    assert_eq!(restored.V, cpu.V);
+    assert_eq!(restored.I, cpu.I);
+    assert_eq!(restored.pc, cpu.pc);
+    assert_eq!(restored.sp, 1);
+    assert_eq!(restored.stack, cpu.stack);
+    assert_eq!(restored.delay, 12);
+    assert_eq!(restored.keyboard, cpu.keyboard);
+    assert_eq!(restored.graphics[..], cpu.graphics[..]);
+    assert_eq!(restored.pixel_color_at(8, 8), 0b11);
+    assert!(restored.hires);
+    assert_eq!(restored.planes, 0b11);
+    assert_eq!(restored.flags, cpu.flags);
+    assert_eq!(restored.save_state(), state);
+}
+
+#[test]
+fn test_execution_continues_identically() {
+    let program = vec!(0x60, 0x00, 0x70, 0x01, 0xA3, 0x00, 0xF0, 0x33, 0x12, 0x02);
+    let mut cpu = Chip8::new(Quirks::vip());
+    cpu.load_program(program);
+    cpu.run_frame(25).unwrap();
+    let state = cpu.save_state();
+    cpu.run_frame(25).unwrap();
+
+    let mut restored = Chip8::new(Quirks::vip());
+    restored.load_state(&state).unwrap();
+    restored.run_frame(25).unwrap();
+    assert_eq!(restored.save_state(), cpu.save_state());
+}
+
+#[test]
+fn test_rejects_bad_states() {
+    let mut cpu = Chip8::new(Quirks::vip());
+    let state = save(&cpu);
+
+    assert_eq!(cpu.load_state(b"nope"), Err(StateError::NotAState));
+    assert_eq!(cpu.load_state(&state[..state.len() - 1]), Err(StateError::Truncated));
+
+    let mut newer = state.clone();
+    newer[4] = ((VERSION + 1) >> 8) as u8;
+    newer[5] = (VERSION + 1) as u8;
+    assert_eq!(cpu.load_state(&newer), Err(StateError::UnsupportedVersion(VERSION + 1)));
+
+    let mut bad_platform = state.clone();
+    bad_platform[6] = 7;
+    assert_eq!(cpu.load_state(&bad_platform), Err(StateError::Invalid("platform")));
+}
+
+#[test]
+fn test_failed_load_leaves_machine_untouched() {
+    let mut cpu = Chip8::new(Quirks::vip());
+    let mut other = Chip8::new(Quirks::vip());
+    other.V[3] = 0x33;
+    let state = other.save_state();
+    cpu.V[3] = 0x11;
+    assert!(cpu.load_state(&state[..state.len() - 10]).is_err());
+    assert_eq!(cpu.V[3], 0x11);
+}
+
+#[test]
+fn test_random_source_is_restored() {
+    //Fill V0 with random bytes forever
+    let program = vec!(0xC0, 0xFF, 0x12, 0x00);
+    let mut cpu = Chip8::new(Quirks::vip());
+    cpu.rng = Box::new(SplitMix::new(7));
+    cpu.load_program(program);
+    cpu.run_frame(10).unwrap();
+    let state = cpu.save_state();
+    cpu.run_frame(10).unwrap();
+
+    let mut restored = Chip8::new(Quirks::vip());
+    restored.rng = Box::new(SplitMix::new(99));
+    restored.load_state(&state).unwrap();
+    restored.run_frame(10).unwrap();
+    assert_eq!(restored.V[0], cpu.V[0]);
+    assert_eq!(restored.save_state(), cpu.save_state());
+}
+
+#[test]
+fn test_rejects_different_random_source() {
+    let cpu = Chip8::new(Quirks::vip());
+    let state = cpu.save_state();
+    let mut other = Chip8::new(Quirks::vip());
+    other.rng = Box::new(Sequence::new(vec!(1)));
+    assert_eq!(other.load_state(&state), Err(StateError::Invalid("random source")));
+}
+
+#[test]
+fn test_loads_version_1() {
+    let mut cpu = Chip8::new(Quirks::vip());
+    cpu.V[5] = 0x55;
+    let state = cpu.save_state();
+    //Version 1 is the same layout without the random source and key wait
+    let rng_length = 1 + cpu.rng.name().len() + 2 + cpu.rng.save().len();
+    let mut old = state[..state.len() - rng_length - 3].to_vec();
+    old[5] = 1;
+
+    let mut restored = Chip8::new(Quirks::vip());
+    restored.rng = Box::new(Sequence::new(vec!(0x42)));
+    restored.load_state(&old).unwrap();
+    assert_eq!(restored.V[5], 0x55);
+    assert_eq!(restored.rng.next_byte(), 0x42);
+}
+
+#[test]
+fn test_key_wait_is_restored() {
+    let mut cpu = Chip8::new(Quirks::vip());
+    cpu.load_program(vec!(0xF3, 0x0A));
+    cpu.step().unwrap();
+    cpu.key_down(0x7);
+    let state = cpu.save_state();
+
+    let mut restored = Chip8::new(Quirks::vip());
+    restored.load_state(&state).unwrap();
+    assert!(restored.is_waiting_for_key());
+    restored.key_up(0x7);
+    assert!(!restored.is_waiting_for_key());
+    assert_eq!(restored.V[3], 0x7);
+}
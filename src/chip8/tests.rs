@@ -1,25 +1,509 @@
-use chip8::Chip8;
+use chip8::random::Sequence;
+use chip8::{Chip8, ErrorKind, MemoryIncrement, Platform, Quirks};
 
 
 #[test]
 fn test_call() {
     let mut cpu = init_cpu_with_program(vec!(0x22, 0x22));
-    cpu.emulate_cycle();
+    cpu.emulate_cycle().unwrap();
     assert_eq!(cpu.pc, 0x222);
 }
 
 #[test]
 fn test_call_and_return() {
     let mut cpu = init_cpu_with_program(vec!(0x22, 0x04, 0x00, 0x00, 0x00, 0xEE));
-    cpu.emulate_cycle();
-    cpu.emulate_cycle();
+    cpu.emulate_cycle().unwrap();
+    cpu.emulate_cycle().unwrap();
     println!("Stack: {:X?}", cpu.stack);
     println!("PC: {:X}", cpu.pc);
     assert_eq!(cpu.pc, 0x202);
 }
 
+#[test]
+fn test_draw_past_the_bottom_edge() {
+    //The 0 glyph from row 30, which runs off the bottom of the screen
+    let mut cpu = init_cpu_with_program(vec!(0xA0, 0x00, 0x61, 0x1E, 0xD0, 0x15));
+    for _ in 0..3 {
+        cpu.emulate_cycle().unwrap();
+    }
+    assert!(cpu.pixel_at(0, 30));
+    assert!(cpu.pixel_at(0, 31));
+}
+
+#[test]
+fn test_shift_left_sets_flag() {
+    let mut cpu = init_cpu_with_program(vec!(0x60, 0x81, 0x80, 0x0E));
+    cpu.emulate_cycle().unwrap();
+    cpu.emulate_cycle().unwrap();
+    assert_eq!(cpu.V[0], 0x02);
+    assert_eq!(cpu.V[0xF], 1);
+}
+
+#[test]
+fn test_add_sets_carry() {
+    let mut cpu = init_cpu_with_program(vec!(0x60, 0xFF, 0x61, 0x02, 0x80, 0x14));
+    for _ in 0..3 {
+        cpu.emulate_cycle().unwrap();
+    }
+    assert_eq!(cpu.V[0], 0x01);
+    assert_eq!(cpu.V[0xF], 1);
+}
+
+#[test]
+fn test_unknown_opcode() {
+    let mut cpu = init_cpu_with_program(vec!(0x60, 0x05, 0x80, 0x1F));
+    cpu.emulate_cycle().unwrap();
+    let error = cpu.emulate_cycle().unwrap_err();
+    assert_eq!(error.kind, ErrorKind::UnknownOpcode);
+    assert_eq!(error.pc, 0x202);
+    assert_eq!(error.opcode, 0x801F);
+    assert_eq!(error.address, None);
+    assert_eq!(cpu.pc, 0x202);
+    assert_eq!(cpu.V[0], 0x05);
+}
+
+#[test]
+fn test_return_with_empty_stack() {
+    let mut cpu = init_cpu_with_program(vec!(0x00, 0xEE));
+    let error = cpu.emulate_cycle().unwrap_err();
+    assert_eq!(error.kind, ErrorKind::StackUnderflow);
+    assert_eq!(cpu.pc, 0x200);
+    assert_eq!(cpu.sp, 0);
+}
+
+#[test]
+fn test_stack_overflow() {
+    let mut cpu = init_cpu_with_program(vec!(0x22, 0x00));
+    for _ in 0..16 {
+        cpu.emulate_cycle().unwrap();
+    }
+    let error = cpu.emulate_cycle().unwrap_err();
+    assert_eq!(error.kind, ErrorKind::StackOverflow);
+    assert_eq!(cpu.sp, 16);
+    assert_eq!(cpu.pc, 0x200);
+}
+
+#[test]
+fn test_draw_out_of_bounds() {
+    let mut cpu = init_cpu_with_program(vec!(0xAF, 0xFE, 0xD0, 0x05));
+    cpu.emulate_cycle().unwrap();
+    let error = cpu.emulate_cycle().unwrap_err();
+    assert_eq!(error.kind, ErrorKind::MemoryOutOfBounds);
+    assert_eq!(error.address, Some(0x1002));
+    assert_eq!(cpu.pc, 0x202);
+    assert_eq!(cpu.V[0xF], 0);
+}
+
+#[test]
+fn test_bcd_out_of_bounds_leaves_memory_untouched() {
+    let mut cpu = init_cpu_with_program(vec!(0xAF, 0xFE, 0x60, 0xFF, 0xF0, 0x33));
+    cpu.emulate_cycle().unwrap();
+    cpu.emulate_cycle().unwrap();
+    let error = cpu.emulate_cycle().unwrap_err();
+    assert_eq!(error.kind, ErrorKind::MemoryOutOfBounds);
+    assert_eq!(error.address, Some(0x1000));
+    assert_eq!(cpu.memory.peek(0xFFE), 0);
+    assert_eq!(cpu.memory.peek(0xFFF), 0);
+}
+
+#[test]
+fn test_store_registers_out_of_bounds() {
+    let mut cpu = init_cpu_with_program(vec!(0xAF, 0xFF, 0xF5, 0x55));
+    cpu.emulate_cycle().unwrap();
+    let error = cpu.emulate_cycle().unwrap_err();
+    assert_eq!(error.kind, ErrorKind::MemoryOutOfBounds);
+    assert_eq!(cpu.memory.peek(0xFFF), 0);
+}
+
+#[test]
+fn test_shift_quirk() {
+    let program = vec!(0x60, 0x01, 0x61, 0x81, 0x80, 0x16);
+    let mut cpu = init_cpu_with_quirks(program.clone(), Quirks::vip());
+    run_cycles(&mut cpu, 3);
+    assert_eq!(cpu.V[0], 0x40);
+    assert_eq!(cpu.V[0xF], 1);
+
+    let mut cpu = init_cpu_with_quirks(program, Quirks::schip());
+    run_cycles(&mut cpu, 3);
+    assert_eq!(cpu.V[0], 0x00);
+    assert_eq!(cpu.V[0xF], 1);
+}
+
+#[test]
+fn test_memory_increment_quirk() {
+    let program = vec!(0xA3, 0x00, 0xF2, 0x55);
+    let expected = [
+        (MemoryIncrement::ByXPlusOne, 0x303),
+        (MemoryIncrement::ByX, 0x302),
+        (MemoryIncrement::Unchanged, 0x300),
+    ];
+    for &(memory_increment, address) in expected.iter() {
+        let mut quirks = Quirks::vip();
+        quirks.memory_increment = memory_increment;
+        let mut cpu = init_cpu_with_quirks(program.clone(), quirks);
+        run_cycles(&mut cpu, 2);
+        assert_eq!(cpu.I, address);
+    }
+}
+
+#[test]
+fn test_store_and_load_registers_include_vx() {
+    let mut cpu = init_cpu_with_program(vec!(0x60, 0x0A, 0x61, 0x0B, 0xA3, 0x00, 0xF1, 0x55, 0x60, 0x00, 0x61, 0x00, 0xA3, 0x00, 0xF1, 0x65));
+    run_cycles(&mut cpu, 8);
+    assert_eq!(cpu.memory.dump()[0x300..0x302], [0x0A, 0x0B]);
+    assert_eq!(cpu.V[0], 0x0A);
+    assert_eq!(cpu.V[1], 0x0B);
+}
+
+#[test]
+fn test_jump_quirk() {
+    let program = vec!(0x60, 0x02, 0x63, 0x04, 0xB3, 0x00);
+    let mut cpu = init_cpu_with_quirks(program.clone(), Quirks::vip());
+    run_cycles(&mut cpu, 3);
+    assert_eq!(cpu.pc, 0x302);
+
+    let mut cpu = init_cpu_with_quirks(program, Quirks::chip48());
+    run_cycles(&mut cpu, 3);
+    assert_eq!(cpu.pc, 0x304);
+}
+
+#[test]
+fn test_logic_vf_reset_quirk() {
+    let program = vec!(0x6F, 0x05, 0x80, 0x11);
+    let mut cpu = init_cpu_with_quirks(program.clone(), Quirks::vip());
+    run_cycles(&mut cpu, 2);
+    assert_eq!(cpu.V[0xF], 0);
+
+    let mut cpu = init_cpu_with_quirks(program, Quirks::schip());
+    run_cycles(&mut cpu, 2);
+    assert_eq!(cpu.V[0xF], 5);
+}
+
+#[test]
+fn test_flag_is_written_after_result() {
+    let mut cpu = init_cpu_with_program(vec!(0x6F, 0xFF, 0x61, 0x02, 0x8F, 0x14));
+    run_cycles(&mut cpu, 3);
+    assert_eq!(cpu.V[0xF], 1);
+}
+
+#[test]
+fn test_subtract_sets_not_borrow() {
+    let mut cpu = init_cpu_with_program(vec!(0x60, 0x05, 0x61, 0x05, 0x80, 0x15, 0x62, 0x01, 0x82, 0x15));
+    run_cycles(&mut cpu, 3);
+    assert_eq!(cpu.V[0], 0);
+    assert_eq!(cpu.V[0xF], 1);
+    run_cycles(&mut cpu, 2);
+    assert_eq!(cpu.V[2], 0xFC);
+    assert_eq!(cpu.V[0xF], 0);
+}
+
+#[test]
+fn test_sprite_clipping_quirk() {
+    //Draw the "0" glyph at (62, 30)
+    let program = vec!(0x60, 0x3E, 0x61, 0x1E, 0xA0, 0x00, 0xD0, 0x15);
+    let mut cpu = init_cpu_with_quirks(program.clone(), Quirks::vip());
+    run_cycles(&mut cpu, 4);
+    assert!(cpu.pixel_at(62, 30));
+    assert!(!cpu.pixel_at(0, 30));
+    assert!(!cpu.pixel_at(62, 0));
+
+    let mut quirks = Quirks::vip();
+    quirks.clip_sprites = false;
+    let mut cpu = init_cpu_with_quirks(program, quirks);
+    run_cycles(&mut cpu, 4);
+    assert!(cpu.pixel_at(62, 30));
+    assert!(cpu.pixel_at(0, 30));
+    assert!(cpu.pixel_at(62, 0));
+}
+
+#[test]
+fn test_collision_over_whole_sprite() {
+    //Draw the "0" glyph twice, then the "1" glyph over it
+    let mut cpu = init_cpu_with_quirks(vec!(0xA0, 0x00, 0xD0, 0x05, 0xA0, 0x05, 0xD0, 0x05), Quirks::schip());
+    run_cycles(&mut cpu, 2);
+    assert_eq!(cpu.V[0xF], 0);
+    run_cycles(&mut cpu, 2);
+    assert_eq!(cpu.V[0xF], 1);
+}
+
+#[test]
+fn test_font_has_every_hex_digit() {
+    let mut cpu = init_cpu_with_program(vec!(0x60, 0x0F, 0xF0, 0x29));
+    run_cycles(&mut cpu, 2);
+    let start = usize::from(cpu.I);
+    assert_eq!(start, 0x0F * 5);
+    assert_eq!(cpu.memory.dump()[start..start + 5], [0xF0, 0x80, 0xF0, 0x80, 0x80]);
+    assert_eq!(cpu.memory.dump()[0x0D * 5..0x0D * 5 + 5], [0xE0, 0x90, 0x90, 0x90, 0xE0]);
+}
+
+#[test]
+fn test_super_chip_instructions_need_super_chip_platform() {
+    let mut cpu = init_cpu_with_program(vec!(0x00, 0xFF));
+    let error = cpu.emulate_cycle().unwrap_err();
+    assert_eq!(error.kind, ErrorKind::UnknownOpcode);
+    assert!(!cpu.hires);
+}
+
+#[test]
+fn test_high_resolution_mode() {
+    //Switch to hires and draw the "0" glyph at (120, 60)
+    let mut cpu = init_super_chip(vec!(0x00, 0xFF, 0x60, 0x78, 0x61, 0x3C, 0xA0, 0x00, 0xD0, 0x14));
+    run_cycles(&mut cpu, 5);
+    assert!(cpu.hires);
+    assert_eq!((cpu.width(), cpu.height()), (128, 64));
+    assert!(cpu.pixel_at(120, 60));
+    assert!(cpu.pixel_at(123, 63));
+
+    cpu.load_program(vec!(0x00, 0xFE));
+    cpu.pc = 0x200;
+    run_cycles(&mut cpu, 1);
+    assert_eq!((cpu.width(), cpu.height()), (64, 32));
+    assert!(cpu.graphics.iter().all(|&pixel| pixel == 0));
+}
+
+#[test]
+fn test_large_sprite() {
+    let mut cpu = init_super_chip(vec!(0x00, 0xFF, 0xA3, 0x00, 0xD0, 0x00));
+    for i in 0..32 {
+        cpu.memory.poke(0x300 + i, 0xFF);
+    }
+    run_cycles(&mut cpu, 3);
+    assert!(cpu.pixel_at(15, 15));
+    assert!(!cpu.pixel_at(16, 15));
+    assert!(!cpu.pixel_at(15, 16));
+    assert_eq!(cpu.V[0xF], 0);
+}
+
+#[test]
+fn test_scroll() {
+    //Draw the top row of the "0" glyph at (4, 0), then scroll
+    let mut cpu = init_super_chip(vec!(0x60, 0x04, 0xA0, 0x00, 0xD0, 0x11, 0x00, 0xC2, 0x00, 0xFB, 0x00, 0xFC, 0x00, 0xFC));
+    run_cycles(&mut cpu, 3);
+    assert!(cpu.pixel_at(4, 0));
+    run_cycles(&mut cpu, 1);
+    assert!(!cpu.pixel_at(4, 0));
+    assert!(cpu.pixel_at(4, 2));
+    run_cycles(&mut cpu, 1);
+    assert!(cpu.pixel_at(8, 2));
+    run_cycles(&mut cpu, 2);
+    assert!(cpu.pixel_at(0, 2));
+    assert!(cpu.pixel_at(3, 2));
+    assert!(!cpu.pixel_at(4, 2));
+}
+
+#[test]
+fn test_big_font() {
+    let mut cpu = init_super_chip(vec!(0x60, 0x08, 0xF0, 0x30));
+    run_cycles(&mut cpu, 2);
+    assert_eq!(cpu.I, 0x50 + 8 * 10);
+    assert_eq!(cpu.memory.dump()[cpu.I as usize..cpu.I as usize + 2], [0xFF, 0xFF]);
+}
+
+#[test]
+fn test_flag_registers() {
+    let mut cpu = init_super_chip(vec!(0x60, 0x01, 0x61, 0x02, 0xF1, 0x75, 0x60, 0x00, 0x61, 0x00, 0xF1, 0x85));
+    run_cycles(&mut cpu, 6);
+    assert_eq!(cpu.V[0..2], [0x01, 0x02]);
+}
+
+#[test]
+fn test_exit() {
+    let mut cpu = init_super_chip(vec!(0x00, 0xFD, 0x60, 0x01));
+    run_cycles(&mut cpu, 2);
+    assert!(cpu.exited);
+    assert_eq!(cpu.pc, 0x202);
+    assert_eq!(cpu.V[0], 0);
+}
+
+#[test]
+fn test_xo_chip_memory_size() {
+    assert_eq!(Chip8::new(Quirks::vip()).memory.len(), 0x1000);
+    assert_eq!(init_xo_chip(vec!()).memory.len(), 0x10000);
+}
+
+#[test]
+fn test_long_index_load() {
+    let mut cpu = init_xo_chip(vec!(0xF0, 0x00, 0xE1, 0x23, 0xF0, 0x65));
+    run_cycles(&mut cpu, 1);
+    assert_eq!(cpu.I, 0xE123);
+    assert_eq!(cpu.pc, 0x204);
+    cpu.memory.poke(0xE123, 0x42);
+    run_cycles(&mut cpu, 1);
+    assert_eq!(cpu.V[0], 0x42);
+}
+
+#[test]
+fn test_skip_over_long_index_load() {
+    let mut cpu = init_xo_chip(vec!(0x30, 0x00, 0xF0, 0x00, 0x12, 0x34, 0x60, 0x01));
+    run_cycles(&mut cpu, 1);
+    assert_eq!(cpu.pc, 0x206);
+}
+
+#[test]
+fn test_save_and_load_register_range() {
+    let mut cpu = init_xo_chip(vec!(0x62, 0x02, 0x63, 0x03, 0xA3, 0x00, 0x53, 0x22, 0x51, 0x23));
+    run_cycles(&mut cpu, 4);
+    assert_eq!(cpu.memory.dump()[0x300..0x302], [0x03, 0x02]);
+    assert_eq!(cpu.I, 0x300);
+    run_cycles(&mut cpu, 1);
+    assert_eq!(cpu.V[1..4], [0x03, 0x02, 0x03]);
+}
+
+#[test]
+fn test_draw_to_both_planes() {
+    //Select both planes and draw a one row sprite with different data per plane
+    let mut cpu = init_xo_chip(vec!(0xF3, 0x01, 0xA3, 0x00, 0xD0, 0x01, 0xF2, 0x01, 0x00, 0xE0));
+    cpu.memory.poke(0x300, 0b1100_0000);
+    cpu.memory.poke(0x301, 0b1010_0000);
+    run_cycles(&mut cpu, 3);
+    assert_eq!(cpu.pixel_color_at(0, 0), 0b11);
+    assert_eq!(cpu.pixel_color_at(1, 0), 0b01);
+    assert_eq!(cpu.pixel_color_at(2, 0), 0b10);
+    assert_eq!(cpu.pixel_color_at(3, 0), 0b00);
+
+    //Clearing only affects the selected plane
+    run_cycles(&mut cpu, 2);
+    assert_eq!(cpu.pixel_color_at(0, 0), 0b01);
+    assert_eq!(cpu.pixel_color_at(2, 0), 0b00);
+}
+
+#[test]
+fn test_audio_pattern_and_pitch() {
+    let mut cpu = init_xo_chip(vec!(0xA3, 0x00, 0xF0, 0x02, 0x60, 0x70, 0xF0, 0x3A));
+    for i in 0..16 {
+        cpu.memory.poke(0x300 + i, i as u8);
+    }
+    run_cycles(&mut cpu, 4);
+    assert_eq!(cpu.audio_pattern[15], 15);
+    assert_eq!(cpu.pitch, 0x70);
+    #[cfg(feature = "std")]
+    assert!(cpu.playback_rate() > 4000.0);
+}
+
+#[test]
+fn test_xo_chip_instructions_need_xo_chip_platform() {
+    let mut cpu = init_super_chip(vec!(0xF0, 0x00, 0x12, 0x34));
+    let error = cpu.emulate_cycle().unwrap_err();
+    assert_eq!(error.kind, ErrorKind::UnknownOpcode);
+}
+
+#[test]
+fn test_timers_tick_once_per_frame() {
+    //Set the delay timer to 10, then loop forever
+    let mut cpu = init_cpu_with_program(vec!(0x60, 0x0A, 0xF0, 0x15, 0x12, 0x04));
+    cpu.run_frame(20).unwrap();
+    assert_eq!(cpu.delay, 9);
+    cpu.run_frame(20).unwrap();
+    assert_eq!(cpu.delay, 8);
+    run_cycles(&mut cpu, 100);
+    assert_eq!(cpu.delay, 8);
+}
+
+#[test]
+fn test_display_wait_quirk() {
+    let program = vec!(0xD0, 0x01, 0xD0, 0x01, 0x60, 0x01);
+    let mut cpu = init_cpu_with_quirks(program.clone(), Quirks::vip());
+    run_cycles(&mut cpu, 3);
+    assert_eq!(cpu.pc, 0x202);
+    cpu.tick_timers();
+    run_cycles(&mut cpu, 2);
+    assert_eq!(cpu.pc, 0x206);
+
+    let mut cpu = init_cpu_with_quirks(program, Quirks::chip48());
+    run_cycles(&mut cpu, 3);
+    assert_eq!(cpu.pc, 0x206);
+}
+
+#[test]
+fn test_run_frame_stops_on_error() {
+    let mut cpu = init_cpu_with_program(vec!(0x60, 0x01, 0x00, 0xEE));
+    cpu.delay = 5;
+    let error = cpu.run_frame(10).unwrap_err();
+    assert_eq!(error.kind, ErrorKind::StackUnderflow);
+    assert_eq!(cpu.delay, 5);
+}
+
+#[test]
+fn test_random_masks_source() {
+    let mut cpu = init_cpu_with_program(vec!(0xC0, 0x0F, 0xC1, 0xFF, 0xC2, 0xF0));
+    cpu.rng = Box::new(Sequence::new(vec!(0xAB, 0xCD)));
+    run_cycles(&mut cpu, 3);
+    assert_eq!(cpu.V[0], 0x0B);
+    assert_eq!(cpu.V[1], 0xCD);
+    assert_eq!(cpu.V[2], 0xA0);
+}
+
+#[test]
+fn test_wait_key_halts_until_release() {
+    let mut cpu = init_cpu_with_program(vec!(0xF5, 0x0A, 0x61, 0x01));
+    cpu.delay = 3;
+    cpu.run_frame(10).unwrap();
+    assert!(cpu.is_waiting_for_key());
+    assert_eq!(cpu.pc, 0x202);
+    assert_eq!(cpu.delay, 2);
+
+    cpu.key_down(0xC);
+    cpu.run_frame(10).unwrap();
+    assert!(cpu.is_waiting_for_key());
+    assert_eq!(cpu.V[5], 0);
+
+    //Releasing some other key does not count
+    cpu.key_down(0x3);
+    cpu.key_up(0x3);
+    assert!(cpu.is_waiting_for_key());
+
+    cpu.key_up(0xC);
+    assert!(!cpu.is_waiting_for_key());
+    assert_eq!(cpu.V[5], 0xC);
+    cpu.step().unwrap();
+    assert_eq!(cpu.V[1], 1);
+}
+
+#[test]
+fn test_wait_key_accepts_held_key() {
+    let mut cpu = init_cpu_with_program(vec!(0xF0, 0x0A));
+    cpu.key_down(0x9);
+    cpu.step().unwrap();
+    assert!(cpu.is_waiting_for_key());
+    cpu.key_up(0x9);
+    assert_eq!(cpu.V[0], 0x9);
+}
+
+#[test]
+fn test_keys_off_the_keypad_are_ignored() {
+    let mut cpu = init_cpu_with_program(vec!(0xF0, 0x0A));
+    cpu.emulate_cycle().unwrap();
+    cpu.key_down(0x10);
+    cpu.key_up(0x10);
+    cpu.key_down(usize::MAX);
+    assert!(!cpu.is_key_down(0x10));
+    assert!(cpu.is_waiting_for_key());
+}
+
+fn init_xo_chip(program: Vec<u8>) -> Chip8 {
+    let mut cpu = Chip8::with_platform(Platform::XoChip, Platform::XoChip.quirks());
+    cpu.load_program(program);
+    cpu
+}
+
+fn init_super_chip(program: Vec<u8>) -> Chip8 {
+    let mut cpu = Chip8::with_platform(Platform::SuperChip, Platform::SuperChip.quirks());
+    cpu.load_program(program);
+    cpu
+}
+
+fn run_cycles(cpu: &mut Chip8, cycles: usize) {
+    for _ in 0..cycles {
+        cpu.emulate_cycle().unwrap();
+    }
+}
+
 fn init_cpu_with_program(program: Vec<u8>) -> Chip8 {
-    let mut cpu = Chip8::new();
+    init_cpu_with_quirks(program, Quirks::vip())
+}
+
+fn init_cpu_with_quirks(program: Vec<u8>, quirks: Quirks) -> Chip8 {
+    let mut cpu = Chip8::new(quirks);
     cpu.load_program(program);
     cpu
-}
\ No newline at end of file
+}
@@ -0,0 +1,251 @@
+#[cfg(test)]
+mod tests;
+
+use alloc::collections::VecDeque;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{self, Display, Formatter};
+
+/// One line of a trace, as written by [`Tracer`](super::Tracer).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub cycle: u64,
+    pub pc: usize,
+    pub opcode: u16,
+    pub v: [u8; 16],
+    pub i: u16,
+    pub sp: usize,
+    pub delay: u8,
+    pub sound: u8,
+    /// The bytes the instruction wrote, as address and value.
+    pub writes: Vec<(usize, u8)>,
+}
+
+impl Entry {
+    pub fn parse(line: &str) -> Option<Entry> {
+        let (state, writes) = match line.split_once(" | ") {
+            Some((state, writes)) => (state, writes),
+            None => (line, ""),
+        };
+        let mut fields = state.split_whitespace();
+        let cycle = fields.next()?.parse().ok()?;
+        let mut field = |name: &str, digits: usize| {
+            fields
+                .next()
+                .and_then(|field| field.strip_prefix(name))
+                .filter(|value| value.len() == digits && value.bytes().all(|byte| byte.is_ascii_hexdigit()))
+        };
+        let number = |value: &str| u16::from_str_radix(value, 16).ok();
+        let pc = number(field("PC:", 4)?)? as usize;
+        let opcode = number(field("OP:", 4)?)?;
+        let registers = field("V:", 32)?;
+        let i = number(field("I:", 4)?)?;
+        let sp = number(field("SP:", 2)?)? as usize;
+        let delay = number(field("DT:", 2)?)? as u8;
+        let sound = number(field("ST:", 2)?)? as u8;
+        let mut v = [0; 16];
+        for (x, value) in v.iter_mut().enumerate() {
+            *value = number(&registers[2 * x..2 * x + 2])? as u8;
+        }
+        let writes = writes
+            .split_whitespace()
+            .map(|write| {
+                let (address, value) = write.split_once('=')?;
+                Some((usize::from_str_radix(address, 16).ok()?, u8::from_str_radix(value, 16).ok()?))
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(Entry {
+            cycle,
+            pc,
+            opcode,
+            v,
+            i,
+            sp,
+            delay,
+            sound,
+            writes,
+        })
+    }
+
+    /// What differs between two entries for the same cycle, as
+    /// `NAME LEFT != RIGHT`.
+    pub fn differences(&self, other: &Entry) -> Vec<String> {
+        let mut out = Vec::new();
+        if self.pc != other.pc {
+            out.push(format!("PC {:04X} != {:04X}", self.pc, other.pc));
+        }
+        if self.opcode != other.opcode {
+            out.push(format!("OP {:04X} != {:04X}", self.opcode, other.opcode));
+        }
+        for x in 0..16 {
+            if self.v[x] != other.v[x] {
+                out.push(format!("V{:X} {:02X} != {:02X}", x, self.v[x], other.v[x]));
+            }
+        }
+        if self.i != other.i {
+            out.push(format!("I {:04X} != {:04X}", self.i, other.i));
+        }
+        if self.sp != other.sp {
+            out.push(format!("SP {:02X} != {:02X}", self.sp, other.sp));
+        }
+        if self.delay != other.delay {
+            out.push(format!("DT {:02X} != {:02X}", self.delay, other.delay));
+        }
+        if self.sound != other.sound {
+            out.push(format!("ST {:02X} != {:02X}", self.sound, other.sound));
+        }
+        if self.writes != other.writes {
+            out.push(format!("writes {} != {}", writes(&self.writes), writes(&other.writes)));
+        }
+        out
+    }
+}
+
+fn writes(writes: &[(usize, u8)]) -> String {
+    if writes.is_empty() {
+        return "none".to_string();
+    }
+    let writes: Vec<String> = writes
+        .iter()
+        .map(|&(address, value)| format!("{:04X}={:02X}", address, value))
+        .collect();
+    writes.join(" ")
+}
+
+/// Which of the two traces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// A line that isn't in the trace format. `number` counts from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MalformedLine {
+    pub side: Side,
+    pub number: usize,
+}
+
+impl Display for MalformedLine {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let side = match self.side {
+            Side::Left => "first",
+            Side::Right => "second",
+        };
+        write!(f, "line {} of the {} trace is not a trace line", self.number, side)
+    }
+}
+
+/// The first cycle where two traces disagree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    pub cycle: u64,
+    pub differences: Vec<String>,
+    /// The lines before it, which both traces agree on.
+    pub context: Vec<String>,
+    pub left: String,
+    pub right: String,
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(f, "Traces diverge at cycle {}: {}", self.cycle, self.differences.join(", "))?;
+        for line in &self.context {
+            writeln!(f, "  {}", line)?;
+        }
+        writeln!(f, "< {}", self.left)?;
+        write!(f, "> {}", self.right)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Comparison {
+    /// The traces agree on every cycle both of them have.
+    Identical { compared: usize },
+    Diverged(Divergence),
+}
+
+/// Lines from one trace, with blank lines skipped.
+struct Lines<I> {
+    side: Side,
+    lines: I,
+    number: usize,
+}
+
+impl<I: Iterator<Item = S>, S: AsRef<str>> Lines<I> {
+    fn next(&mut self) -> Result<Option<(Entry, String)>, MalformedLine> {
+        for line in self.lines.by_ref() {
+            self.number += 1;
+            let line = line.as_ref().trim_end();
+            if line.is_empty() {
+                continue;
+            }
+            return match Entry::parse(line) {
+                Some(entry) => Ok(Some((entry, line.to_string()))),
+                None => Err(MalformedLine {
+                    side: self.side,
+                    number: self.number,
+                }),
+            };
+        }
+        Ok(None)
+    }
+}
+
+/// Lines two traces up by cycle and finds the first cycle where they
+/// differ, with up to `context` lines before it.
+///
+/// Cycles only one trace has are skipped, so traces filtered differently
+/// can still be compared where they overlap, and it doesn't matter if one
+/// run went on for longer.
+pub fn compare<L, R, S, T>(left: L, right: R, context: usize) -> Result<Comparison, MalformedLine>
+where
+    L: IntoIterator<Item = S>,
+    R: IntoIterator<Item = T>,
+    S: AsRef<str>,
+    T: AsRef<str>,
+{
+    let mut left = Lines {
+        side: Side::Left,
+        lines: left.into_iter(),
+        number: 0,
+    };
+    let mut right = Lines {
+        side: Side::Right,
+        lines: right.into_iter(),
+        number: 0,
+    };
+    let mut recent = VecDeque::new();
+    let mut compared = 0;
+    let (mut a, mut b) = (left.next()?, right.next()?);
+    while let (Some((x, x_line)), Some((y, y_line))) = (a.take(), b.take()) {
+        if x.cycle < y.cycle {
+            a = left.next()?;
+            b = Some((y, y_line));
+            continue;
+        }
+        if y.cycle < x.cycle {
+            a = Some((x, x_line));
+            b = right.next()?;
+            continue;
+        }
+        let differences = x.differences(&y);
+        if !differences.is_empty() {
+            return Ok(Comparison::Diverged(Divergence {
+                cycle: x.cycle,
+                differences,
+                context: recent.into_iter().collect(),
+                left: x_line,
+                right: y_line,
+            }));
+        }
+        compared += 1;
+        recent.push_back(x_line);
+        if recent.len() > context {
+            recent.pop_front();
+        }
+        a = left.next()?;
+        b = right.next()?;
+    }
+    Ok(Comparison::Identical { compared })
+}
@@ -0,0 +1,99 @@
+use chip8::trace::compare::{compare, Comparison, Divergence, Entry, MalformedLine, Side};
+use chip8::trace::Tracer;
+use chip8::{Chip8, Quirks};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+const LINE: &str = "0000000013 PC:0206 OP:F133 V:01030200000000000000000000000000 I:0300 SP:01 DT:00 ST:00 LD B, V1 | 0300=00 0301=00 0302=03";
+
+//LD V1, 0x03; LD V2, 0x10; SHR V1, V2; ADD V1, 0x01
+fn trace(quirks: Quirks) -> Vec<String> {
+    let mut cpu = Chip8::new(quirks);
+    cpu.load_program(vec!(0x61, 0x03, 0x62, 0x10, 0x81, 0x26, 0x71, 0x01));
+    let lines = Rc::new(RefCell::new(Vec::new()));
+    let sink = lines.clone();
+    cpu.tracer = Some(Tracer::new(move |line| sink.borrow_mut().push(line.to_string())));
+    for _ in 0..4 {
+        cpu.step().unwrap();
+    }
+    let lines = lines.borrow().clone();
+    lines
+}
+
+fn diverged(comparison: Comparison) -> Divergence {
+    match comparison {
+        Comparison::Diverged(divergence) => divergence,
+        other => panic!("Expected a divergence, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_parse() {
+    let entry = Entry::parse(LINE).unwrap();
+    assert_eq!(entry.cycle, 13);
+    assert_eq!(entry.pc, 0x206);
+    assert_eq!(entry.opcode, 0xF133);
+    assert_eq!(entry.v[..3], [0x01, 0x03, 0x02]);
+    assert_eq!(entry.i, 0x300);
+    assert_eq!(entry.sp, 1);
+    assert_eq!(entry.writes, vec!((0x300, 0x00), (0x301, 0x00), (0x302, 0x03)));
+    assert_eq!(Entry::parse("0000000013 PC:0206 OP:F133 V:0103"), None);
+}
+
+#[test]
+fn test_identical_traces() {
+    let lines = trace(Quirks::vip());
+    assert_eq!(
+        compare(&lines, &lines, 5),
+        Ok(Comparison::Identical { compared: 4 })
+    );
+}
+
+#[test]
+fn test_first_divergence_between_quirks() {
+    let mut modern = Quirks::vip();
+    modern.shift_uses_vy = false;
+    let (left, right) = (trace(Quirks::vip()), trace(modern));
+    let divergence = diverged(compare(&left, &right, 2).unwrap());
+    assert_eq!(divergence.cycle, 3);
+    assert_eq!(divergence.differences, vec!("V1 08 != 01", "VF 00 != 01"));
+    assert_eq!(divergence.context, left[1..3].to_vec());
+    assert_eq!(divergence.left, left[3]);
+    assert!(divergence.to_string().starts_with("Traces diverge at cycle 3: V1 08 != 01, VF 00 != 01\n  0000000001"));
+}
+
+#[test]
+fn test_cycles_in_only_one_trace_are_skipped() {
+    let lines = trace(Quirks::vip());
+    let filtered = vec!(lines[0].clone(), String::new(), lines[2].clone());
+    assert_eq!(
+        compare(&lines, &filtered, 5),
+        Ok(Comparison::Identical { compared: 2 })
+    );
+}
+
+#[test]
+fn test_memory_writes_differ() {
+    let other = LINE.replace("0302=03", "0302=04");
+    let divergence = diverged(compare([LINE], [other], 5).unwrap());
+    assert_eq!(
+        divergence.differences,
+        vec!("writes 0300=00 0301=00 0302=03 != 0300=00 0301=00 0302=04")
+    );
+    let other = LINE.split(" | ").next().unwrap();
+    let divergence = diverged(compare([LINE], [other], 5).unwrap());
+    assert_eq!(divergence.differences, vec!("writes 0300=00 0301=00 0302=03 != none"));
+}
+
+#[test]
+fn test_malformed_line() {
+    let error = compare([LINE], ["", "Execution halted"], 5).unwrap_err();
+    assert_eq!(
+        error,
+        MalformedLine {
+            side: Side::Right,
+            number: 2
+        }
+    );
+    assert_eq!(error.to_string(), "line 2 of the second trace is not a trace line");
+}
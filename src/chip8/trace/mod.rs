@@ -0,0 +1,127 @@
+#[cfg(test)]
+mod tests;
+
+pub mod compare;
+
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use chip8::opcode::Instruction;
+use chip8::Chip8;
+use core::fmt::Write;
+use core::ops::Range;
+
+/// Writes one line per executed instruction, to compare runs against each
+/// other or against other emulators.
+///
+/// Each line shows the machine just before the instruction runs, in fixed
+/// width fields followed by the disassembly and then, after a `|`, any
+/// bytes the instruction wrote as `ADDRESS=VALUE`:
+///
+/// ```text
+/// 0000000012 PC:0204 OP:6202 V:01030000000000000000000000000000 I:0000 SP:01 DT:00 ST:00 LD V2, 0x02
+/// 0000000013 PC:0206 OP:F133 V:01030200000000000000000000000000 I:0300 SP:01 DT:00 ST:00 LD B, V1 | 0300=00 0301=00 0302=03
+/// ```
+///
+/// The cycle is the number of instructions executed since the tracer was
+/// attached, counting the ones filtered out. Install it in
+/// [`Chip8::tracer`]; with no tracer the machine only checks for one, and
+/// without the `trace` feature it doesn't even do that.
+pub struct Tracer {
+    cycle: u64,
+    addresses: Option<Range<usize>>,
+    cycles: Option<Range<u64>>,
+    /// The line for the instruction being executed, until it finishes.
+    pending: Option<String>,
+    sink: Box<dyn FnMut(&str)>,
+}
+
+impl Tracer {
+    /// Hands each line to `sink`, without a line break.
+    pub fn new<F: FnMut(&str) + 'static>(sink: F) -> Self {
+        Tracer {
+            cycle: 0,
+            addresses: None,
+            cycles: None,
+            pending: None,
+            sink: Box::new(sink),
+        }
+    }
+
+    /// Writes lines to `writer`, ignoring errors so a full disk doesn't stop
+    /// the machine.
+    #[cfg(feature = "std")]
+    pub fn to_writer<W: ::std::io::Write + 'static>(mut writer: W) -> Self {
+        Tracer::new(move |line| {
+            let _ = writeln!(writer, "{}", line);
+        })
+    }
+
+    /// Only traces instructions at addresses in `range`.
+    pub fn filter_addresses(&mut self, range: Range<usize>) {
+        self.addresses = Some(range);
+    }
+
+    /// Only traces cycles in `range`.
+    pub fn filter_cycles(&mut self, range: Range<u64>) {
+        self.cycles = Some(range);
+    }
+
+    /// The cycle of the next instruction.
+    pub fn cycle(&self) -> u64 {
+        self.cycle
+    }
+
+    /// Called by the machine before it executes `opcode` at pc.
+    pub(crate) fn begin(&mut self, chip8: &Chip8, opcode: u16) {
+        let cycle = self.cycle;
+        self.cycle += 1;
+        if self.addresses.as_ref().is_some_and(|range| !range.contains(&chip8.pc))
+            || self.cycles.as_ref().is_some_and(|range| !range.contains(&cycle))
+        {
+            return;
+        }
+        self.pending = Some(line(chip8, cycle, opcode));
+    }
+
+    /// Called by the machine for each byte the instruction writes.
+    pub(crate) fn write(&mut self, address: usize, value: u8) {
+        if let Some(ref mut line) = self.pending {
+            if !line.contains(" | ") {
+                line.push_str(" |");
+            }
+            let _ = write!(line, " {:04X}={:02X}", address, value);
+        }
+    }
+
+    /// Called by the machine once the instruction has finished or failed.
+    pub(crate) fn end(&mut self) {
+        if let Some(line) = self.pending.take() {
+            (self.sink)(&line);
+        }
+    }
+}
+
+/// The trace line for `opcode` at pc.
+pub fn line(chip8: &Chip8, cycle: u64, opcode: u16) -> String {
+    let mut out = String::new();
+    let _ = write!(out, "{:010} PC:{:04X} OP:{:04X} V:", cycle, chip8.pc, opcode);
+    for value in chip8.V.iter() {
+        let _ = write!(out, "{:02X}", value);
+    }
+    let _ = write!(
+        out,
+        " I:{:04X} SP:{:02X} DT:{:02X} ST:{:02X} ",
+        chip8.I, chip8.sp, chip8.delay, chip8.sound
+    );
+    let decoded = if opcode == 0xF000 && chip8.pc + 3 < chip8.memory.len() {
+        let next = u16::from(chip8.memory.peek(chip8.pc + 2)) << 8 | u16::from(chip8.memory.peek(chip8.pc + 3));
+        Instruction::decode_pair(opcode, next)
+    } else {
+        Instruction::decode(opcode)
+    };
+    match decoded {
+        Ok(instruction) => out.push_str(&instruction.to_string()),
+        Err(_) => out.push_str("???"),
+    }
+    out
+}
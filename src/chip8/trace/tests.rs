@@ -0,0 +1,70 @@
+use chip8::trace::{line, Tracer};
+use chip8::{Chip8, Quirks};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+type Lines = Rc<RefCell<Vec<String>>>;
+
+//Count V1 up in a loop: LD V0, 0x05; ADD V1, 0x01; JP 0x202
+fn traced_cpu(configure: fn(&mut Tracer)) -> (Chip8, Lines) {
+    let mut cpu = Chip8::new(Quirks::vip());
+    cpu.load_program(vec!(0x60, 0x05, 0x71, 0x01, 0x12, 0x02));
+    let lines = Rc::new(RefCell::new(Vec::new()));
+    let sink = lines.clone();
+    let mut tracer = Tracer::new(move |line| sink.borrow_mut().push(line.to_string()));
+    configure(&mut tracer);
+    cpu.tracer = Some(tracer);
+    (cpu, lines)
+}
+
+#[test]
+fn test_lines_show_state_before_each_instruction() {
+    let (mut cpu, lines) = traced_cpu(|_| {});
+    cpu.delay = 0x3C;
+    for _ in 0..3 {
+        cpu.step().unwrap();
+    }
+    assert_eq!(
+        *lines.borrow(),
+        vec!(
+            "0000000000 PC:0200 OP:6005 V:00000000000000000000000000000000 I:0000 SP:00 DT:3C ST:00 LD V0, 0x05",
+            "0000000001 PC:0202 OP:7101 V:05000000000000000000000000000000 I:0000 SP:00 DT:3C ST:00 ADD V1, 0x01",
+            "0000000002 PC:0204 OP:1202 V:05010000000000000000000000000000 I:0000 SP:00 DT:3C ST:00 JP 0x202",
+        )
+    );
+    assert_eq!(cpu.tracer.as_ref().unwrap().cycle(), 3);
+}
+
+#[test]
+fn test_address_filter() {
+    let (mut cpu, lines) = traced_cpu(|tracer| tracer.filter_addresses(0x202..0x204));
+    cpu.run_frame(7).unwrap();
+    let cycles: Vec<String> = lines.borrow().iter().map(|line| line[..10].to_string()).collect();
+    assert_eq!(cycles, vec!("0000000001", "0000000003", "0000000005"));
+}
+
+#[test]
+fn test_cycle_filter() {
+    let (mut cpu, lines) = traced_cpu(|tracer| tracer.filter_cycles(2..4));
+    cpu.run_frame(10).unwrap();
+    assert_eq!(lines.borrow().len(), 2);
+    assert!(lines.borrow()[0].starts_with("0000000002 PC:0204"));
+}
+
+#[test]
+fn test_failed_instructions_are_traced() {
+    let mut cpu = Chip8::new(Quirks::vip());
+    cpu.load_program(vec!(0xFF, 0xFF));
+    let lines = Rc::new(RefCell::new(Vec::new()));
+    let sink = lines.clone();
+    cpu.tracer = Some(Tracer::new(move |line| sink.borrow_mut().push(line.to_string())));
+    assert!(cpu.step().is_err());
+    assert!(lines.borrow()[0].ends_with("OP:FFFF V:00000000000000000000000000000000 I:0000 SP:00 DT:00 ST:00 ???"));
+}
+
+#[test]
+fn test_long_load_shows_both_words() {
+    let mut cpu = Chip8::new(Quirks::vip());
+    cpu.load_program(vec!(0xF0, 0x00, 0x12, 0x34));
+    assert!(line(&cpu, 0, 0xF000).ends_with("LD I, LONG 0x1234"));
+}
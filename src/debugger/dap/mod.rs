@@ -0,0 +1,663 @@
+#[cfg(test)]
+mod tests;
+
+use super::expression::{Expression, Template};
+use super::{decode_at, parse_number, set_register, word_at, Debugger, Stop};
+use chip8::movie::Movie;
+use chip8::{Chip8, Platform};
+use serde_json::Value;
+use std::convert::TryFrom;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::mem;
+use std::net::TcpListener;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The only thread, since the machine has one.
+const THREAD_ID: u64 = 1;
+const REGISTERS_REFERENCE: u64 = 1;
+/// The disassembly of the program, served by the `source` request.
+const LISTING_REFERENCE: u64 = 1;
+const PROGRAM_START: usize = 0x200;
+const FRAME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// Serves one Debug Adapter Protocol client on `input` and `output`, such
+/// as stdin and stdout, until it disconnects.
+///
+/// The program runs in real time, one frame every 60th of a second, with
+/// requests handled between frames. Stack frames point into a listing of
+/// the program with one line per 16-bit word, so line breakpoints set in
+/// it land on addresses, and the disassembly and memory views read the
+/// machine directly.
+pub fn serve<R: Read + Send + 'static, W: Write>(input: R, output: W) -> io::Result<()> {
+    let (sender, requests) = mpsc::channel();
+    thread::spawn(move || {
+        let mut input = BufReader::new(input);
+        while let Ok(Some(message)) = read_message(&mut input) {
+            if sender.send(message).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut server = Server::new(output);
+    let mut next_frame = Instant::now();
+    loop {
+        let request = if server.is_running() {
+            match requests.recv_timeout(next_frame.saturating_duration_since(Instant::now())) {
+                Ok(request) => Some(request),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+        } else {
+            match requests.recv() {
+                Ok(request) => Some(request),
+                Err(_) => return Ok(()),
+            }
+        };
+        match request {
+            Some(request) => {
+                let was_running = server.is_running();
+                if !server.handle(&request)? {
+                    return Ok(());
+                }
+                if !was_running {
+                    next_frame = Instant::now();
+                }
+            }
+            None => {
+                server.run_frame()?;
+                next_frame += FRAME;
+            }
+        }
+    }
+}
+
+/// Serves the first client to connect to `listener`.
+pub fn serve_tcp(listener: &TcpListener) -> io::Result<()> {
+    let (stream, _) = listener.accept()?;
+    stream.set_nodelay(true)?;
+    serve(stream.try_clone()?, stream)
+}
+
+/// Reads one message, or `None` at the end of the input.
+fn read_message<R: BufRead>(input: &mut R) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = value.trim().parse().ok();
+        }
+    }
+    let length = length.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length"))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+fn write_message<W: Write>(output: &mut W, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    //In one piece, so a socket doesn't hold the body back waiting on an ack
+    output.write_all(format!("Content-Length: {}\r\n\r\n{}", body.len(), body).as_bytes())?;
+    output.flush()
+}
+
+/// The program being debugged.
+struct Session {
+    chip8: Chip8,
+    debugger: Debugger,
+    name: String,
+    length: usize,
+    stop_on_entry: bool,
+    /// Ids of the breakpoints from `setBreakpoints` and
+    /// `setInstructionBreakpoints`, which each replace their own set.
+    line_breakpoints: Vec<usize>,
+    instruction_breakpoints: Vec<usize>,
+}
+
+struct Server<W> {
+    output: W,
+    seq: u64,
+    session: Option<Session>,
+    /// Events to send once the current response is out.
+    events: Vec<Value>,
+}
+
+impl<W: Write> Server<W> {
+    fn new(output: W) -> Self {
+        Server {
+            output,
+            seq: 0,
+            session: None,
+            events: Vec::new(),
+        }
+    }
+
+    fn is_running(&self) -> bool {
+        self.session
+            .as_ref()
+            .is_some_and(|session| !session.debugger.is_paused() && !session.chip8.exited)
+    }
+
+    fn send(&mut self, mut message: Value) -> io::Result<()> {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+        write_message(&mut self.output, &message)
+    }
+
+    fn event(&mut self, event: &str, body: Value) {
+        self.events
+            .push(json!({ "type": "event", "event": event, "body": body }));
+    }
+
+    fn stopped(&mut self, reason: &str, description: &str) {
+        self.event(
+            "stopped",
+            json!({
+                "reason": reason,
+                "description": description,
+                "threadId": THREAD_ID,
+                "allThreadsStopped": true,
+            }),
+        );
+    }
+
+    /// Sends the messages logpoints printed to the debug console.
+    fn output(&mut self, log: Vec<String>) {
+        for message in log {
+            self.event(
+                "output",
+                json!({ "category": "console", "output": format!("{}\n", message) }),
+            );
+        }
+    }
+
+    /// Answers one request. Returns false once the client has disconnected.
+    fn handle(&mut self, request: &Value) -> io::Result<bool> {
+        let command = request["command"].as_str().unwrap_or_default();
+        let arguments = &request["arguments"];
+        let result = match command {
+            "initialize" => Ok(capabilities()),
+            "launch" => self.launch(arguments),
+            "configurationDone" => self.configuration_done(),
+            "setBreakpoints" => self.set_breakpoints(arguments),
+            "setInstructionBreakpoints" => self.set_instruction_breakpoints(arguments),
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "CHIP-8" }] })),
+            "stackTrace" => self.session().map(|session| stack_trace(session)),
+            "scopes" => Ok(json!({ "scopes": [{
+                "name": "Registers",
+                "presentationHint": "registers",
+                "variablesReference": REGISTERS_REFERENCE,
+                "expensive": false,
+            }] })),
+            "variables" => self.session().map(|session| registers(&session.chip8)),
+            "setVariable" => self.set_variable(arguments),
+            "source" => self.session().map(|session| json!({ "content": listing(session) })),
+            "continue" => self.resume(),
+            "pause" => self.pause(),
+            "next" => self.step(|session| Ok(session.debugger.step_over(&mut session.chip8))),
+            "stepIn" => self.step(|session| Ok(session.debugger.step_instruction(&mut session.chip8))),
+            "stepOut" => self.step(|session| {
+                if session.debugger.step_out(&session.chip8) {
+                    Ok(None)
+                } else {
+                    Err("Not in a subroutine".to_string())
+                }
+            }),
+            "evaluate" => self.evaluate(arguments),
+            "readMemory" => self
+                .session()
+                .and_then(|session| read_memory(&session.chip8, arguments)),
+            "disassemble" => self
+                .session()
+                .and_then(|session| disassemble(&session.chip8, arguments)),
+            "disconnect" => Ok(Value::Null),
+            "terminate" => {
+                self.event("terminated", json!({}));
+                Ok(Value::Null)
+            }
+            _ => Err(format!("Unsupported request {}", command)),
+        };
+
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": command,
+            "success": result.is_ok(),
+        });
+        match result {
+            Ok(Value::Null) => {}
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = json!(message),
+        }
+        self.send(response)?;
+        for event in mem::take(&mut self.events) {
+            self.send(event)?;
+        }
+        Ok(command != "disconnect")
+    }
+
+    fn session(&mut self) -> Result<&mut Session, String> {
+        self.session
+            .as_mut()
+            .ok_or_else(|| "No program has been launched".to_string())
+    }
+
+    fn launch(&mut self, arguments: &Value) -> Result<Value, String> {
+        let path = arguments["program"].as_str().ok_or("Launch needs a program")?;
+        let rom = fs::read(path).map_err(|error| format!("Could not read {}: {}", path, error))?;
+        let platform = match arguments["platform"].as_str() {
+            None | Some("chip8") => Platform::Chip8,
+            Some("schip") => Platform::SuperChip,
+            Some("xochip") => Platform::XoChip,
+            Some(other) => return Err(format!("Unknown platform {}, expected chip8, schip or xochip", other)),
+        };
+        let instructions_per_frame = arguments["ipf"]
+            .as_u64()
+            .map_or_else(|| platform.instructions_per_frame(), |ipf| ipf as usize);
+        let seed = arguments["seed"].as_u64().unwrap_or_default();
+        let movie = Movie::new(&rom, seed, platform, platform.quirks(), instructions_per_frame);
+        let chip8 = movie.start(&rom).map_err(|error| error.to_string())?;
+
+        //Nothing runs until the client has sent its breakpoints
+        let mut debugger = Debugger::new(instructions_per_frame);
+        debugger.pause();
+        let name = path.rsplit(['/', '\\']).next().unwrap_or(path).to_string();
+        self.session = Some(Session {
+            chip8,
+            debugger,
+            name,
+            length: rom.len(),
+            stop_on_entry: arguments["stopOnEntry"].as_bool().unwrap_or_default(),
+            line_breakpoints: Vec::new(),
+            instruction_breakpoints: Vec::new(),
+        });
+        self.event("initialized", json!({}));
+        Ok(Value::Null)
+    }
+
+    fn configuration_done(&mut self) -> Result<Value, String> {
+        let session = self.session()?;
+        if session.stop_on_entry {
+            self.stopped("entry", "Stopped on entry");
+        } else {
+            session.debugger.resume();
+        }
+        Ok(Value::Null)
+    }
+
+    fn set_breakpoints(&mut self, arguments: &Value) -> Result<Value, String> {
+        let session = self.session()?;
+        for id in session.line_breakpoints.drain(..) {
+            session.debugger.remove(id);
+        }
+        let mut breakpoints = Vec::new();
+        for requested in arguments["breakpoints"].as_array().into_iter().flatten() {
+            let line = requested["line"].as_u64().unwrap_or(1);
+            let address = line
+                .checked_sub(1)
+                .and_then(|offset| usize::try_from(offset).ok())
+                .and_then(|offset| offset.checked_mul(2))
+                .and_then(|offset| PROGRAM_START.checked_add(offset))
+                .filter(|&address| address < session.chip8.memory.len());
+            let mut breakpoint = match address {
+                Some(address) => add_breakpoint(session, address, requested),
+                None => json!({ "verified": false, "message": format!("Line {} is outside memory", line) }),
+            };
+            if let Some(id) = breakpoint["id"].as_u64() {
+                session.line_breakpoints.push(id as usize);
+            }
+            breakpoint["line"] = json!(line);
+            breakpoints.push(breakpoint);
+        }
+        Ok(json!({ "breakpoints": breakpoints }))
+    }
+
+    fn set_instruction_breakpoints(&mut self, arguments: &Value) -> Result<Value, String> {
+        let session = self.session()?;
+        for id in session.instruction_breakpoints.drain(..) {
+            session.debugger.remove(id);
+        }
+        let mut breakpoints = Vec::new();
+        for requested in arguments["breakpoints"].as_array().into_iter().flatten() {
+            let address = address_argument(&requested["instructionReference"], &requested["offset"])?;
+            let breakpoint = add_breakpoint(session, address, requested);
+            if let Some(id) = breakpoint["id"].as_u64() {
+                session.instruction_breakpoints.push(id as usize);
+            }
+            breakpoints.push(breakpoint);
+        }
+        Ok(json!({ "breakpoints": breakpoints }))
+    }
+
+    fn set_variable(&mut self, arguments: &Value) -> Result<Value, String> {
+        let session = self.session()?;
+        let name = arguments["name"].as_str().unwrap_or_default();
+        let value = parse_number(arguments["value"].as_str().unwrap_or_default().trim())?;
+        set_register(&mut session.chip8, name, value)?;
+        Ok(json!({ "value": format!("0x{:X}", value) }))
+    }
+
+    fn resume(&mut self) -> Result<Value, String> {
+        self.session()?.debugger.resume();
+        Ok(json!({ "allThreadsContinued": true }))
+    }
+
+    fn pause(&mut self) -> Result<Value, String> {
+        self.session()?.debugger.pause();
+        self.stopped("pause", "Paused");
+        Ok(Value::Null)
+    }
+
+    /// Starts a step. Single steps are over straight away, while stepping
+    /// over a call or out of a subroutine finishes in a later frame.
+    fn step<F>(&mut self, start: F) -> Result<Value, String>
+    where
+        F: FnOnce(&mut Session) -> Result<Option<Stop>, String>,
+    {
+        let session = self.session()?;
+        let stop = match start(session) {
+            Ok(stop) => stop,
+            Err(error) => {
+                session.debugger.pause();
+                return Err(error);
+            }
+        };
+        let paused = session.debugger.is_paused();
+        let log = session.debugger.take_log();
+        self.output(log);
+        match stop {
+            Some(stop) => self.stopped(stop_reason(&stop), &stop.to_string()),
+            None if paused => self.stopped("step", "Stopped"),
+            None => {}
+        }
+        Ok(Value::Null)
+    }
+
+    /// Evaluates an expression. In the debug console, anything else is run
+    /// as a debugger command.
+    fn evaluate(&mut self, arguments: &Value) -> Result<Value, String> {
+        let text = arguments["expression"].as_str().unwrap_or_default();
+        let repl = arguments["context"] == "repl";
+        let session = self.session()?;
+        let result = match Expression::parse(text) {
+            Ok(expression) => {
+                let value = expression.evaluate(&session.chip8).map_err(|error| error.to_string())?;
+                format!("0x{:X} ({})", value, value)
+            }
+            Err(error) if !repl => return Err(error.to_string()),
+            Err(_) => {
+                let (was_paused, pc) = (session.debugger.is_paused(), session.chip8.pc);
+                let output = session.debugger.execute(&mut session.chip8, text);
+                match (was_paused, session.debugger.is_paused()) {
+                    (true, false) => self.event(
+                        "continued",
+                        json!({ "threadId": THREAD_ID, "allThreadsContinued": true }),
+                    ),
+                    (_, true) if !was_paused || self.session()?.chip8.pc != pc => self.stopped("step", "Stopped"),
+                    _ => {}
+                }
+                output
+            }
+        };
+        Ok(json!({ "result": result, "variablesReference": 0 }))
+    }
+
+    /// Runs a frame and reports whatever happened in it.
+    fn run_frame(&mut self) -> io::Result<()> {
+        let session = match self.session {
+            Some(ref mut session) => session,
+            None => return Ok(()),
+        };
+        let stop = session.debugger.run_frame(&mut session.chip8);
+        let log = session.debugger.take_log();
+        let exited = session.chip8.exited;
+        self.output(log);
+        match stop {
+            Some(stop) => self.stopped(stop_reason(&stop), &stop.to_string()),
+            None if exited => {
+                self.event("exited", json!({ "exitCode": 0 }));
+                self.event("terminated", json!({}));
+            }
+            None => {}
+        }
+        for event in mem::take(&mut self.events) {
+            self.send(event)?;
+        }
+        Ok(())
+    }
+}
+
+/// The protocol's name for why the program stopped.
+fn stop_reason(stop: &Stop) -> &'static str {
+    match *stop {
+        Stop::Breakpoint(_) | Stop::Condition(_) => "breakpoint",
+        Stop::Watchpoint { .. } => "data breakpoint",
+        Stop::Step => "step",
+        Stop::Error(_) | Stop::Invalid { .. } => "exception",
+    }
+}
+
+fn capabilities() -> Value {
+    json!({
+        "supportsConfigurationDoneRequest": true,
+        "supportsConditionalBreakpoints": true,
+        "supportsLogPoints": true,
+        "supportsInstructionBreakpoints": true,
+        "supportsSetVariable": true,
+        "supportsReadMemoryRequest": true,
+        "supportsDisassembleRequest": true,
+        "supportsTerminateRequest": true,
+    })
+}
+
+/// Reads an address from a memory or instruction reference, such as
+/// `"0x200"`, plus an optional offset.
+fn address_argument(reference: &Value, offset: &Value) -> Result<usize, String> {
+    let base = parse_number(reference.as_str().unwrap_or_default())?;
+    let offset = offset.as_i64().unwrap_or_default();
+    i64::try_from(base)
+        .ok()
+        .and_then(|base| base.checked_add(offset))
+        .and_then(|address| usize::try_from(address).ok())
+        .ok_or_else(|| format!("0x{:X} offset by {} is not an address", base, offset))
+}
+
+/// Adds a breakpoint, or a logpoint when there is a log message, and
+/// describes it in the protocol's terms.
+fn add_breakpoint(session: &mut Session, address: usize, requested: &Value) -> Value {
+    let condition = match requested["condition"]
+        .as_str()
+        .filter(|condition| !condition.trim().is_empty())
+    {
+        Some(condition) => match Expression::parse(condition) {
+            Ok(condition) => Some(condition),
+            Err(error) => return json!({ "verified": false, "message": format!("Bad condition: {}", error) }),
+        },
+        None => None,
+    };
+    let id = match requested["logMessage"].as_str() {
+        Some(message) => match Template::parse(message) {
+            Ok(message) => session.debugger.add_logpoint(address, message, condition),
+            Err(error) => return json!({ "verified": false, "message": format!("Bad log message: {}", error) }),
+        },
+        None => session.debugger.add_breakpoint(address, condition),
+    };
+    json!({
+        "id": id,
+        "verified": address < session.chip8.memory.len(),
+        "instructionReference": format!("0x{:03X}", address),
+    })
+}
+
+/// The line of the listing an address is on, if it is in the program.
+fn listing_line(session: &Session, address: usize) -> Option<usize> {
+    if address >= PROGRAM_START && address < PROGRAM_START + session.length {
+        Some((address - PROGRAM_START) / 2 + 1)
+    } else {
+        None
+    }
+}
+
+/// The program as it is in memory now, one line per 16-bit word.
+fn listing(session: &Session) -> String {
+    let end = (PROGRAM_START + session.length).min(session.chip8.memory.len().saturating_sub(1));
+    let lines: Vec<String> = (PROGRAM_START..end)
+        .step_by(2)
+        .map(|address| {
+            let text = decode_at(&session.chip8, address)
+                .map_or("???".to_string(), |(instruction, _)| instruction.to_string());
+            format!("0x{:03X}: {:04X}  {}", address, word_at(&session.chip8, address), text)
+        })
+        .collect();
+    lines.join("\n")
+}
+
+/// The current instruction, then each call on the stack, innermost first.
+fn stack_trace(session: &Session) -> Value {
+    let chip8 = &session.chip8;
+    let callers = chip8.stack[..chip8.sp]
+        .iter()
+        .rev()
+        .map(|&address| usize::from(address).saturating_sub(2));
+    let frames: Vec<Value> = Some(chip8.pc)
+        .into_iter()
+        .chain(callers)
+        .enumerate()
+        .map(|(id, address)| {
+            let mut frame = json!({
+                "id": id,
+                "name": format!("0x{:03X}", address),
+                "instructionPointerReference": format!("0x{:03X}", address),
+                "line": 0,
+                "column": 0,
+            });
+            if let Some(line) = listing_line(session, address) {
+                frame["line"] = json!(line);
+                frame["column"] = json!(1);
+                frame["source"] = json!({ "name": session.name, "sourceReference": LISTING_REFERENCE });
+            }
+            frame
+        })
+        .collect();
+    json!({ "stackFrames": frames, "totalFrames": frames.len() })
+}
+
+fn registers(chip8: &Chip8) -> Value {
+    let variable = |name: String, value: String| json!({ "name": name, "value": value, "variablesReference": 0 });
+    let mut variables: Vec<Value> = chip8
+        .V
+        .iter()
+        .enumerate()
+        .map(|(x, value)| variable(format!("V{:X}", x), format!("0x{:02X}", value)))
+        .collect();
+    let mut i = variable("I".to_string(), format!("0x{:04X}", chip8.I));
+    i["memoryReference"] = json!(format!("0x{:X}", chip8.I));
+    variables.push(i);
+    let mut pc = variable("PC".to_string(), format!("0x{:03X}", chip8.pc));
+    pc["memoryReference"] = json!(format!("0x{:X}", chip8.pc));
+    variables.push(pc);
+    variables.push(variable("SP".to_string(), chip8.sp.to_string()));
+    variables.push(variable("DT".to_string(), chip8.delay.to_string()));
+    variables.push(variable("ST".to_string(), chip8.sound.to_string()));
+    json!({ "variables": variables })
+}
+
+fn read_memory(chip8: &Chip8, arguments: &Value) -> Result<Value, String> {
+    let address = address_argument(&arguments["memoryReference"], &arguments["offset"])?;
+    let count = arguments["count"].as_u64().unwrap_or_default() as usize;
+    let end = address.saturating_add(count).min(chip8.memory.len());
+    let bytes: Vec<u8> = (address.min(end)..end)
+        .map(|address| chip8.memory.peek(address))
+        .collect();
+    Ok(json!({
+        "address": format!("0x{:X}", address),
+        "data": base64(&bytes),
+        "unreadableBytes": count - bytes.len(),
+    }))
+}
+
+/// Disassembles `instructionCount` instructions around a memory reference.
+/// Instructions before it are taken to be a word each.
+fn disassemble(chip8: &Chip8, arguments: &Value) -> Result<Value, String> {
+    let base = address_argument(&arguments["memoryReference"], &arguments["offset"])?;
+    let offset = arguments["instructionOffset"].as_i64().unwrap_or_default();
+    let mut address = i64::try_from(base)
+        .ok()
+        .zip(offset.checked_mul(2))
+        .and_then(|(base, offset)| base.checked_add(offset))
+        .ok_or_else(|| format!("0x{:X} offset by {} instructions is not an address", base, offset))?;
+    let count = arguments["instructionCount"].as_u64().unwrap_or_default();
+    if count > chip8.memory.len() as u64 {
+        return Err(format!("{} instructions is more than memory holds", count));
+    }
+    let mut instructions = Vec::new();
+    for _ in 0..count {
+        let in_memory = usize::try_from(address)
+            .ok()
+            .filter(|&at| at.saturating_add(1) < chip8.memory.len());
+        instructions.push(match in_memory.map(|at| (at, decode_at(chip8, at))) {
+            Some((at, Some((instruction, size)))) => {
+                let bytes: Vec<String> = (at..at.saturating_add(size))
+                    .map(|address| format!("{:02X}", chip8.memory.peek(address)))
+                    .collect();
+                let entry = json!({
+                    "address": format!("0x{:03X}", address),
+                    "instructionBytes": bytes.join(" "),
+                    "instruction": instruction.to_string(),
+                });
+                address = address.saturating_add(size as i64);
+                entry
+            }
+            Some((at, None)) => {
+                let entry = json!({
+                    "address": format!("0x{:03X}", address),
+                    "instructionBytes": format!("{:04X}", word_at(chip8, at)),
+                    "instruction": "???",
+                });
+                address = address.saturating_add(2);
+                entry
+            }
+            None => {
+                let entry = json!({
+                    "address": format!("0x{:03X}", address.max(0)),
+                    "instruction": "",
+                    "presentationHint": "invalid",
+                });
+                address = address.saturating_add(2);
+                entry
+            }
+        });
+    }
+    Ok(json!({ "instructions": instructions }))
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let group = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |group, (i, &byte)| group | u32::from(byte) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(group >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
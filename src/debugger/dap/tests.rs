@@ -0,0 +1,245 @@
+use debugger::dap::{read_message, serve_tcp, write_message};
+use serde_json::Value;
+use std::env;
+use std::fs;
+use std::io::BufReader;
+use std::net::{TcpListener, TcpStream};
+use std::process;
+use std::thread;
+use std::time::Duration;
+
+//Call a subroutine at 0x208 that adds to V1 twice, then loop back to the start
+const PROGRAM: [u8; 14] = [
+    0x60, 0x01, 0x22, 0x08, 0x62, 0x02, 0x12, 0x00, 0x71, 0x01, 0x71, 0x01, 0x00, 0xEE,
+];
+
+/// A scripted client talking to a server on a local socket.
+struct Client {
+    input: BufReader<TcpStream>,
+    output: TcpStream,
+    seq: u64,
+    events: Vec<Value>,
+}
+
+impl Client {
+    fn connect() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || serve_tcp(&listener).unwrap());
+        let output = TcpStream::connect(address).unwrap();
+        output.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        Client {
+            input: BufReader::new(output.try_clone().unwrap()),
+            output,
+            seq: 0,
+            events: Vec::new(),
+        }
+    }
+
+    /// Starts the program at the top of the loop, with the machine paused
+    /// until configurationDone.
+    fn launch(name: &str, stop_on_entry: bool) -> Self {
+        let path = env::temp_dir().join(format!("chip8-dap-{}-{}.ch8", process::id(), name));
+        fs::write(&path, PROGRAM).unwrap();
+        let mut client = Client::connect();
+        assert_eq!(
+            client.request("initialize", json!({ "adapterID": "chip8" }))["success"],
+            true
+        );
+        let launched = client.request(
+            "launch",
+            json!({ "program": path.to_str().unwrap(), "ipf": 10, "stopOnEntry": stop_on_entry }),
+        );
+        assert_eq!(launched["success"], true);
+        client.event("initialized");
+        client
+    }
+
+    fn request(&mut self, command: &str, arguments: Value) -> Value {
+        self.seq += 1;
+        let request = json!({ "seq": self.seq, "type": "request", "command": command, "arguments": arguments });
+        write_message(&mut self.output, &request).unwrap();
+        loop {
+            let message = read_message(&mut self.input).unwrap().unwrap();
+            if message["type"] == "response" && message["request_seq"] == self.seq {
+                return message;
+            }
+            self.events.push(message);
+        }
+    }
+
+    /// The body of the next request's response, which must succeed.
+    fn body(&mut self, command: &str, arguments: Value) -> Value {
+        let response = self.request(command, arguments);
+        assert_eq!(response["success"], true, "{}", response);
+        response["body"].clone()
+    }
+
+    /// Waits for an event, skipping any others.
+    fn event(&mut self, name: &str) -> Value {
+        if let Some(position) = self.events.iter().position(|event| event["event"] == name) {
+            return self.events.remove(position)["body"].clone();
+        }
+        loop {
+            let message = read_message(&mut self.input).unwrap().unwrap();
+            if message["event"] == name {
+                return message["body"].clone();
+            }
+        }
+    }
+
+    fn pc(&mut self) -> Value {
+        self.body("stackTrace", json!({ "threadId": 1 }))["stackFrames"][0]["name"].clone()
+    }
+}
+
+#[test]
+fn test_conditional_breakpoint_and_registers() {
+    let mut client = Client::launch("breakpoint", false);
+    let breakpoints = client.body(
+        "setBreakpoints",
+        json!({ "source": { "sourceReference": 1 }, "breakpoints": [{ "line": 6, "condition": "V1 == 3" }, { "line": 2, "condition": "V1 ==" }] }),
+    );
+    assert_eq!(breakpoints["breakpoints"][0]["verified"], true);
+    assert_eq!(breakpoints["breakpoints"][0]["instructionReference"], "0x20A");
+    assert_eq!(breakpoints["breakpoints"][1]["verified"], false);
+    let far = client.body(
+        "setBreakpoints",
+        json!({ "source": { "sourceReference": 1 }, "breakpoints": [{ "line": 6, "condition": "V1 == 3" }, { "line": u64::MAX }] }),
+    );
+    assert_eq!(far["breakpoints"][1]["verified"], false);
+    assert_eq!(far["breakpoints"][1]["line"], u64::MAX);
+    client.body("configurationDone", json!({}));
+
+    assert_eq!(client.event("stopped")["reason"], "breakpoint");
+    let trace = client.body("stackTrace", json!({ "threadId": 1 }));
+    assert_eq!(trace["stackFrames"][0]["name"], "0x20A");
+    assert_eq!(trace["stackFrames"][0]["line"], 6);
+    assert_eq!(trace["stackFrames"][1]["name"], "0x202");
+
+    let scopes = client.body("scopes", json!({ "frameId": 0 }));
+    let reference = scopes["scopes"][0]["variablesReference"].clone();
+    let variables = client.body("variables", json!({ "variablesReference": reference }));
+    let names: Vec<&str> = variables["variables"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|v| v["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names[..2], ["V0", "V1"]);
+    assert_eq!(names[16..], ["I", "PC", "SP", "DT", "ST"]);
+    assert_eq!(variables["variables"][1]["value"], "0x03");
+
+    let set = client.body(
+        "setVariable",
+        json!({ "variablesReference": reference, "name": "V5", "value": "0x10" }),
+    );
+    assert_eq!(set["value"], "0x10");
+    assert_eq!(
+        client.body("evaluate", json!({ "expression": "V5 + V1" }))["result"],
+        "0x13 (19)"
+    );
+    client.request("disconnect", json!({}));
+}
+
+#[test]
+fn test_stepping() {
+    let mut client = Client::launch("stepping", true);
+    client.body("configurationDone", json!({}));
+    assert_eq!(client.event("stopped")["reason"], "entry");
+    assert_eq!(client.pc(), "0x200");
+
+    client.body("stepIn", json!({ "threadId": 1 }));
+    assert_eq!(client.event("stopped")["reason"], "step");
+    assert_eq!(client.pc(), "0x202");
+
+    //Stepping over the call finishes once the subroutine returns
+    client.body("next", json!({ "threadId": 1 }));
+    assert_eq!(client.event("stopped")["reason"], "step");
+    assert_eq!(client.pc(), "0x204");
+
+    for _ in 0..5 {
+        client.body("stepIn", json!({ "threadId": 1 }));
+        client.event("stopped");
+    }
+    assert_eq!(client.pc(), "0x20A");
+    client.body("stepOut", json!({ "threadId": 1 }));
+    client.event("stopped");
+    assert_eq!(client.pc(), "0x204");
+    assert_eq!(client.request("stepOut", json!({ "threadId": 1 }))["success"], false);
+
+    client.body("continue", json!({ "threadId": 1 }));
+    client.body("pause", json!({ "threadId": 1 }));
+    assert_eq!(client.event("stopped")["reason"], "pause");
+    client.request("disconnect", json!({}));
+}
+
+#[test]
+fn test_memory_and_disassembly() {
+    let mut client = Client::launch("memory", true);
+    let memory = client.body(
+        "readMemory",
+        json!({ "memoryReference": "0x200", "offset": 2, "count": 4 }),
+    );
+    assert_eq!(memory["address"], "0x202");
+    assert_eq!(memory["data"], "IghiAg==");
+    let memory = client.body("readMemory", json!({ "memoryReference": "0xFFE", "count": 4 }));
+    assert_eq!(memory["unreadableBytes"], 2);
+
+    let disassembly = client.body(
+        "disassemble",
+        json!({ "memoryReference": "0x202", "instructionOffset": -1, "instructionCount": 3 }),
+    );
+    let instructions = disassembly["instructions"].as_array().unwrap();
+    assert_eq!(instructions[0]["address"], "0x200");
+    assert_eq!(instructions[1]["instruction"], "CALL 0x208");
+    assert_eq!(instructions[2]["instructionBytes"], "62 02");
+
+    //Addresses and counts that don't fit are an error rather than a crash
+    let huge = json!({ "memoryReference": "0x7FFFFFFFFFFFFFFF", "offset": 1, "count": 1 });
+    assert_eq!(client.request("readMemory", huge)["success"], false);
+    let huge = json!({ "memoryReference": "0x200", "instructionOffset": i64::MAX, "instructionCount": 1 });
+    assert_eq!(client.request("disassemble", huge)["success"], false);
+    let huge = json!({ "memoryReference": "0x200", "instructionCount": u64::MAX });
+    assert_eq!(client.request("disassemble", huge)["success"], false);
+    let far = client.body(
+        "disassemble",
+        json!({ "memoryReference": "0x7FFFFFFFFFFFFFFF", "instructionCount": 2 }),
+    );
+    assert_eq!(far["instructions"][1]["presentationHint"], "invalid");
+
+    let source = client.body("source", json!({ "sourceReference": 1 }));
+    assert_eq!(
+        source["content"].as_str().unwrap().lines().nth(1),
+        Some("0x202: 2208  CALL 0x208")
+    );
+    client.request("disconnect", json!({}));
+}
+
+#[test]
+fn test_logpoints_and_instruction_breakpoints() {
+    let mut client = Client::launch("logpoints", false);
+    client.body(
+        "setBreakpoints",
+        json!({ "source": { "sourceReference": 1 }, "breakpoints": [{ "line": 5, "logMessage": "V1 is {V1}" }] }),
+    );
+    client.body(
+        "setInstructionBreakpoints",
+        json!({ "breakpoints": [{ "instructionReference": "0x204", "condition": "V1 >= 4" }] }),
+    );
+    client.body("configurationDone", json!({}));
+
+    assert_eq!(client.event("output")["output"], "V1 is 0\n");
+    assert_eq!(client.event("output")["output"], "V1 is 2\n");
+    assert_eq!(client.event("stopped")["reason"], "breakpoint");
+    assert_eq!(client.pc(), "0x204");
+
+    //The debug console runs debugger commands too
+    let result = client.body("evaluate", json!({ "expression": "x 0x200 2", "context": "repl" }));
+    assert_eq!(result["result"], "0x200: 60 01");
+    assert_eq!(
+        client.request("evaluate", json!({ "expression": "x 0x200 2" }))["success"],
+        false
+    );
+    client.request("disconnect", json!({}));
+}
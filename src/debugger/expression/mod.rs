@@ -0,0 +1,460 @@
+#[cfg(test)]
+mod tests;
+
+use chip8::Chip8;
+use std::fmt::{self, Display, Formatter};
+
+/// Something the debugger can evaluate against the machine, such as
+/// `V3 == 0x10 && I >= 0x300`.
+///
+/// Operands are numbers, decimal or hex with `0x`, the registers `V0`-`VF`,
+/// `I`, `PC`, `SP`, `DT` and `ST`, and memory as `[address]`. Operators and
+/// their precedence are Rust's: `* / %`, `+ -`, `<< >>`, `&`, `^`, `|`,
+/// comparisons, `&&`, `||`, plus unary `-`, `!` and `~`. Comparisons and
+/// logic give 1 or 0, and any non-zero value counts as true.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expression {
+    source: String,
+    root: Node,
+}
+
+/// Why an expression could not be parsed. `position` is the byte offset of
+/// the problem in the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{} at column {}", self.message, self.position + 1)
+    }
+}
+
+/// Why an expression could not be evaluated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvalError {
+    DivisionByZero,
+    /// `[address]` is outside memory.
+    OutOfMemory(i64),
+}
+
+impl Display for EvalError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            EvalError::DivisionByZero => write!(f, "division by zero"),
+            EvalError::OutOfMemory(address) => write!(f, "[0x{:X}] is outside memory", address),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Register {
+    V(usize),
+    I,
+    Pc,
+    Sp,
+    Delay,
+    Sound,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Unary {
+    Negate,
+    Not,
+    Complement,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Binary {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Shl,
+    Shr,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+impl Binary {
+    fn precedence(self) -> u8 {
+        match self {
+            Binary::Or => 1,
+            Binary::And => 2,
+            Binary::Eq | Binary::Ne | Binary::Lt | Binary::Le | Binary::Gt | Binary::Ge => 3,
+            Binary::BitOr => 4,
+            Binary::BitXor => 5,
+            Binary::BitAnd => 6,
+            Binary::Shl | Binary::Shr => 7,
+            Binary::Add | Binary::Sub => 8,
+            Binary::Mul | Binary::Div | Binary::Rem => 9,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Number(i64),
+    Register(Register),
+    Memory(Box<Node>),
+    Unary(Unary, Box<Node>),
+    Binary(Binary, Box<Node>, Box<Node>),
+}
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Self, ParseError> {
+        let mut parser = Parser { source, position: 0 };
+        let root = parser.expression(0)?;
+        parser.skip_whitespace();
+        if parser.position < source.len() {
+            return Err(parser.error("unexpected input"));
+        }
+        Ok(Expression {
+            source: source.trim().to_string(),
+            root,
+        })
+    }
+
+    pub fn evaluate(&self, chip8: &Chip8) -> Result<i64, EvalError> {
+        evaluate(&self.root, chip8)
+    }
+
+    /// Evaluates the expression as a condition.
+    pub fn is_true(&self, chip8: &Chip8) -> Result<bool, EvalError> {
+        self.evaluate(chip8).map(|value| value != 0)
+    }
+}
+
+impl Display for Expression {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+fn evaluate(node: &Node, chip8: &Chip8) -> Result<i64, EvalError> {
+    Ok(match *node {
+        Node::Number(value) => value,
+        Node::Register(register) => match register {
+            Register::V(x) => i64::from(chip8.V[x]),
+            Register::I => i64::from(chip8.I),
+            Register::Pc => chip8.pc as i64,
+            Register::Sp => chip8.sp as i64,
+            Register::Delay => i64::from(chip8.delay),
+            Register::Sound => i64::from(chip8.sound),
+        },
+        Node::Memory(ref address) => {
+            let address = evaluate(address, chip8)?;
+            if address < 0 || address as usize >= chip8.memory.len() {
+                return Err(EvalError::OutOfMemory(address));
+            }
+            i64::from(chip8.memory.peek(address as usize))
+        }
+        Node::Unary(operator, ref operand) => {
+            let value = evaluate(operand, chip8)?;
+            match operator {
+                Unary::Negate => value.wrapping_neg(),
+                Unary::Not => (value == 0) as i64,
+                Unary::Complement => !value,
+            }
+        }
+        Node::Binary(Binary::And, ref left, ref right) => {
+            (evaluate(left, chip8)? != 0 && evaluate(right, chip8)? != 0) as i64
+        }
+        Node::Binary(Binary::Or, ref left, ref right) => {
+            (evaluate(left, chip8)? != 0 || evaluate(right, chip8)? != 0) as i64
+        }
+        Node::Binary(operator, ref left, ref right) => {
+            let (left, right) = (evaluate(left, chip8)?, evaluate(right, chip8)?);
+            match operator {
+                Binary::Eq => (left == right) as i64,
+                Binary::Ne => (left != right) as i64,
+                Binary::Lt => (left < right) as i64,
+                Binary::Le => (left <= right) as i64,
+                Binary::Gt => (left > right) as i64,
+                Binary::Ge => (left >= right) as i64,
+                Binary::BitOr => left | right,
+                Binary::BitXor => left ^ right,
+                Binary::BitAnd => left & right,
+                Binary::Shl => left.wrapping_shl(right as u32),
+                Binary::Shr => left.wrapping_shr(right as u32),
+                Binary::Add => left.wrapping_add(right),
+                Binary::Sub => left.wrapping_sub(right),
+                Binary::Mul => left.wrapping_mul(right),
+                Binary::Div | Binary::Rem if right == 0 => return Err(EvalError::DivisionByZero),
+                Binary::Div => left.wrapping_div(right),
+                Binary::Rem => left.wrapping_rem(right),
+                Binary::And | Binary::Or => unreachable!(),
+            }
+        }
+    })
+}
+
+/// Precedence climbing over the source text.
+struct Parser<'a> {
+    source: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &str) -> ParseError {
+        ParseError {
+            position: self.position,
+            message: message.to_string(),
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.source[self.position..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn eat(&mut self, symbol: &str) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(symbol) {
+            self.position += symbol.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// The operator coming up, without consuming it.
+    fn binary_operator(&mut self) -> Option<(&'static str, Binary)> {
+        self.skip_whitespace();
+        //Longest symbols first so `<=` isn't read as `<`
+        const OPERATORS: [(&str, Binary); 18] = [
+            ("||", Binary::Or),
+            ("&&", Binary::And),
+            ("==", Binary::Eq),
+            ("!=", Binary::Ne),
+            ("<=", Binary::Le),
+            (">=", Binary::Ge),
+            ("<<", Binary::Shl),
+            (">>", Binary::Shr),
+            ("<", Binary::Lt),
+            (">", Binary::Gt),
+            ("|", Binary::BitOr),
+            ("^", Binary::BitXor),
+            ("&", Binary::BitAnd),
+            ("+", Binary::Add),
+            ("-", Binary::Sub),
+            ("*", Binary::Mul),
+            ("/", Binary::Div),
+            ("%", Binary::Rem),
+        ];
+        OPERATORS
+            .iter()
+            .find(|&&(symbol, _)| self.rest().starts_with(symbol))
+            .cloned()
+    }
+
+    /// Parses operators binding tighter than `min_precedence`.
+    fn expression(&mut self, min_precedence: u8) -> Result<Node, ParseError> {
+        let mut left = self.unary()?;
+        while let Some((symbol, operator)) = self.binary_operator() {
+            if operator.precedence() <= min_precedence {
+                break;
+            }
+            self.position += symbol.len();
+            let right = self.expression(operator.precedence())?;
+            left = Node::Binary(operator, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Node, ParseError> {
+        if self.eat("-") {
+            return Ok(Node::Unary(Unary::Negate, Box::new(self.unary()?)));
+        }
+        if self.rest().starts_with("!=") {
+            return Err(self.error("expected a value"));
+        }
+        if self.eat("!") {
+            return Ok(Node::Unary(Unary::Not, Box::new(self.unary()?)));
+        }
+        if self.eat("~") {
+            return Ok(Node::Unary(Unary::Complement, Box::new(self.unary()?)));
+        }
+        self.operand()
+    }
+
+    fn operand(&mut self) -> Result<Node, ParseError> {
+        self.skip_whitespace();
+        if self.eat("(") {
+            let inner = self.expression(0)?;
+            if !self.eat(")") {
+                return Err(self.error("expected )"));
+            }
+            return Ok(inner);
+        }
+        if self.eat("[") {
+            let address = self.expression(0)?;
+            if !self.eat("]") {
+                return Err(self.error("expected ]"));
+            }
+            return Ok(Node::Memory(Box::new(address)));
+        }
+
+        let start = self.position;
+        let word: &str = {
+            let rest = self.rest();
+            let length = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            &rest[..length]
+        };
+        if word.is_empty() {
+            return Err(self.error("expected a value"));
+        }
+        self.position += word.len();
+
+        let upper = word.to_uppercase();
+        let number = if let Some(hex) = upper.strip_prefix("0X") {
+            i64::from_str_radix(hex, 16).ok()
+        } else if upper.starts_with(|c: char| c.is_ascii_digit()) {
+            upper.parse().ok()
+        } else {
+            None
+        };
+        if let Some(number) = number {
+            return Ok(Node::Number(number));
+        }
+        let register = match upper.as_str() {
+            "I" => Register::I,
+            "PC" => Register::Pc,
+            "SP" => Register::Sp,
+            "DT" => Register::Delay,
+            "ST" => Register::Sound,
+            _ => match upper.strip_prefix('V') {
+                Some(digit) if digit.len() == 1 && digit.chars().all(|c| c.is_ascii_hexdigit()) => {
+                    Register::V(usize::from_str_radix(digit, 16).unwrap())
+                }
+                _ => {
+                    self.position = start;
+                    return Err(self.error(&format!("unknown name \"{}\"", word)));
+                }
+            },
+        };
+        Ok(Node::Register(register))
+    }
+}
+
+/// A logpoint message: text with `{expression}` holes, printed in decimal,
+/// or in hex as `{expression:x}`. `{{` and `}}` are literal braces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    source: String,
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Decimal(Expression),
+    Hex(Expression),
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Self, ParseError> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = source.char_indices().peekable();
+        while let Some((position, c)) = chars.next() {
+            match c {
+                '{' if chars.peek().map(|&(_, next)| next) == Some('{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek().map(|&(_, next)| next) == Some('}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let end = source[position..]
+                        .find('}')
+                        .map(|end| position + end)
+                        .ok_or(ParseError {
+                            position,
+                            message: "unclosed {".to_string(),
+                        })?;
+                    let inner = &source[position + 1..end];
+                    let (inner, hex) = match inner.rfind(':') {
+                        Some(colon) if inner[colon + 1..].eq_ignore_ascii_case("x") => (&inner[..colon], true),
+                        _ => (inner, false),
+                    };
+                    let expression = Expression::parse(inner).map_err(|error| ParseError {
+                        position: position + 1 + error.position,
+                        message: error.message,
+                    })?;
+                    if !text.is_empty() {
+                        parts.push(Part::Text(text.clone()));
+                        text.clear();
+                    }
+                    parts.push(if hex {
+                        Part::Hex(expression)
+                    } else {
+                        Part::Decimal(expression)
+                    });
+                    while chars.peek().is_some_and(|&(next, _)| next <= end) {
+                        chars.next();
+                    }
+                }
+                '}' => {
+                    return Err(ParseError {
+                        position,
+                        message: "unmatched }".to_string(),
+                    })
+                }
+                _ => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(Template {
+            source: source.to_string(),
+            parts,
+        })
+    }
+
+    /// Fills in the holes. A hole that cannot be evaluated shows the error.
+    pub fn render(&self, chip8: &Chip8) -> String {
+        let mut out = String::new();
+        for part in &self.parts {
+            match *part {
+                Part::Text(ref text) => out.push_str(text),
+                Part::Decimal(ref expression) => match expression.evaluate(chip8) {
+                    Ok(value) => out.push_str(&value.to_string()),
+                    Err(error) => out.push_str(&format!("<{}>", error)),
+                },
+                Part::Hex(ref expression) => match expression.evaluate(chip8) {
+                    Ok(value) => out.push_str(&format!("0x{:X}", value)),
+                    Err(error) => out.push_str(&format!("<{}>", error)),
+                },
+            }
+        }
+        out
+    }
+}
+
+impl Display for Template {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
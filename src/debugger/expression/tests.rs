@@ -0,0 +1,89 @@
+use chip8::{Chip8, Quirks};
+use debugger::expression::{EvalError, Expression, ParseError, Template};
+
+fn init_cpu() -> Chip8 {
+    let mut cpu = Chip8::new(Quirks::vip());
+    cpu.V[3] = 0x10;
+    cpu.I = 0x300;
+    cpu.delay = 7;
+    cpu.memory.poke(0x300, 0xAB);
+    cpu
+}
+
+fn evaluate(source: &str) -> Result<i64, EvalError> {
+    Expression::parse(source).unwrap().evaluate(&init_cpu())
+}
+
+#[test]
+fn test_registers_and_memory() {
+    assert_eq!(evaluate("V3"), Ok(0x10));
+    assert_eq!(evaluate("v3 + i"), Ok(0x310));
+    assert_eq!(evaluate("PC"), Ok(0x200));
+    assert_eq!(evaluate("DT * ST + SP"), Ok(0));
+    assert_eq!(evaluate("[I]"), Ok(0xAB));
+    assert_eq!(evaluate("[0x2FF + 1] & 0x0F"), Ok(0xB));
+}
+
+#[test]
+fn test_precedence() {
+    assert_eq!(evaluate("V3 == 0x10 && I >= 0x300"), Ok(1));
+    assert_eq!(evaluate("V3 == 0x10 && I > 0x300"), Ok(0));
+    assert_eq!(evaluate("1 + 2 * 3"), Ok(7));
+    assert_eq!(evaluate("(1 + 2) * 3"), Ok(9));
+    assert_eq!(evaluate("[I] & 0x80 == 0x80"), Ok(1));
+    assert_eq!(evaluate("1 << 4 | 1"), Ok(17));
+    assert_eq!(evaluate("0 || 2 && !0"), Ok(1));
+    assert_eq!(evaluate("10 - 4 - 3"), Ok(3));
+    assert_eq!(evaluate("-V3 % 3"), Ok(-1));
+    assert_eq!(evaluate("~0 ^ 1"), Ok(-2));
+}
+
+#[test]
+fn test_evaluation_errors() {
+    assert_eq!(evaluate("V3 / (DT - 7)"), Err(EvalError::DivisionByZero));
+    assert_eq!(evaluate("[0x1000]"), Err(EvalError::OutOfMemory(0x1000)));
+    assert_eq!(evaluate("[-1]"), Err(EvalError::OutOfMemory(-1)));
+    //The right side isn't evaluated when the left decides
+    assert_eq!(evaluate("0 && [0x1000]"), Ok(0));
+}
+
+#[test]
+fn test_parse_errors() {
+    let error = |source| Expression::parse(source).unwrap_err();
+    assert_eq!(
+        error("V3 =="),
+        ParseError {
+            position: 5,
+            message: "expected a value".to_string()
+        }
+    );
+    assert_eq!(
+        error("VG"),
+        ParseError {
+            position: 0,
+            message: "unknown name \"VG\"".to_string()
+        }
+    );
+    assert_eq!(
+        error("(V1"),
+        ParseError {
+            position: 3,
+            message: "expected )".to_string()
+        }
+    );
+    assert_eq!(error("1 2").to_string(), "unexpected input at column 3");
+    assert_eq!(error("0xZZ").message, "unknown name \"0xZZ\"");
+}
+
+#[test]
+fn test_templates() {
+    let cpu = init_cpu();
+    let render = |source| Template::parse(source).unwrap().render(&cpu);
+    assert_eq!(render("V3={V3} I={I:x}"), "V3=16 I=0x300");
+    assert_eq!(
+        render("{{literal}} {[0x1000]}"),
+        "{literal} <[0x1000] is outside memory>"
+    );
+    assert_eq!(Template::parse("{V3").unwrap_err().message, "unclosed {");
+    assert_eq!(Template::parse("x {V3 +} y").unwrap_err().position, 7);
+}
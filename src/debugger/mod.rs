@@ -0,0 +1,825 @@
+#[cfg(test)]
+mod tests;
+
+#[cfg(feature = "dap")]
+pub mod dap;
+pub mod expression;
+
+use self::expression::{EvalError, Expression, Template};
+use chip8::bus::Observed;
+use chip8::opcode::Instruction;
+use chip8::{Access, Chip8, ExecutionError};
+use std::cell::RefCell;
+use std::fmt::{self, Display, Formatter, Write};
+use std::mem;
+use std::ops::Range;
+use std::rc::Rc;
+
+const HELP: &str = "\
+step [n]          s   execute n instructions (default 1)
+next              n   step over a subroutine call
+finish            f   run until the current subroutine returns
+continue          c   run until a breakpoint
+pause                 stop running
+break ADDR        b   stop before the instruction at ADDR runs
+break ADDR if EXPR    stop there only when EXPR holds
+break if EXPR         stop after an instruction makes EXPR true
+watch [read|write|access] ADDR [LEN] [if EXPR]
+                      stop after memory is read or written (default write)
+log ADDR MESSAGE [if EXPR]
+                      print MESSAGE at ADDR without stopping, filling in
+                      {EXPR} in decimal and {EXPR:x} in hex
+enable ID             turn a breakpoint, watchpoint or logpoint back on
+disable ID            turn it off without forgetting it
+delete ID         d   remove it
+breakpoints       i   list them with their hit counts
+registers         r   show registers and timers
+set NAME VALUE        change V0-VF, I, PC, SP, DT or ST
+x ADDR [LEN]          dump memory
+disassemble [ADDR]    disassemble around pc, or from ADDR
+backtrace         bt  show the call stack
+Numbers are decimal, or hex with 0x. An empty line repeats the last command.
+Expressions use V0-VF, I, PC, SP, DT, ST, [ADDR] for memory and Rust's
+operators, for example: V3 == 0x10 && [I] >= 0x30";
+
+/// Why the debugger took control back from the program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    /// Execution reached a breakpoint, which has not run yet.
+    Breakpoint(usize),
+    /// The condition of the breakpoint with this id became true.
+    Condition(usize),
+    /// An instruction touched memory under a watchpoint.
+    Watchpoint {
+        id: usize,
+        address: usize,
+        value: u8,
+        access: Access,
+    },
+    /// The condition of the point with this id could not be evaluated.
+    Invalid { id: usize, error: EvalError },
+    /// A step, step over or step out finished.
+    Step,
+    /// An instruction failed.
+    Error(ExecutionError),
+}
+
+impl Display for Stop {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Stop::Breakpoint(address) => write!(f, "Breakpoint at 0x{:03X}", address),
+            Stop::Condition(id) => write!(f, "Breakpoint {} condition met", id),
+            Stop::Watchpoint {
+                id,
+                address,
+                value,
+                access,
+            } => {
+                let verb = if access == Access::Write { "write of" } else { "read of" };
+                write!(f, "Watchpoint {}: {} 0x{:02X} at 0x{:03X}", id, verb, value, address)
+            }
+            Stop::Invalid { id, error } => write!(f, "Could not check the condition of {}: {}", id, error),
+            Stop::Step => write!(f, "Stopped"),
+            Stop::Error(ref error) => write!(f, "Execution halted: {}", error),
+        }
+    }
+}
+
+/// Which accesses a watchpoint fires on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Watch {
+    Read,
+    Write,
+    /// Both reads and writes.
+    Access,
+}
+
+impl Watch {
+    fn matches(self, access: Access) -> bool {
+        match self {
+            Watch::Read => access == Access::Read,
+            Watch::Write => access == Access::Write,
+            Watch::Access => access != Access::Fetch,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Kind {
+    /// Stops before the instruction at an address, or after any instruction
+    /// when there is no address and only a condition.
+    Breakpoint(Option<usize>),
+    Watchpoint(Range<usize>, Watch),
+    Logpoint(usize, Template),
+}
+
+impl Kind {
+    fn is_condition(&self) -> bool {
+        matches!(*self, Kind::Breakpoint(None))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Point {
+    id: usize,
+    kind: Kind,
+    condition: Option<Expression>,
+    enabled: bool,
+    hits: usize,
+    /// Whether the condition held last time, for breakpoints that fire as
+    /// it becomes true.
+    holding: bool,
+}
+
+impl Display for Point {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}: ", self.id)?;
+        match self.kind {
+            Kind::Breakpoint(Some(address)) => write!(f, "breakpoint at 0x{:03X}", address)?,
+            Kind::Breakpoint(None) => write!(f, "breakpoint")?,
+            Kind::Watchpoint(ref range, watch) => {
+                let watch = match watch {
+                    Watch::Read => "read",
+                    Watch::Write => "write",
+                    Watch::Access => "access",
+                };
+                write!(f, "{} watchpoint on 0x{:03X}", watch, range.start)?;
+                if range.len() > 1 {
+                    write!(f, "-0x{:03X}", range.end - 1)?;
+                }
+            }
+            Kind::Logpoint(address, ref message) => write!(f, "logpoint at 0x{:03X}: {}", address, message)?,
+        }
+        if let Some(ref condition) = self.condition {
+            write!(f, " if {}", condition)?;
+        }
+        write!(f, ", {} hit{}", self.hits, if self.hits == 1 { "" } else { "s" })?;
+        if !self.enabled {
+            write!(f, " (disabled)")?;
+        }
+        Ok(())
+    }
+}
+
+/// What the debugger shares with the memory observer it installs for
+/// watchpoints.
+#[derive(Debug, Default)]
+struct Watches {
+    /// The enabled watchpoints, by id.
+    active: Vec<(usize, Range<usize>, Watch)>,
+    /// Accesses they caught during the current instruction, as id, address,
+    /// value and access.
+    caught: Vec<(usize, usize, u8, Access)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Paused,
+    Running,
+    /// Running until pc reaches `pc` with the stack back at `sp`.
+    StepOver { pc: usize, sp: usize },
+    /// Running until the stack drops below `sp`.
+    StepOut { sp: usize },
+}
+
+/// An interactive debugger driven by text commands.
+///
+/// The frontend feeds it lines typed by the user with
+/// [`Debugger::execute`] and runs the machine through
+/// [`Debugger::run_frame`] instead of [`Chip8::run_frame`]. Nothing runs
+/// while it is paused. Timers tick once every `instructions_per_frame`
+/// instructions, so single stepping keeps them in step with the program.
+///
+/// Breakpoints, watchpoints and logpoints share one list of ids. Their
+/// conditions are checked against the machine between instructions, and
+/// the messages of logpoints collect until the frontend takes them with
+/// [`Debugger::take_log`].
+pub struct Debugger {
+    instructions_per_frame: usize,
+    steps_since_tick: usize,
+    mode: Mode,
+    /// Set when resuming, so the breakpoint the program stopped on doesn't
+    /// stop it again straight away.
+    resuming: bool,
+    /// The pc the points that look at the machine before an instruction
+    /// were last checked at, so a logpoint prints only once however
+    /// execution gets past it.
+    checked_at: Option<usize>,
+    points: Vec<Point>,
+    next_id: usize,
+    watches: Rc<RefCell<Watches>>,
+    log: Vec<String>,
+    last_command: String,
+}
+
+impl Debugger {
+    pub fn new(instructions_per_frame: usize) -> Self {
+        Debugger {
+            instructions_per_frame: instructions_per_frame.max(1),
+            steps_since_tick: 0,
+            mode: Mode::Running,
+            resuming: false,
+            checked_at: None,
+            points: Vec::new(),
+            next_id: 1,
+            watches: Rc::new(RefCell::new(Watches::default())),
+            log: Vec::new(),
+            last_command: String::new(),
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.mode == Mode::Paused
+    }
+
+    pub fn pause(&mut self) {
+        self.mode = Mode::Paused;
+    }
+
+    pub fn resume(&mut self) {
+        self.run(Mode::Running);
+    }
+
+    fn run(&mut self, mode: Mode) {
+        self.mode = mode;
+        self.resuming = true;
+    }
+
+    /// Adds a breakpoint at `address`, which only stops when `condition`
+    /// holds if there is one. Returns its id.
+    pub fn add_breakpoint(&mut self, address: usize, condition: Option<Expression>) -> usize {
+        self.add(Kind::Breakpoint(Some(address)), condition)
+    }
+
+    /// Adds a breakpoint that stops after whichever instruction makes
+    /// `condition` hold. Returns its id.
+    pub fn add_condition(&mut self, condition: Expression) -> usize {
+        self.add(Kind::Breakpoint(None), Some(condition))
+    }
+
+    /// Adds a watchpoint that stops after an instruction reads or writes
+    /// memory in `range`. Returns its id.
+    pub fn add_watchpoint(&mut self, range: Range<usize>, watch: Watch, condition: Option<Expression>) -> usize {
+        self.add(Kind::Watchpoint(range, watch), condition)
+    }
+
+    /// Adds a logpoint that renders `message` whenever execution reaches
+    /// `address` and `condition` holds, without stopping. Returns its id.
+    pub fn add_logpoint(&mut self, address: usize, message: Template, condition: Option<Expression>) -> usize {
+        self.add(Kind::Logpoint(address, message), condition)
+    }
+
+    fn add(&mut self, kind: Kind, condition: Option<Expression>) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.points.push(Point {
+            id,
+            kind,
+            condition,
+            enabled: true,
+            hits: 0,
+            holding: false,
+        });
+        self.update_watches();
+        id
+    }
+
+    /// Removes the breakpoint, watchpoint or logpoint with this id.
+    pub fn remove(&mut self, id: usize) -> bool {
+        let count = self.points.len();
+        self.points.retain(|point| point.id != id);
+        self.update_watches();
+        self.points.len() != count
+    }
+
+    pub fn set_enabled(&mut self, id: usize, enabled: bool) -> bool {
+        match self.points.iter_mut().find(|point| point.id == id) {
+            Some(point) => {
+                point.enabled = enabled;
+                self.update_watches();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// How many times the point with this id has fired.
+    pub fn hits(&self, id: usize) -> Option<usize> {
+        self.points.iter().find(|point| point.id == id).map(|point| point.hits)
+    }
+
+    /// The messages logpoints have printed since the last call.
+    pub fn take_log(&mut self) -> Vec<String> {
+        mem::take(&mut self.log)
+    }
+
+    fn update_watches(&mut self) {
+        self.watches.borrow_mut().active = self
+            .points
+            .iter()
+            .filter(|point| point.enabled)
+            .filter_map(|point| match point.kind {
+                Kind::Watchpoint(ref range, watch) => Some((point.id, range.clone(), watch)),
+                _ => None,
+            })
+            .collect();
+    }
+
+    /// Wraps the machine's memory so watchpoints see its accesses, unless
+    /// that has already happened. Replacing the memory drops the wrapper,
+    /// which shows in the count of handles to the shared state.
+    fn observe(&self, chip8: &mut Chip8) {
+        if Rc::strong_count(&self.watches) > 1 || self.watches.borrow().active.is_empty() {
+            return;
+        }
+        let watches = self.watches.clone();
+        chip8.wrap_memory(|memory| {
+            Box::new(Observed::new(memory, move |address, value, access| {
+                let mut watches = watches.borrow_mut();
+                let caught: Vec<_> = watches
+                    .active
+                    .iter()
+                    .filter(|&&(_, ref range, watch)| range.contains(&address) && watch.matches(access))
+                    .map(|&(id, _, _)| (id, address, value, access))
+                    .collect();
+                watches.caught.extend(caught);
+            }))
+        });
+    }
+
+    /// Whether the point's condition holds, counting a hit if so.
+    fn fire(point: &mut Point, chip8: &Chip8) -> Result<bool, Stop> {
+        let holds = match point.condition {
+            Some(ref condition) => condition
+                .is_true(chip8)
+                .map_err(|error| Stop::Invalid { id: point.id, error })?,
+            None => true,
+        };
+        //A condition that stays true only counts when it becomes true
+        if holds && !(point.holding && point.kind.is_condition()) {
+            point.hits += 1;
+        }
+        Ok(holds)
+    }
+
+    /// Checks the points that look at the machine between instructions:
+    /// logpoints and breakpoints at pc, and breakpoints with only a
+    /// condition, which fire when it becomes true. Only the logpoints are
+    /// checked when `logpoints_only` is set.
+    fn check_before(&mut self, chip8: &Chip8, logpoints_only: bool) -> Option<Stop> {
+        self.checked_at = Some(chip8.pc);
+        let mut stop = None;
+        for point in self.points.iter_mut().filter(|point| point.enabled) {
+            let fired = match point.kind {
+                Kind::Breakpoint(_) if logpoints_only => continue,
+                Kind::Breakpoint(Some(address)) | Kind::Logpoint(address, _) if address == chip8.pc => {
+                    Self::fire(point, chip8)
+                }
+                Kind::Breakpoint(None) => {
+                    let was_holding = point.holding;
+                    let fired = Self::fire(point, chip8);
+                    point.holding = fired == Ok(true);
+                    fired.map(|holds| holds && !was_holding)
+                }
+                _ => continue,
+            };
+            let fired = match fired {
+                Ok(fired) => fired,
+                Err(invalid) => {
+                    stop = stop.or(Some(invalid));
+                    continue;
+                }
+            };
+            match point.kind {
+                Kind::Logpoint(_, ref message) if fired => self.log.push(message.render(chip8)),
+                Kind::Breakpoint(Some(address)) if fired => stop = stop.or(Some(Stop::Breakpoint(address))),
+                Kind::Breakpoint(None) if fired => stop = stop.or(Some(Stop::Condition(point.id))),
+                _ => {}
+            }
+        }
+        stop
+    }
+
+    /// Checks the accesses the last instruction made against the
+    /// watchpoints.
+    fn check_watches(&mut self, chip8: &Chip8) -> Option<Stop> {
+        let caught = mem::take(&mut self.watches.borrow_mut().caught);
+        let mut stop = None;
+        for (id, address, value, access) in caught {
+            let point = match self.points.iter_mut().find(|point| point.id == id) {
+                Some(point) => point,
+                None => continue,
+            };
+            match Self::fire(point, chip8) {
+                Ok(true) => {
+                    stop = stop.or(Some(Stop::Watchpoint {
+                        id,
+                        address,
+                        value,
+                        access,
+                    }))
+                }
+                Ok(false) => {}
+                Err(invalid) => stop = stop.or(Some(invalid)),
+            }
+        }
+        stop
+    }
+
+    fn is_breakpoint(&self, address: usize) -> bool {
+        self.points.iter().any(|point| match point.kind {
+            Kind::Breakpoint(Some(at)) => point.enabled && at == address,
+            _ => false,
+        })
+    }
+
+    /// The prompt to show while paused, with the next instruction.
+    pub fn prompt(&self, chip8: &Chip8) -> String {
+        format!("{}\n(chip8) ", self.disassemble_line(chip8, chip8.pc))
+    }
+
+    /// Runs one frame's worth of instructions unless paused. Returns why it
+    /// stopped early, in which case the debugger is now paused.
+    pub fn run_frame(&mut self, chip8: &mut Chip8) -> Option<Stop> {
+        self.observe(chip8);
+        for _ in 0..self.instructions_per_frame {
+            if self.mode == Mode::Paused {
+                return None;
+            }
+            let mut stop = if self.checked_at == Some(chip8.pc) {
+                None
+            } else {
+                self.check_before(chip8, self.resuming)
+            };
+            stop = stop.or(match self.mode {
+                Mode::StepOver { pc, sp } if chip8.pc == pc && chip8.sp == sp => Some(Stop::Step),
+                Mode::StepOut { sp } if chip8.sp < sp => Some(Stop::Step),
+                _ => None,
+            });
+            if stop.is_none() {
+                self.resuming = false;
+                stop = self.step(chip8);
+            }
+            if stop.is_some() {
+                self.pause();
+                return stop;
+            }
+        }
+        None
+    }
+
+    /// Executes one line typed by the user and returns what to print.
+    pub fn execute(&mut self, chip8: &mut Chip8, line: &str) -> String {
+        let line = if line.trim().is_empty() {
+            self.last_command.clone()
+        } else {
+            self.last_command = line.trim().to_string();
+            self.last_command.clone()
+        };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        //Everything after the command word, for commands taking expressions
+        let rest = line.split_once(char::is_whitespace).map_or("", |(_, rest)| rest.trim());
+        let result = match words.as_slice() {
+            [] => Ok(String::new()),
+            ["help"] | ["h"] | ["?"] => Ok(HELP.to_string()),
+            ["step"] | ["s"] => self.step_command(chip8, 1),
+            ["step", count] | ["s", count] => parse_number(count).and_then(|count| self.step_command(chip8, count)),
+            ["next"] | ["n"] => self.next_command(chip8),
+            ["finish"] | ["f"] => self.finish_command(chip8),
+            ["continue"] | ["c"] => {
+                self.resume();
+                Ok(String::new())
+            }
+            ["pause"] => {
+                self.pause();
+                Ok(String::new())
+            }
+            ["break", _, ..] | ["b", _, ..] => self.break_command(rest),
+            ["watch", _, ..] => self.watch_command(rest),
+            ["log", _, _, ..] => self.log_command(rest),
+            ["enable", id] => parse_number(id).and_then(|id| self.enable_command(id, true)),
+            ["disable", id] => parse_number(id).and_then(|id| self.enable_command(id, false)),
+            ["delete", id] | ["d", id] => parse_number(id).and_then(|id| {
+                if self.remove(id) {
+                    Ok(format!("Deleted {}", id))
+                } else {
+                    Err(format!("Nothing numbered {}", id))
+                }
+            }),
+            ["breakpoints"] | ["i"] => Ok(self.breakpoints_listing()),
+            ["registers"] | ["r"] => Ok(registers(chip8)),
+            ["set", name, value] => parse_number(value).and_then(|value| set_register(chip8, name, value)),
+            ["x", address] => parse_number(address).and_then(|address| memory_dump(chip8, address, 64)),
+            ["x", address, length] => parse_number(address)
+                .and_then(|address| parse_number(length).and_then(|length| memory_dump(chip8, address, length))),
+            ["disassemble"] => Ok(self.disassembly(chip8, chip8.pc.saturating_sub(8), 10)),
+            ["disassemble", address] => parse_number(address).map(|address| self.disassembly(chip8, address, 10)),
+            ["backtrace"] | ["bt"] => Ok(backtrace(chip8)),
+            _ => Err(format!("Unknown command \"{}\", try help", line)),
+        };
+        result.unwrap_or_else(|error| error)
+    }
+
+    /// Executes one instruction, ticking the timers every frame's worth.
+    /// The logpoints at pc run first unless they already have, and the
+    /// memory the instruction touches is checked against the watchpoints.
+    fn step(&mut self, chip8: &mut Chip8) -> Option<Stop> {
+        self.observe(chip8);
+        if self.checked_at != Some(chip8.pc) {
+            if let Some(stop) = self.check_before(chip8, true) {
+                return Some(stop);
+            }
+        }
+        self.watches.borrow_mut().caught.clear();
+        if let Err(error) = chip8.step() {
+            return Some(Stop::Error(error));
+        }
+        self.checked_at = None;
+        self.steps_since_tick += 1;
+        if self.steps_since_tick == self.instructions_per_frame {
+            self.steps_since_tick = 0;
+            chip8.tick_timers();
+        }
+        self.check_watches(chip8)
+    }
+
+    /// Executes the next instruction and pauses. Returns why it stopped if
+    /// that was anything other than finishing the step.
+    pub fn step_instruction(&mut self, chip8: &mut Chip8) -> Option<Stop> {
+        self.pause();
+        self.step(chip8)
+    }
+
+    /// Steps over a subroutine call, leaving [`Debugger::run_frame`] to run
+    /// until it returns. Anything else is a single step. Returns why it
+    /// stopped if the call itself did not run cleanly, in which case the
+    /// debugger is paused.
+    pub fn step_over(&mut self, chip8: &mut Chip8) -> Option<Stop> {
+        match decode_at(chip8, chip8.pc) {
+            Some((Instruction::Call { .. }, size)) => {
+                self.run(Mode::StepOver {
+                    pc: chip8.pc + size,
+                    sp: chip8.sp,
+                });
+                //Get past the call itself so the return address is pushed
+                let stop = self.step(chip8);
+                if stop.is_some() {
+                    self.pause();
+                }
+                stop
+            }
+            _ => self.step_instruction(chip8),
+        }
+    }
+
+    /// Leaves [`Debugger::run_frame`] to run until the current subroutine
+    /// returns. Returns false outside a subroutine.
+    pub fn step_out(&mut self, chip8: &Chip8) -> bool {
+        if chip8.sp == 0 {
+            return false;
+        }
+        self.run(Mode::StepOut { sp: chip8.sp });
+        true
+    }
+
+    fn step_command(&mut self, chip8: &mut Chip8, count: usize) -> Result<String, String> {
+        self.pause();
+        for _ in 0..count {
+            if let Some(stop) = self.step_instruction(chip8) {
+                return Ok(stop.to_string());
+            }
+        }
+        Ok(String::new())
+    }
+
+    fn next_command(&mut self, chip8: &mut Chip8) -> Result<String, String> {
+        Ok(self.step_over(chip8).map_or_else(String::new, |stop| stop.to_string()))
+    }
+
+    fn finish_command(&mut self, chip8: &mut Chip8) -> Result<String, String> {
+        if !self.step_out(chip8) {
+            return Err("Not in a subroutine".to_string());
+        }
+        Ok(String::new())
+    }
+
+    /// `break ADDR`, `break ADDR if EXPR` or `break if EXPR`.
+    fn break_command(&mut self, arguments: &str) -> Result<String, String> {
+        let (address, condition) = split_condition(arguments)?;
+        if address.is_empty() {
+            let condition = condition.ok_or("Expected an address or a condition")?;
+            let text = condition.to_string();
+            let id = self.add_condition(condition);
+            return Ok(format!("Breakpoint {} when {}", id, text));
+        }
+        let address = parse_number(address)?;
+        let id = self.add_breakpoint(address, condition);
+        Ok(format!("Breakpoint {} at 0x{:03X}", id, address))
+    }
+
+    /// `watch [read|write|access] ADDR [LEN] [if EXPR]`.
+    fn watch_command(&mut self, arguments: &str) -> Result<String, String> {
+        let (arguments, condition) = split_condition(arguments)?;
+        let mut words: Vec<&str> = arguments.split_whitespace().collect();
+        let watch = match words.first() {
+            Some(&"read") => Some(Watch::Read),
+            Some(&"write") => Some(Watch::Write),
+            Some(&"access") => Some(Watch::Access),
+            _ => None,
+        };
+        if watch.is_some() {
+            words.remove(0);
+        }
+        let (start, length) = match words.as_slice() {
+            [start] => (parse_number(start)?, 1),
+            [start, length] => (parse_number(start)?, parse_number(length)?),
+            _ => return Err("Usage: watch [read|write|access] ADDR [LEN] [if EXPR]".to_string()),
+        };
+        if length == 0 {
+            return Err("Cannot watch nothing".to_string());
+        }
+        let end = start
+            .checked_add(length)
+            .ok_or_else(|| format!("0x{:X} bytes from 0x{:03X} is past the end of memory", length, start))?;
+        let id = self.add_watchpoint(start..end, watch.unwrap_or(Watch::Write), condition);
+        if length == 1 {
+            Ok(format!("Watchpoint {} on 0x{:03X}", id, start))
+        } else {
+            Ok(format!("Watchpoint {} on 0x{:03X}-0x{:03X}", id, start, end - 1))
+        }
+    }
+
+    /// `log ADDR MESSAGE` or `log ADDR MESSAGE if EXPR`. An `if` that isn't
+    /// followed by an expression is part of the message.
+    fn log_command(&mut self, arguments: &str) -> Result<String, String> {
+        let (address, message) = arguments
+            .split_once(char::is_whitespace)
+            .ok_or("Usage: log ADDR MESSAGE [if EXPR]")?;
+        let address = parse_number(address)?;
+        let (message, condition) = match message.rsplit_once(" if ") {
+            Some((text, condition)) => match Expression::parse(condition) {
+                Ok(condition) => (text, Some(condition)),
+                Err(_) => (message, None),
+            },
+            None => (message, None),
+        };
+        let message = Template::parse(message.trim()).map_err(|error| error.to_string())?;
+        let id = self.add_logpoint(address, message, condition);
+        Ok(format!("Logpoint {} at 0x{:03X}", id, address))
+    }
+
+    fn enable_command(&mut self, id: usize, enabled: bool) -> Result<String, String> {
+        if !self.set_enabled(id, enabled) {
+            return Err(format!("Nothing numbered {}", id));
+        }
+        Ok(format!("{} {}", if enabled { "Enabled" } else { "Disabled" }, id))
+    }
+
+    fn breakpoints_listing(&self) -> String {
+        if self.points.is_empty() {
+            return "No breakpoints, watchpoints or logpoints".to_string();
+        }
+        let lines: Vec<String> = self.points.iter().map(Point::to_string).collect();
+        lines.join("\n")
+    }
+
+    fn disassembly(&self, chip8: &Chip8, start: usize, lines: usize) -> String {
+        let mut out = Vec::new();
+        let mut address = start;
+        while out.len() < lines && address.saturating_add(1) < chip8.memory.len() {
+            out.push(self.disassemble_line(chip8, address));
+            address += decode_at(chip8, address).map_or(2, |(_, size)| size);
+        }
+        out.join("\n")
+    }
+
+    /// One line of disassembly, marked with `>` at pc and `*` on a
+    /// breakpoint.
+    fn disassemble_line(&self, chip8: &Chip8, address: usize) -> String {
+        let marker = if address == chip8.pc { '>' } else { ' ' };
+        let breakpoint = if self.is_breakpoint(address) { '*' } else { ' ' };
+        if address.saturating_add(1) >= chip8.memory.len() {
+            return format!("{}{} 0x{:03X}: out of memory", marker, breakpoint, address);
+        }
+        let text = match decode_at(chip8, address) {
+            Some((instruction, _)) => instruction.to_string(),
+            None => "???".to_string(),
+        };
+        format!("{}{} 0x{:03X}: {:04X}  {}", marker, breakpoint, address, word_at(chip8, address), text)
+    }
+}
+
+fn parse_number(token: &str) -> Result<usize, String> {
+    let parsed = match token.strip_prefix("0x").or_else(|| token.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => token.parse(),
+    };
+    parsed.map_err(|_| format!("\"{}\" is not a number", token))
+}
+
+/// Splits `ARGS if EXPR` into the arguments and the parsed condition.
+fn split_condition(arguments: &str) -> Result<(&str, Option<Expression>), String> {
+    let split = if let Some(condition) = arguments.strip_prefix("if ") {
+        Some(("", condition))
+    } else {
+        arguments.split_once(" if ")
+    };
+    match split {
+        Some((arguments, condition)) => Expression::parse(condition)
+            .map(|condition| (arguments.trim(), Some(condition)))
+            .map_err(|error| format!("Bad condition: {}", error)),
+        None => Ok((arguments, None)),
+    }
+}
+
+fn word_at(chip8: &Chip8, address: usize) -> u16 {
+    u16::from(chip8.memory.peek(address)) << 8 | u16::from(chip8.memory.peek(address + 1))
+}
+
+/// Decodes the instruction at `address` and its size in bytes.
+fn decode_at(chip8: &Chip8, address: usize) -> Option<(Instruction, usize)> {
+    if address.saturating_add(1) >= chip8.memory.len() {
+        return None;
+    }
+    let opcode = word_at(chip8, address);
+    let decoded = if opcode == 0xF000 && address + 3 < chip8.memory.len() {
+        Instruction::decode_pair(opcode, word_at(chip8, address + 2))
+    } else {
+        Instruction::decode(opcode)
+    };
+    decoded.ok().map(|instruction| (instruction, instruction.size()))
+}
+
+fn registers(chip8: &Chip8) -> String {
+    let mut out = String::new();
+    for (i, value) in chip8.V.iter().enumerate() {
+        let separator = if i % 8 == 7 { '\n' } else { ' ' };
+        let _ = write!(out, "V{:X}={:02X}{}", i, value, separator);
+    }
+    let _ = write!(
+        out,
+        "I={:04X} PC={:03X} SP={:X} DT={:02X} ST={:02X}",
+        chip8.I, chip8.pc, chip8.sp, chip8.delay, chip8.sound
+    );
+    out
+}
+
+fn set_register(chip8: &mut Chip8, name: &str, value: usize) -> Result<String, String> {
+    let name = name.to_uppercase();
+    let too_big = || format!("0x{:X} does not fit in {}", value, name);
+    match name.as_str() {
+        "I" => chip8.I = if value <= 0xFFFF { value as u16 } else { return Err(too_big()) },
+        "PC" if value < chip8.memory.len() => chip8.pc = value,
+        "SP" if value <= chip8.stack.len() => chip8.sp = value,
+        "DT" if value <= 0xFF => chip8.delay = value as u8,
+        "ST" if value <= 0xFF => chip8.sound = value as u8,
+        "PC" | "SP" | "DT" | "ST" => return Err(too_big()),
+        _ => {
+            let register = name
+                .strip_prefix('V')
+                .filter(|digit| digit.len() == 1)
+                .and_then(|digit| usize::from_str_radix(digit, 16).ok())
+                .ok_or_else(|| format!("Unknown register {}", name))?;
+            if value > 0xFF {
+                return Err(too_big());
+            }
+            chip8.V[register] = value as u8;
+        }
+    }
+    Ok(format!("{} = 0x{:X}", name, value))
+}
+
+fn memory_dump(chip8: &Chip8, address: usize, length: usize) -> Result<String, String> {
+    if address >= chip8.memory.len() {
+        return Err(format!("0x{:03X} is outside memory", address));
+    }
+    if length == 0 {
+        return Err("Cannot dump nothing".to_string());
+    }
+    let end = address.saturating_add(length).min(chip8.memory.len());
+    let lines: Vec<String> = (address..end)
+        .step_by(16)
+        .map(|line| {
+            let bytes: Vec<String> = (line..(line + 16).min(end))
+                .map(|address| format!("{:02X}", chip8.memory.peek(address)))
+                .collect();
+            format!("0x{:03X}: {}", line, bytes.join(" "))
+        })
+        .collect();
+    Ok(lines.join("\n"))
+}
+
+/// The call stack, innermost first. Each return address follows the call
+/// that pushed it.
+fn backtrace(chip8: &Chip8) -> String {
+    let mut lines = vec![format!("#0 0x{:03X}", chip8.pc)];
+    for (depth, &return_address) in chip8.stack[..chip8.sp].iter().rev().enumerate() {
+        lines.push(format!(
+            "#{} 0x{:03X} called from 0x{:03X}",
+            depth + 1,
+            return_address,
+            usize::from(return_address).saturating_sub(2)
+        ));
+    }
+    lines.join("\n")
+}
@@ -0,0 +1,291 @@
+use chip8::{Chip8, Quirks};
+use chip8::Access;
+use debugger::{Debugger, Stop};
+
+//Call a subroutine at 0x208 that adds to V1 twice, then loop back to the start
+fn init_cpu() -> Chip8 {
+    let mut cpu = Chip8::new(Quirks::vip());
+    cpu.load_program(vec!(0x60, 0x01, 0x22, 0x08, 0x62, 0x02, 0x12, 0x00, 0x71, 0x01, 0x71, 0x01, 0x00, 0xEE));
+    cpu
+}
+
+fn run_until_stop(debugger: &mut Debugger, cpu: &mut Chip8) -> Stop {
+    for _ in 0..100 {
+        if let Some(stop) = debugger.run_frame(cpu) {
+            return stop;
+        }
+    }
+    panic!("The debugger never stopped.");
+}
+
+#[test]
+fn test_breakpoints_stop_before_executing() {
+    let mut cpu = init_cpu();
+    let mut debugger = Debugger::new(10);
+    debugger.execute(&mut cpu, "break 0x20A");
+    assert_eq!(run_until_stop(&mut debugger, &mut cpu), Stop::Breakpoint(0x20A));
+    assert!(debugger.is_paused());
+    assert_eq!(cpu.V[1], 1);
+
+    //Continuing runs the breakpoint's instruction before stopping on it again
+    debugger.execute(&mut cpu, "c");
+    assert_eq!(run_until_stop(&mut debugger, &mut cpu), Stop::Breakpoint(0x20A));
+    assert_eq!(cpu.V[1], 3);
+
+    assert_eq!(debugger.execute(&mut cpu, "d 1"), "Deleted 1");
+    assert_eq!(debugger.execute(&mut cpu, "d 1"), "Nothing numbered 1");
+    assert_eq!(debugger.execute(&mut cpu, "breakpoints"), "No breakpoints, watchpoints or logpoints");
+}
+
+#[test]
+fn test_step_over_and_out() {
+    let mut cpu = init_cpu();
+    let mut debugger = Debugger::new(10);
+    debugger.pause();
+    debugger.execute(&mut cpu, "step");
+    debugger.execute(&mut cpu, "next");
+    assert_eq!(run_until_stop(&mut debugger, &mut cpu), Stop::Step);
+    assert_eq!(cpu.pc, 0x204);
+    assert_eq!(cpu.V[1], 2);
+
+    debugger.execute(&mut cpu, "s 5");
+    assert_eq!(cpu.pc, 0x20A);
+    debugger.execute(&mut cpu, "finish");
+    assert_eq!(run_until_stop(&mut debugger, &mut cpu), Stop::Step);
+    assert_eq!(cpu.pc, 0x204);
+    assert_eq!(cpu.sp, 0);
+}
+
+#[test]
+fn test_empty_line_repeats_last_command() {
+    let mut cpu = init_cpu();
+    let mut debugger = Debugger::new(10);
+    debugger.execute(&mut cpu, "s");
+    debugger.execute(&mut cpu, "");
+    assert_eq!(cpu.pc, 0x208);
+}
+
+#[test]
+fn test_registers_can_be_changed() {
+    let mut cpu = init_cpu();
+    let mut debugger = Debugger::new(10);
+    assert_eq!(debugger.execute(&mut cpu, "set vA 0x42"), "VA = 0x42");
+    assert_eq!(debugger.execute(&mut cpu, "set DT 30"), "DT = 0x1E");
+    assert_eq!(debugger.execute(&mut cpu, "set V0 256"), "0x100 does not fit in V0");
+    assert_eq!(debugger.execute(&mut cpu, "set VG 1"), "Unknown register VG");
+    assert_eq!(cpu.V[0xA], 0x42);
+    assert_eq!(cpu.delay, 30);
+
+    let registers = debugger.execute(&mut cpu, "r");
+    assert!(registers.contains("VA=42"));
+    assert!(registers.ends_with("I=0000 PC=200 SP=0 DT=1E ST=00"));
+}
+
+#[test]
+fn test_inspection() {
+    let mut cpu = init_cpu();
+    let mut debugger = Debugger::new(10);
+    debugger.execute(&mut cpu, "b 0x204");
+    assert_eq!(debugger.execute(&mut cpu, "x 0x200 3"), "0x200: 60 01 22");
+    assert_eq!(
+        debugger.execute(&mut cpu, "disassemble 0x200").lines().take(3).collect::<Vec<_>>(),
+        vec!(">  0x200: 6001  LD V0, 0x01", "   0x202: 2208  CALL 0x208", " * 0x204: 6202  LD V2, 0x02")
+    );
+
+    debugger.execute(&mut cpu, "s 2");
+    assert_eq!(debugger.execute(&mut cpu, "bt"), "#0 0x208\n#1 0x204 called from 0x202");
+    assert!(debugger.execute(&mut cpu, "frobnicate").starts_with("Unknown command"));
+}
+
+#[test]
+fn test_timers_tick_while_stepping() {
+    let mut cpu = init_cpu();
+    cpu.delay = 5;
+    let mut debugger = Debugger::new(4);
+    debugger.execute(&mut cpu, "s 8");
+    assert_eq!(cpu.delay, 3);
+}
+
+#[test]
+fn test_conditional_breakpoints() {
+    let mut cpu = init_cpu();
+    let mut debugger = Debugger::new(10);
+    assert_eq!(
+        debugger.execute(&mut cpu, "b 0x20A if V1 == 3 && V0 == 1"),
+        "Breakpoint 1 at 0x20A"
+    );
+    assert_eq!(run_until_stop(&mut debugger, &mut cpu), Stop::Breakpoint(0x20A));
+    assert_eq!(cpu.V[1], 3);
+    assert_eq!(debugger.hits(1), Some(1));
+
+    //A breakpoint with only a condition stops after the instruction that makes it true
+    assert_eq!(
+        debugger.execute(&mut cpu, "break if V1 >= 6"),
+        "Breakpoint 2 when V1 >= 6"
+    );
+    debugger.execute(&mut cpu, "disable 1");
+    debugger.execute(&mut cpu, "c");
+    assert_eq!(run_until_stop(&mut debugger, &mut cpu), Stop::Condition(2));
+    assert_eq!((cpu.V[1], cpu.pc), (6, 0x20C));
+
+    //It doesn't fire again while the condition stays true
+    debugger.execute(&mut cpu, "enable 1");
+    debugger.execute(&mut cpu, "set V1 2");
+    debugger.execute(&mut cpu, "c");
+    assert_eq!(run_until_stop(&mut debugger, &mut cpu), Stop::Breakpoint(0x20A));
+    assert_eq!(debugger.hits(2), Some(1));
+    assert_eq!(
+        debugger.execute(&mut cpu, "breakpoints"),
+        "1: breakpoint at 0x20A if V1 == 3 && V0 == 1, 2 hits\n2: breakpoint if V1 >= 6, 1 hit"
+    );
+    assert!(debugger
+        .execute(&mut cpu, "b 0x200 if V1 ==")
+        .starts_with("Bad condition"));
+}
+
+#[test]
+fn test_watchpoints() {
+    //Store V0-V1 at 0x300 over and over, then read them back
+    let mut cpu = Chip8::new(Quirks::vip());
+    cpu.load_program(vec![
+        0x60, 0x07, 0xA3, 0x00, 0xF1, 0x55, 0xA3, 0x00, 0xF1, 0x65, 0x70, 0x01, 0x12, 0x02,
+    ]);
+    let mut debugger = Debugger::new(10);
+    assert_eq!(debugger.execute(&mut cpu, "watch 0x301"), "Watchpoint 1 on 0x301");
+    assert_eq!(
+        run_until_stop(&mut debugger, &mut cpu),
+        Stop::Watchpoint {
+            id: 1,
+            address: 0x301,
+            value: 0,
+            access: Access::Write
+        }
+    );
+    assert_eq!(cpu.pc, 0x206);
+
+    debugger.execute(&mut cpu, "d 1");
+    assert_eq!(
+        debugger.execute(&mut cpu, "watch read 0x300 2 if [0x300] == 8"),
+        "Watchpoint 2 on 0x300-0x301"
+    );
+    debugger.execute(&mut cpu, "c");
+    assert_eq!(
+        run_until_stop(&mut debugger, &mut cpu),
+        Stop::Watchpoint {
+            id: 2,
+            address: 0x300,
+            value: 8,
+            access: Access::Read
+        }
+    );
+    assert_eq!(cpu.pc, 0x20A);
+    assert_eq!(debugger.hits(2), Some(2));
+}
+
+#[test]
+fn test_watchpoints_survive_loading_state() {
+    let mut cpu = Chip8::new(Quirks::vip());
+    cpu.load_program(vec![0xA3, 0x00, 0xF0, 0x55, 0x12, 0x00]);
+    let state = cpu.save_state();
+    let mut debugger = Debugger::new(10);
+    debugger.execute(&mut cpu, "watch access 0x300");
+    assert!(debugger.run_frame(&mut cpu).is_some());
+    cpu.load_state(&state).unwrap();
+    debugger.execute(&mut cpu, "c");
+    assert!(debugger.run_frame(&mut cpu).is_some());
+    assert_eq!(debugger.hits(1), Some(2));
+}
+
+#[test]
+fn test_logpoints_print_without_stopping() {
+    let mut cpu = init_cpu();
+    //Two trips around the loop
+    let mut debugger = Debugger::new(14);
+    assert_eq!(
+        debugger.execute(&mut cpu, "log 0x20A V1 is {V1}, next is {[PC]:x}"),
+        "Logpoint 1 at 0x20A"
+    );
+    assert_eq!(debugger.run_frame(&mut cpu), None);
+    assert_eq!(
+        debugger.take_log(),
+        vec!("V1 is 1, next is 0x71", "V1 is 3, next is 0x71")
+    );
+    assert!(debugger.take_log().is_empty());
+    assert_eq!(debugger.hits(1), Some(2));
+    assert!(debugger.execute(&mut cpu, "log 0x20A {V1").starts_with("unclosed {"));
+}
+
+#[test]
+fn test_logpoints_with_conditions() {
+    let mut cpu = init_cpu();
+    let mut debugger = Debugger::new(14);
+    debugger.execute(&mut cpu, "log 0x20A V1 is {V1} if V1 > 2");
+    debugger.execute(&mut cpu, "log 0x20C returning if you like");
+    assert_eq!(debugger.run_frame(&mut cpu), None);
+    assert_eq!(
+        debugger.take_log(),
+        vec!("returning if you like", "V1 is 3", "returning if you like")
+    );
+    assert_eq!(
+        debugger.execute(&mut cpu, "breakpoints"),
+        "1: logpoint at 0x20A: V1 is {V1} if V1 > 2, 1 hit\n2: logpoint at 0x20C: returning if you like, 2 hits"
+    );
+}
+
+#[test]
+fn test_bad_conditions_stop() {
+    let mut cpu = init_cpu();
+    let mut debugger = Debugger::new(10);
+    debugger.execute(&mut cpu, "break 0x204 if [0x1000] == 0");
+    let stop = run_until_stop(&mut debugger, &mut cpu);
+    assert_eq!(
+        stop.to_string(),
+        "Could not check the condition of 1: [0x1000] is outside memory"
+    );
+}
+
+#[test]
+fn test_points_fire_while_stepping() {
+    let mut cpu = init_cpu();
+    let mut debugger = Debugger::new(10);
+    debugger.execute(&mut cpu, "log 0x20A V1 is {V1}");
+    debugger.execute(&mut cpu, "s 4");
+    assert_eq!(debugger.take_log(), vec!("V1 is 1"));
+
+    //Stepping on from a breakpoint doesn't print its logpoint again
+    debugger.execute(&mut cpu, "b 0x20A");
+    debugger.execute(&mut cpu, "c");
+    assert_eq!(run_until_stop(&mut debugger, &mut cpu), Stop::Breakpoint(0x20A));
+    debugger.execute(&mut cpu, "s");
+    assert_eq!(debugger.take_log(), vec!("V1 is 3"));
+
+    let mut cpu = Chip8::new(Quirks::vip());
+    cpu.load_program(vec![0xA3, 0x00, 0xF1, 0x55, 0x12, 0x00]);
+    let mut debugger = Debugger::new(10);
+    debugger.execute(&mut cpu, "watch 0x301");
+    assert_eq!(debugger.execute(&mut cpu, "s 5"), "Watchpoint 1: write of 0x00 at 0x301");
+    assert_eq!(cpu.pc, 0x204);
+}
+
+#[test]
+fn test_step_over_a_failing_call() {
+    let mut cpu = Chip8::new(Quirks::vip());
+    cpu.load_program(vec![0x22, 0x00]);
+    let mut debugger = Debugger::new(10);
+    debugger.execute(&mut cpu, "s 16");
+    assert!(debugger.execute(&mut cpu, "next").starts_with("Execution halted"));
+    assert!(debugger.is_paused());
+}
+
+#[test]
+fn test_lengths_past_the_end_of_memory() {
+    let mut cpu = init_cpu();
+    let mut debugger = Debugger::new(10);
+    assert!(debugger.execute(&mut cpu, "x 0xFF0 0xFFFFFFFFFFFFFFFF").starts_with("0xFF0: 00"));
+    assert_eq!(debugger.execute(&mut cpu, "x 0x200 0"), "Cannot dump nothing");
+    assert_eq!(debugger.execute(&mut cpu, "watch 0 0"), "Cannot watch nothing");
+    assert!(debugger
+        .execute(&mut cpu, "watch 0x10 0xFFFFFFFFFFFFFFFF")
+        .ends_with("past the end of memory"));
+    assert_eq!(debugger.execute(&mut cpu, "breakpoints"), "No breakpoints, watchpoints or logpoints");
+}
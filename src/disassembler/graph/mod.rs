@@ -0,0 +1,320 @@
+#[cfg(test)]
+mod tests;
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::String;
+use alloc::vec::Vec;
+use chip8::opcode::Instruction;
+use core::fmt::Write;
+use disassembler::{Disassembly, Item, ORIGIN};
+
+/// Why control can pass from one block to another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    /// Running off the end of the block, or coming back from a call.
+    Next,
+    Jump,
+    /// Taking a `3XNN`-style skip.
+    Skip,
+    Call,
+    /// From a `00EE` back to where the subroutine was called from.
+    Return,
+    /// The `BNNN` target for V0 = 0. Others are unknown.
+    Computed,
+}
+
+/// A run of instructions only entered at the top and only left at the
+/// bottom.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub start: usize,
+    pub instructions: Vec<(usize, Instruction)>,
+    /// Successors by block start, including calls and returns.
+    pub edges: Vec<(usize, Edge)>,
+}
+
+impl Block {
+    fn last(&self) -> Instruction {
+        self.instructions[self.instructions.len() - 1].1
+    }
+}
+
+/// The basic blocks of a [`Disassembly`], with subroutines found from the
+/// entry point and `2NNN` targets.
+///
+/// DOT output draws jumps solid, skips labelled, calls dashed, returns
+/// dotted and computed jumps in red, since their other targets are missing.
+#[derive(Debug, Clone)]
+pub struct Graph<'a> {
+    disassembly: &'a Disassembly,
+    pub blocks: BTreeMap<usize, Block>,
+    /// Entry points, the program's first.
+    pub subroutines: Vec<usize>,
+}
+
+impl<'a> Graph<'a> {
+    pub fn new(disassembly: &'a Disassembly) -> Self {
+        let code: BTreeMap<usize, Instruction> = disassembly
+            .items
+            .iter()
+            .filter_map(|item| match *item {
+                Item::Code { address, instruction } => Some((address, instruction)),
+                Item::Data { .. } => None,
+            })
+            .collect();
+
+        //Successors of each instruction, and where blocks have to start
+        let mut successors: BTreeMap<usize, Vec<(usize, Edge)>> = BTreeMap::new();
+        let mut leaders = BTreeSet::new();
+        let mut called = BTreeSet::new();
+        let mut previous_ended = false;
+        for (&address, &instruction) in &code {
+            if previous_ended || !code.contains_key(&(address.wrapping_sub(2))) && !is_long_before(&code, address) {
+                leaders.insert(address);
+            }
+            let next = address + instruction.size();
+            let mut out = Vec::new();
+            let mut ends = true;
+            match instruction {
+                Instruction::Jump { nnn } => out.push((usize::from(nnn), Edge::Jump)),
+                Instruction::Call { nnn } => {
+                    out.push((usize::from(nnn), Edge::Call));
+                    out.push((next, Edge::Next));
+                    called.insert(usize::from(nnn));
+                }
+                Instruction::SkipEqImm { .. }
+                | Instruction::SkipNeImm { .. }
+                | Instruction::SkipEqReg { .. }
+                | Instruction::SkipNeReg { .. }
+                | Instruction::SkipKey { .. }
+                | Instruction::SkipNotKey { .. } => {
+                    out.push((next, Edge::Next));
+                    let skipped = code.get(&next).map_or(2, |instruction| instruction.size());
+                    out.push((next + skipped, Edge::Skip));
+                }
+                Instruction::JumpV0 { nnn } => out.push((usize::from(nnn), Edge::Computed)),
+                Instruction::Return | Instruction::Exit => {}
+                _ => {
+                    out.push((next, Edge::Next));
+                    ends = false;
+                }
+            }
+            out.retain(|&(target, _)| code.contains_key(&target));
+            for &(target, edge) in &out {
+                if edge != Edge::Next || ends {
+                    leaders.insert(target);
+                }
+            }
+            previous_ended = ends;
+            successors.insert(address, out);
+        }
+        let subroutines: Vec<usize> = Some(ORIGIN)
+            .into_iter()
+            .chain(called.into_iter().filter(|&entry| entry != ORIGIN))
+            .filter(|entry| code.contains_key(entry))
+            .collect();
+
+        let mut blocks = BTreeMap::new();
+        let mut current: Option<Block> = None;
+        for (&address, &instruction) in &code {
+            if leaders.contains(&address) {
+                if let Some(block) = current.take() {
+                    blocks.insert(block.start, block);
+                }
+            }
+            let block = current.get_or_insert_with(|| Block {
+                start: address,
+                instructions: Vec::new(),
+                edges: Vec::new(),
+            });
+            block.instructions.push((address, instruction));
+            block.edges = successors.remove(&address).unwrap_or_default();
+        }
+        if let Some(block) = current {
+            blocks.insert(block.start, block);
+        }
+
+        let mut graph = Graph {
+            disassembly,
+            blocks,
+            subroutines,
+        };
+        graph.add_returns();
+        graph
+    }
+
+    /// Links each `00EE` back to the blocks after the calls to its
+    /// subroutine.
+    fn add_returns(&mut self) {
+        let mut returns = Vec::new();
+        for &entry in &self.subroutines {
+            let sites: Vec<usize> = self
+                .blocks
+                .values()
+                .filter(|block| block.last() == Instruction::Call { nnn: entry as u16 })
+                .filter_map(|block| block.edges.iter().find(|edge| edge.1 == Edge::Next).map(|edge| edge.0))
+                .collect();
+            for start in self.members(entry) {
+                if self.blocks[&start].last() == Instruction::Return {
+                    returns.extend(sites.iter().map(|&site| (start, site)));
+                }
+            }
+        }
+        for (start, site) in returns {
+            let edges = &mut self.blocks.get_mut(&start).unwrap().edges;
+            if !edges.contains(&(site, Edge::Return)) {
+                edges.push((site, Edge::Return));
+            }
+        }
+    }
+
+    /// The blocks of the subroutine at `entry`: everything reachable from
+    /// it without following calls or returns.
+    pub fn members(&self, entry: usize) -> Vec<usize> {
+        let mut seen = BTreeSet::new();
+        let mut pending = vec![entry];
+        while let Some(start) = pending.pop() {
+            let block = match self.blocks.get(&start) {
+                Some(block) if seen.insert(start) => block,
+                _ => continue,
+            };
+            for &(target, edge) in &block.edges {
+                if edge != Edge::Call && edge != Edge::Return {
+                    pending.push(target);
+                }
+            }
+        }
+        seen.into_iter().collect()
+    }
+
+    /// The whole program as one DOT graph, with each subroutine in a
+    /// cluster. Blocks shared between subroutines go in the first.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph program {\n    node [shape=box, fontname=\"monospace\"];\n");
+        let mut placed = BTreeSet::new();
+        for &entry in &self.subroutines {
+            let _ = writeln!(
+                out,
+                "    subgraph cluster_{:03X} {{\n        label=\"{}\";",
+                entry,
+                self.name(entry)
+            );
+            for start in self.members(entry) {
+                if placed.insert(start) {
+                    let _ = writeln!(out, "    {}", self.node(start));
+                }
+            }
+            out.push_str("    }\n");
+        }
+        for block in self.blocks.values() {
+            if placed.insert(block.start) {
+                let _ = writeln!(out, "{}", self.node(block.start));
+            }
+        }
+        for block in self.blocks.values() {
+            for &(target, edge) in &block.edges {
+                let _ = writeln!(out, "{}", self.edge(block.start, target, edge));
+            }
+        }
+        out.push('}');
+        out
+    }
+
+    /// One subroutine as a DOT graph. Calls go to a node standing in for
+    /// the callee and returns to a single exit node.
+    pub fn subroutine_to_dot(&self, entry: usize) -> Option<String> {
+        if !self.blocks.contains_key(&entry) {
+            return None;
+        }
+        let mut out = format!(
+            "digraph {} {{\n    node [shape=box, fontname=\"monospace\"];\n",
+            self.name(entry)
+        );
+        let members = self.members(entry);
+        let mut callees = BTreeSet::new();
+        let mut returns = false;
+        for &start in &members {
+            let _ = writeln!(out, "{}", self.node(start));
+            for &(target, edge) in &self.blocks[&start].edges {
+                match edge {
+                    Edge::Call => {
+                        callees.insert(target);
+                        let _ = writeln!(out, "    b{:03X} -> call_{:03X} [style=dashed];", start, target);
+                    }
+                    Edge::Return => {}
+                    _ => {
+                        let _ = writeln!(out, "{}", self.edge(start, target, edge));
+                    }
+                }
+            }
+            if self.blocks[&start].last() == Instruction::Return {
+                returns = true;
+                let _ = writeln!(out, "    b{:03X} -> exit [style=dotted];", start);
+            }
+        }
+        for callee in callees {
+            let _ = writeln!(
+                out,
+                "    call_{:03X} [label=\"{}\", shape=ellipse];",
+                callee,
+                self.name(callee)
+            );
+        }
+        if returns {
+            out.push_str("    exit [label=\"RET\", shape=ellipse];\n");
+        }
+        out.push('}');
+        Some(out)
+    }
+
+    fn name(&self, entry: usize) -> String {
+        match self.disassembly.labels.get(&entry) {
+            Some(label) => label.clone(),
+            None if entry == ORIGIN => String::from("main"),
+            None => format!("sub_{:03X}", entry),
+        }
+    }
+
+    fn node(&self, start: usize) -> String {
+        let block = &self.blocks[&start];
+        let mut label = String::new();
+        if let Some(name) = self.disassembly.labels.get(&start) {
+            let _ = write!(label, "{}:\\l", name);
+        }
+        for &(address, instruction) in &block.instructions {
+            let _ = write!(
+                label,
+                "0x{:03X}  {}\\l",
+                address,
+                escape(&self.disassembly.source(instruction))
+            );
+        }
+        let computed = block.edges.iter().any(|edge| edge.1 == Edge::Computed);
+        let color = if computed { ", color=red" } else { "" };
+        format!("    b{:03X} [label=\"{}\"{}];", start, label, color)
+    }
+
+    fn edge(&self, from: usize, to: usize, edge: Edge) -> String {
+        let style = match edge {
+            Edge::Next => "",
+            Edge::Jump => "",
+            Edge::Skip => " [label=\"skip\"]",
+            Edge::Call => " [style=dashed]",
+            Edge::Return => " [style=dotted]",
+            Edge::Computed => " [label=\"computed\", color=red]",
+        };
+        format!("    b{:03X} -> b{:03X}{};", from, to, style)
+    }
+}
+
+/// Whether `address` follows a four byte instruction.
+fn is_long_before(code: &BTreeMap<usize, Instruction>, address: usize) -> bool {
+    address >= 4
+        && code
+            .get(&(address - 4))
+            .is_some_and(|instruction| instruction.size() == 4)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
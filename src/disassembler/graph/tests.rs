@@ -0,0 +1,68 @@
+use chip8::Platform;
+use disassembler::graph::{Edge, Graph};
+use disassembler::Disassembler;
+
+//Loop calling 0x20A until it has counted V0 up to 1, then exit
+const PROGRAM: [u8; 14] = [
+    0x60, 0x00, 0x22, 0x0A, 0x30, 0x01, 0x12, 0x02, 0x00, 0xFD, 0x70, 0x01, 0x00, 0xEE,
+];
+
+#[test]
+fn test_blocks_and_edges() {
+    let disassembly = Disassembler::new(&PROGRAM, Platform::SuperChip).disassemble();
+    let graph = Graph::new(&disassembly);
+    let starts: Vec<usize> = graph.blocks.keys().cloned().collect();
+    assert_eq!(starts, vec!(0x200, 0x202, 0x204, 0x206, 0x208, 0x20A));
+    assert_eq!(graph.blocks[&0x200].edges, vec!((0x202, Edge::Next)));
+    assert_eq!(graph.blocks[&0x202].edges, vec!((0x20A, Edge::Call), (0x204, Edge::Next)));
+    assert_eq!(graph.blocks[&0x204].edges, vec!((0x206, Edge::Next), (0x208, Edge::Skip)));
+    assert_eq!(graph.blocks[&0x206].edges, vec!((0x202, Edge::Jump)));
+    assert!(graph.blocks[&0x208].edges.is_empty());
+    assert_eq!(graph.blocks[&0x20A].instructions.len(), 2);
+    assert_eq!(graph.blocks[&0x20A].edges, vec!((0x204, Edge::Return)));
+    assert_eq!(graph.subroutines, vec!(0x200, 0x20A));
+    assert_eq!(graph.members(0x200), vec!(0x200, 0x202, 0x204, 0x206, 0x208));
+}
+
+#[test]
+fn test_subroutine_dot() {
+    let disassembly = Disassembler::new(&PROGRAM, Platform::SuperChip).disassemble();
+    let graph = Graph::new(&disassembly);
+    assert_eq!(
+        graph.subroutine_to_dot(0x20A).unwrap(),
+        "digraph sub_20A {
+    node [shape=box, fontname=\"monospace\"];
+    b20A [label=\"sub_20A:\\l0x20A  ADD V0, 0x01\\l0x20C  RET\\l\"];
+    b20A -> exit [style=dotted];
+    exit [label=\"RET\", shape=ellipse];
+}"
+    );
+    let main = graph.subroutine_to_dot(0x200).unwrap();
+    assert!(main.starts_with("digraph main {"));
+    assert!(main.contains("\n    b202 -> call_20A [style=dashed];\n"));
+    assert!(main.contains("\n    call_20A [label=\"sub_20A\", shape=ellipse];\n"));
+    assert!(main.contains("\n    b206 -> b202;\n"));
+    assert_eq!(graph.subroutine_to_dot(0x300), None);
+}
+
+#[test]
+fn test_program_dot() {
+    let disassembly = Disassembler::new(&PROGRAM, Platform::SuperChip).disassemble();
+    let dot = Graph::new(&disassembly).to_dot();
+    assert!(dot.starts_with("digraph program {"));
+    assert!(dot.contains("    subgraph cluster_20A {\n        label=\"sub_20A\";\n        b20A [label="));
+    assert!(dot.contains("\n    b202 -> b20A [style=dashed];\n"));
+    assert!(dot.contains("\n    b204 -> b208 [label=\"skip\"];\n"));
+    assert!(dot.contains("\n    b20A -> b204 [style=dotted];\n"));
+}
+
+#[test]
+fn test_computed_jumps_are_flagged() {
+    let rom = [0xB2, 0x04, 0x00, 0x00, 0x00, 0xFD];
+    let disassembly = Disassembler::new(&rom, Platform::SuperChip).disassemble();
+    let graph = Graph::new(&disassembly);
+    assert_eq!(graph.blocks[&0x200].edges, vec!((0x204, Edge::Computed)));
+    let dot = graph.to_dot();
+    assert!(dot.contains("b200 [label=\"0x200  JP V0, label_204\\l\", color=red];"));
+    assert!(dot.contains("\n    b200 -> b204 [label=\"computed\", color=red];\n"));
+}
@@ -0,0 +1,298 @@
+//! A static disassembler that follows control flow to tell code from data.
+//!
+//! Starting from the entry point, it decodes instructions and follows
+//! `1NNN` jumps, `2NNN` calls and the conditional skips, stopping at `00EE`,
+//! `00FD` and anything that doesn't decode. Bytes never reached are data.
+//! Jump, call and `ANNN` targets get labels, and the listing re-assembles to
+//! the same ROM.
+
+#[cfg(test)]
+mod tests;
+
+pub mod graph;
+
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use chip8::coverage::Coverage;
+use chip8::opcode::Instruction;
+use chip8::Platform;
+use core::fmt::{self, Display, Formatter};
+
+/// Where ROMs are loaded.
+pub const ORIGIN: usize = 0x200;
+/// Bytes per `DB` line in the listing.
+const BYTES_PER_LINE: usize = 8;
+
+/// A run of bytes in the listing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Item {
+    Code { address: usize, instruction: Instruction },
+    Data { address: usize, bytes: Vec<u8> },
+}
+
+impl Item {
+    pub fn address(&self) -> usize {
+        match *self {
+            Item::Code { address, .. } | Item::Data { address, .. } => address,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        match *self {
+            Item::Code { instruction, .. } => instruction.size(),
+            Item::Data { ref bytes, .. } => bytes.len(),
+        }
+    }
+}
+
+/// Finds the code in a ROM. Add any entry points it can't find on its own,
+/// such as the targets of `BNNN` jump tables, before calling
+/// [`Disassembler::disassemble`].
+pub struct Disassembler<'a> {
+    rom: &'a [u8],
+    platform: Platform,
+    entries: Vec<usize>,
+}
+
+impl<'a> Disassembler<'a> {
+    pub fn new(rom: &'a [u8], platform: Platform) -> Self {
+        Disassembler {
+            rom,
+            platform,
+            entries: vec![ORIGIN],
+        }
+    }
+
+    pub fn add_entry(&mut self, address: usize) {
+        self.entries.push(address);
+    }
+
+    /// Adds every instruction `coverage` saw executed as an entry point, so
+    /// code only reached through computed jumps is found too.
+    pub fn add_coverage(&mut self, coverage: &Coverage) {
+        let end = ORIGIN + self.rom.len();
+        let mut address = ORIGIN;
+        while address < end {
+            //Take each run of executed bytes a word at a time
+            if coverage.is_code(address) && coverage.is_code(address + 1) {
+                self.entries.push(address);
+                address += 2;
+            } else {
+                address += 1;
+            }
+        }
+    }
+
+    pub fn disassemble(&self) -> Disassembly {
+        let mut code = BTreeMap::new();
+        //Bytes claimed by an instruction, so overlapping decodes are refused
+        let mut claimed = vec![false; self.rom.len()];
+        let mut targets = BTreeMap::new();
+        let mut computed_jumps = Vec::new();
+        let mut pending = self.entries.clone();
+        while let Some(start) = pending.pop() {
+            let mut address = start;
+            while let Some(instruction) = self.decode(address) {
+                let offset = address - ORIGIN;
+                if code.contains_key(&address) || claimed[offset..offset + instruction.size()].contains(&true) {
+                    break;
+                }
+                for byte in &mut claimed[offset..offset + instruction.size()] {
+                    *byte = true;
+                }
+                code.insert(address, instruction);
+                let next = address + instruction.size();
+                match instruction {
+                    Instruction::Jump { nnn } => {
+                        refer(&mut targets, usize::from(nnn), Target::Jump);
+                        pending.push(usize::from(nnn));
+                        break;
+                    }
+                    Instruction::Call { nnn } => {
+                        refer(&mut targets, usize::from(nnn), Target::Call);
+                        pending.push(usize::from(nnn));
+                    }
+                    Instruction::SkipEqImm { .. }
+                    | Instruction::SkipNeImm { .. }
+                    | Instruction::SkipEqReg { .. }
+                    | Instruction::SkipNeReg { .. }
+                    | Instruction::SkipKey { .. }
+                    | Instruction::SkipNotKey { .. } => {
+                        let skipped = self.decode(next).map_or(2, Instruction::size);
+                        pending.push(next + skipped);
+                    }
+                    Instruction::JumpV0 { nnn } => {
+                        //Only the target for V0 = 0 is known
+                        refer(&mut targets, usize::from(nnn), Target::Jump);
+                        pending.push(usize::from(nnn));
+                        computed_jumps.push(address);
+                        break;
+                    }
+                    Instruction::LoadI { nnn } => refer(&mut targets, usize::from(nnn), Target::Data),
+                    Instruction::LoadILong { nnnn } => refer(&mut targets, usize::from(nnnn), Target::Data),
+                    Instruction::Return | Instruction::Exit => break,
+                    _ => {}
+                }
+                address = next;
+            }
+        }
+
+        //Everything else is data, split wherever something points into it
+        let mut items = Vec::new();
+        let mut address = ORIGIN;
+        let end = ORIGIN + self.rom.len();
+        while address < end {
+            if let Some(&instruction) = code.get(&address) {
+                items.push(Item::Code { address, instruction });
+                address += instruction.size();
+                continue;
+            }
+            let start = address;
+            address += 1;
+            while address < end && !code.contains_key(&address) && !targets.contains_key(&address) {
+                address += 1;
+            }
+            items.push(Item::Data {
+                address: start,
+                bytes: self.rom[start - ORIGIN..address - ORIGIN].to_vec(),
+            });
+        }
+
+        //Only label addresses that start an item, so every label lands
+        //where it points
+        let starts: Vec<usize> = items.iter().map(Item::address).collect();
+        let labels = targets
+            .into_iter()
+            .filter(|(address, _)| starts.binary_search(address).is_ok())
+            .map(|(address, target)| {
+                let prefix = match target {
+                    Target::Call => "sub",
+                    Target::Jump => "label",
+                    Target::Data if code.contains_key(&address) => "label",
+                    Target::Data => "data",
+                };
+                (address, format!("{}_{:03X}", prefix, address))
+            })
+            .collect();
+        computed_jumps.sort_unstable();
+        Disassembly {
+            items,
+            labels,
+            computed_jumps,
+        }
+    }
+
+    /// The instruction at `address`, if it is in the ROM and the platform
+    /// would execute it.
+    fn decode(&self, address: usize) -> Option<Instruction> {
+        let word = |address: usize| {
+            let offset = address.checked_sub(ORIGIN)?;
+            let bytes = self.rom.get(offset..offset + 2)?;
+            Some(u16::from(bytes[0]) << 8 | u16::from(bytes[1]))
+        };
+        let opcode = word(address)?;
+        let instruction = if opcode == 0xF000 && self.platform >= Platform::XoChip {
+            Instruction::decode_pair(opcode, word(address + 2)?)
+        } else {
+            Instruction::decode(opcode)
+        };
+        match instruction {
+            //The interpreter refuses to run machine code routines
+            Ok(Instruction::Sys { .. }) => None,
+            Ok(instruction) if instruction.platform() <= self.platform => Some(instruction),
+            _ => None,
+        }
+    }
+}
+
+/// How an address is referred to, which picks its label's name. Calls
+/// win over jumps, and jumps over data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Target {
+    Data,
+    Jump,
+    Call,
+}
+
+fn refer(targets: &mut BTreeMap<usize, Target>, address: usize, target: Target) {
+    let existing = targets.entry(address).or_insert(target);
+    *existing = (*existing).max(target);
+}
+
+/// A ROM split into code and data. `Display` gives the listing as source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Disassembly {
+    /// Every byte of the ROM, in order.
+    pub items: Vec<Item>,
+    pub labels: BTreeMap<usize, String>,
+    /// `BNNN` instructions, whose targets can't be known statically.
+    pub computed_jumps: Vec<usize>,
+}
+
+impl Disassembly {
+    /// The ROM the listing assembles to.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        for item in &self.items {
+            match *item {
+                Item::Code { instruction, .. } => out.extend(instruction.to_bytes()),
+                Item::Data { ref bytes, .. } => out.extend_from_slice(bytes),
+            }
+        }
+        out
+    }
+
+    /// An instruction as source, with its address operand replaced by a
+    /// label when there is one.
+    pub fn source(&self, instruction: Instruction) -> String {
+        let label = |address: u16| self.labels.get(&usize::from(address));
+        match instruction {
+            Instruction::Jump { nnn } if label(nnn).is_some() => format!("JP {}", label(nnn).unwrap()),
+            Instruction::Call { nnn } if label(nnn).is_some() => format!("CALL {}", label(nnn).unwrap()),
+            Instruction::LoadI { nnn } if label(nnn).is_some() => format!("LD I, {}", label(nnn).unwrap()),
+            Instruction::JumpV0 { nnn } if label(nnn).is_some() => format!("JP V0, {}", label(nnn).unwrap()),
+            Instruction::LoadILong { nnnn } if label(nnnn).is_some() => {
+                format!("LD I, LONG {}", label(nnnn).unwrap())
+            }
+            _ => instruction.to_string(),
+        }
+    }
+}
+
+impl Display for Disassembly {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let size: usize = self.items.iter().map(Item::size).sum();
+        write!(f, "; {} bytes, loaded at 0x{:03X}", size, ORIGIN)?;
+        for item in &self.items {
+            let address = item.address();
+            if let Some(label) = self.labels.get(&address) {
+                write!(f, "\n{}:", label)?;
+            }
+            match *item {
+                Item::Code { instruction, .. } => {
+                    let computed = if self.computed_jumps.contains(&address) {
+                        ", computed jump"
+                    } else {
+                        ""
+                    };
+                    write!(
+                        f,
+                        "\n    {:<23} ; 0x{:03X}{}",
+                        self.source(instruction),
+                        address,
+                        computed
+                    )?;
+                }
+                Item::Data { ref bytes, .. } => {
+                    for (line, bytes) in bytes.chunks(BYTES_PER_LINE).enumerate() {
+                        let bytes: Vec<String> = bytes.iter().map(|byte| format!("0x{:02X}", byte)).collect();
+                        let db = format!("DB {}", bytes.join(", "));
+                        write!(f, "\n    {:<23} ; 0x{:03X}", db, address + line * BYTES_PER_LINE)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,91 @@
+use chip8::bus::Access;
+use chip8::coverage::Coverage;
+use chip8::opcode::Instruction;
+use chip8::Platform;
+use disassembler::{Disassembler, Item};
+
+//Point I at a sprite, draw it from a subroutine and loop, with the sprite
+//after the code
+const PROGRAM: [u8; 14] = [
+    0xA2, 0x0A, 0x22, 0x06, 0x12, 0x02, 0xD0, 0x13, 0x00, 0xEE, 0xF0, 0x90, 0xF0, 0x00,
+];
+
+#[test]
+fn test_listing() {
+    let disassembly = Disassembler::new(&PROGRAM, Platform::Chip8).disassemble();
+    assert_eq!(
+        disassembly.to_string(),
+        "; 14 bytes, loaded at 0x200
+    LD I, data_20A          ; 0x200
+label_202:
+    CALL sub_206            ; 0x202
+    JP label_202            ; 0x204
+sub_206:
+    DRW V0, V1, 0x3         ; 0x206
+    RET                     ; 0x208
+data_20A:
+    DB 0xF0, 0x90, 0xF0, 0x00 ; 0x20A"
+    );
+    assert_eq!(disassembly.to_bytes(), PROGRAM.to_vec());
+}
+
+#[test]
+fn test_pong_round_trips() {
+    let rom = include_bytes!("../../roms/pong");
+    let disassembly = Disassembler::new(rom, Platform::Chip8).disassemble();
+    assert_eq!(disassembly.to_bytes(), rom.to_vec());
+    assert_eq!(disassembly.labels.get(&0x2D4).map(String::as_str), Some("sub_2D4"));
+    assert_eq!(disassembly.items.last(), Some(&Item::Data { address: 0x2F2, bytes: vec!(0, 0, 0, 0) }));
+}
+
+#[test]
+fn test_skips_follow_both_paths() {
+    //SE V0, 0x00 over a long load, then RET or EXIT
+    let rom = [0x30, 0x00, 0xF0, 0x00, 0x12, 0x34, 0x00, 0xEE, 0x00, 0xFD];
+    let items = Disassembler::new(&rom, Platform::XoChip).disassemble().items;
+    let code: Vec<usize> = items
+        .iter()
+        .filter_map(|item| match *item {
+            Item::Code { address, .. } => Some(address),
+            Item::Data { .. } => None,
+        })
+        .collect();
+    assert_eq!(code, vec!(0x200, 0x202, 0x206));
+    assert_eq!(items[3], Item::Data { address: 0x208, bytes: vec!(0x00, 0xFD) });
+
+    //Before XO-CHIP, F000 isn't an instruction and only two bytes are skipped
+    let items = Disassembler::new(&rom, Platform::SuperChip).disassemble().items;
+    assert_eq!(items[1], Item::Data { address: 0x202, bytes: vec!(0xF0, 0x00) });
+    assert_eq!(items[2], Item::Code { address: 0x204, instruction: Instruction::Jump { nnn: 0x234 } });
+}
+
+#[test]
+fn test_computed_jumps_need_entries() {
+    //JP V0, 0x204 into a table of jumps, of which only the first is found
+    let rom = [0xB2, 0x04, 0x00, 0x00, 0x12, 0x08, 0x12, 0x0A, 0x00, 0xFD, 0x00, 0xFD];
+    let disassembly = Disassembler::new(&rom, Platform::SuperChip).disassemble();
+    assert_eq!(disassembly.computed_jumps, vec!(0x200));
+    assert!(disassembly.to_string().contains("JP V0, label_204        ; 0x200, computed jump"));
+    assert_eq!(disassembly.items[3], Item::Data { address: 0x206, bytes: vec!(0x12, 0x0A) });
+
+    let mut coverage = Coverage::new(0x1000);
+    coverage.mark(0x206, Access::Fetch);
+    coverage.mark(0x207, Access::Fetch);
+    let mut disassembler = Disassembler::new(&rom, Platform::SuperChip);
+    disassembler.add_coverage(&coverage);
+    let items = disassembler.disassemble().items;
+    assert_eq!(items[3], Item::Code { address: 0x206, instruction: Instruction::Jump { nnn: 0x20A } });
+    assert_eq!(items.len(), 6);
+}
+
+#[test]
+fn test_overlapping_code_is_refused() {
+    //A jump into the middle of LD V1, 0x22
+    let rom = [0x12, 0x03, 0x61, 0x22, 0x08, 0x00, 0xEE];
+    let mut disassembler = Disassembler::new(&rom, Platform::Chip8);
+    disassembler.add_entry(0x202);
+    let disassembly = disassembler.disassemble();
+    assert_eq!(disassembly.to_bytes(), rom.to_vec());
+    assert!(disassembly.labels.is_empty());
+    assert!(disassembly.to_string().contains("JP 0x203"));
+}
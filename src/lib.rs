@@ -0,0 +1,33 @@
+//! A CHIP-8, SUPER-CHIP and XO-CHIP interpreter.
+//!
+//! The windowed app in `main.rs` is one frontend built on this crate.
+//! Everything it needs is public here, so other tools can drive [`Chip8`]
+//! directly without pulling in a windowing stack. `chip8-dap`, built with
+//! the `dap` feature, is another: it serves the debugger to editors over the
+//! Debug Adapter Protocol.
+//!
+//! Without the `std` feature the interpreter builds as `no_std`, needing
+//! only an allocator. ROMs are then loaded from byte slices with
+//! [`Chip8::load_rom`] and nothing is printed.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+#[macro_use]
+extern crate alloc;
+#[cfg(any(feature = "std", test))]
+extern crate core;
+#[cfg(feature = "dap")]
+#[macro_use]
+extern crate serde_json;
+
+#[cfg(feature = "assembler")]
+pub mod assembler;
+pub mod chip8;
+#[cfg(feature = "debugger")]
+pub mod debugger;
+#[cfg(feature = "disassembler")]
+pub mod disassembler;
+pub mod sprite;
+
+pub use chip8::opcode::Instruction;
+pub use chip8::{Chip8, ExecutionError, Platform, Quirks};
@@ -1,170 +1,328 @@
+extern crate emu;
 extern crate piston;
 extern crate piston_window;
-extern crate rand;
 
 use piston::input::*;
-use piston_window::{clear, rectangle, PistonWindow, WindowSettings};
-use std::io;
-use std::time::{Duration, Instant};
+use piston_window::{clear, rectangle, EventLoop, PistonWindow, WindowSettings};
+use emu::chip8;
+use emu::chip8::coverage::Coverage;
+use emu::chip8::movie::{Movie, Player, Recorder};
+use emu::chip8::rewind::Rewind;
+use emu::chip8::Tracer;
+use emu::debugger::Debugger;
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, BufWriter, Write};
+use std::ops::Range;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-mod assembler;
-mod chip8;
-mod sprite;
+const WINDOW_WIDTH: f64 = 640.0;
+const WINDOW_HEIGHT: f64 = 320.0;
+const REWIND_BUDGET: usize = 16 * 1024 * 1024;
+const MOVIE_CHECKPOINT_INTERVAL: u32 = 60;
+//Colour for each pixel value: off, first plane, second plane, both planes
+const PALETTE: [[f32; 4]; 4] = [
+    [0.0, 0.0, 0.0, 1.0], //BLACK
+    [1.0, 1.0, 1.0, 1.0], //WHITE
+    [1.0, 0.4, 0.0, 1.0], //ORANGE
+    [0.5, 0.5, 0.5, 1.0], //GREY
+];
 
-const DEBUG_MODE: bool = true;
+/// Settings chosen on the command line, so that each ROM can be run with the
+/// platform and speed it was written for.
+struct Options {
+    rom: String,
+    platform: chip8::Platform,
+    instructions_per_frame: Option<usize>,
+    seed: Option<u64>,
+    record: Option<String>,
+    play: Option<String>,
+    debug: bool,
+    trace: Option<String>,
+    trace_addresses: Option<Range<u64>>,
+    trace_cycles: Option<Range<u64>>,
+    coverage: Option<String>,
+}
+
+fn parse_options() -> Options {
+    let mut options = Options {
+        rom: "./roms/pong".to_string(),
+        platform: chip8::Platform::Chip8,
+        instructions_per_frame: None,
+        seed: None,
+        record: None,
+        play: None,
+        debug: false,
+        trace: None,
+        trace_addresses: None,
+        trace_cycles: None,
+        coverage: None,
+    };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--platform" => {
+                options.platform = match args.next().as_deref() {
+                    Some("chip8") => chip8::Platform::Chip8,
+                    Some("schip") => chip8::Platform::SuperChip,
+                    Some("xochip") => chip8::Platform::XoChip,
+                    other => panic!("Unknown platform {:?}, expected chip8, schip or xochip.", other),
+                }
+            }
+            "--ipf" => {
+                let value = args.next().and_then(|value| value.parse().ok());
+                options.instructions_per_frame = Some(value.expect("--ipf expects a number."));
+            }
+            "--seed" => {
+                let value = args.next().and_then(|value| value.parse().ok());
+                options.seed = Some(value.expect("--seed expects a number."));
+            }
+            "--record" => options.record = Some(args.next().expect("--record expects a file.")),
+            "--play" => options.play = Some(args.next().expect("--play expects a file.")),
+            "--debug" => options.debug = true,
+            "--trace" => options.trace = Some(args.next().expect("--trace expects a file.")),
+            "--trace-addresses" => {
+                let range = args.next().as_deref().and_then(parse_range);
+                options.trace_addresses = Some(range.expect("--trace-addresses expects START..END."));
+            }
+            "--trace-cycles" => {
+                let range = args.next().as_deref().and_then(parse_range);
+                options.trace_cycles = Some(range.expect("--trace-cycles expects START..END."));
+            }
+            "--coverage" => options.coverage = Some(args.next().expect("--coverage expects a file.")),
+            _ => options.rom = arg,
+        }
+    }
+    options
+}
+
+/// Parses `START..END`, each decimal or hex with 0x.
+fn parse_range(text: &str) -> Option<Range<u64>> {
+    let number = |text: &str| match text.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    };
+    let (start, end) = text.split_once("..")?;
+    Some(number(start)?..number(end)?)
+}
+
+/// The keypad laid out on the left of a QWERTY keyboard, with the top row
+/// on the number pad.
+fn keypad_key(key: Key) -> Option<usize> {
+    let layout = [
+        Key::NumPad1, Key::NumPad2, Key::NumPad3, Key::NumPad4,
+        Key::Q, Key::W, Key::E, Key::R,
+        Key::A, Key::S, Key::D, Key::F,
+        Key::Z, Key::X, Key::C, Key::V,
+    ];
+    layout.iter().position(|&candidate| candidate == key)
+}
+
+/// Reads debugger commands from stdin without blocking the window.
+fn read_commands() -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let stdin = io::stdin();
+        for line in stdin.lock().lines() {
+            if line.map(|line| sender.send(line)).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+fn show_prompt(debugger: &Debugger, chip8: &chip8::Chip8) {
+    print!("{}", debugger.prompt(chip8));
+    let _ = io::stdout().flush();
+}
 
 fn main() {
-    let mut window: PistonWindow = WindowSettings::new("CHIP 8", [64 * 10, 32 * 10])
+    let options = parse_options();
+    let instructions_per_frame = options
+        .instructions_per_frame
+        .unwrap_or_else(|| options.platform.instructions_per_frame());
+
+    let mut window: PistonWindow = WindowSettings::new("CHIP 8", [WINDOW_WIDTH as u32, WINDOW_HEIGHT as u32])
         .build()
         .unwrap();
-    let mut chip8 = chip8::Chip8::new();
-    chip8.load("./roms/pong".to_string());
-    chip8.debug_memory();
+    window.set_ups(60);
+    let rom = fs::read(&options.rom).expect("Could not read the ROM.");
+    //Pass --seed to replay a run exactly, otherwise every run is different
+    let seed = options.seed.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos() as u64)
+            .unwrap_or_default()
+    });
+    let mut player = None;
+    let mut recorder = None;
+    let mut chip8 = if let Some(ref path) = options.play {
+        let bytes = fs::read(path).expect("Could not read the movie.");
+        let movie = Movie::from_bytes(&bytes).expect("Could not load the movie.");
+        let chip8 = movie.start(&rom).expect("Could not play the movie.");
+        player = Some(Player::new(movie));
+        chip8
+    } else {
+        let quirks = options.platform.quirks();
+        let movie = Movie::new(&rom, seed, options.platform, quirks, instructions_per_frame);
+        let chip8 = movie.start(&rom).expect("Could not start the ROM.");
+        if options.record.is_some() {
+            recorder = Some(Recorder::new(movie, MOVIE_CHECKPOINT_INTERVAL));
+        }
+        chip8
+    };
+    let state_path = format!("{}.state", options.rom);
+    if let Some(ref path) = options.trace {
+        let file = fs::File::create(path).expect("Could not create the trace.");
+        let mut tracer = Tracer::to_writer(BufWriter::new(file));
+        if let Some(ref range) = options.trace_addresses {
+            tracer.filter_addresses(range.start as usize..range.end as usize);
+        }
+        if let Some(ref range) = options.trace_cycles {
+            tracer.filter_cycles(range.clone());
+        }
+        chip8.tracer = Some(tracer);
+    }
+    //Pass --coverage to build up a map of code and data over play sessions
+    let coverage = options.coverage.as_ref().map(|path| {
+        let coverage = match fs::read(path) {
+            Ok(bytes) => Coverage::from_bytes(&bytes).expect("Could not load the coverage map."),
+            Err(_) => Coverage::new(chip8.memory.len()),
+        };
+        chip8.debug_memory_covered(&coverage);
+        coverage.record(&mut chip8)
+    });
+    if coverage.is_none() {
+        chip8.debug_memory();
+    }
+
+    //Press F1, or pass --debug, to break into the debugger and type commands
+    let commands = read_commands();
+    let mut debugger = Debugger::new(instructions_per_frame);
+    if options.debug {
+        debugger.pause();
+        show_prompt(&debugger, &chip8);
+    }
 
-    let mut now = Instant::now();
-    let mut cycle_count = 0;
+    let mut halted = false;
+    let mut rewind = Rewind::new(REWIND_BUDGET, 1);
+    let mut rewinding = false;
     while let Some(e) = window.next() {
-        if DEBUG_MODE {
-            println!("DEBUG MODE - Press any key to emulate next cycle");
-            println!("{}", chip8);
-            let mut input = String::new();
-            io::stdin().read_line(&mut input);
+        while let Ok(line) = commands.try_recv() {
+            let output = debugger.execute(&mut chip8, &line);
+            for message in debugger.take_log() {
+                println!("{}", message);
+            }
+            if !output.is_empty() {
+                println!("{}", output);
+            }
+            if debugger.is_paused() {
+                show_prompt(&debugger, &chip8);
+            }
+        }
+
+        if e.update_args().is_some() && player.as_ref().is_some_and(Player::is_finished) {
+            println!("Movie finished, handing over the keypad");
+            player = None;
         }
 
-        chip8.emulate_cycle();
-        cycle_count += 1;
-        // if cycle_count % 60 == 0 {
-        // println!(
-        //     "Cycle # {}. {}.{} seconds since last cycle.",
-        //     cycle_count,
-        //     now.elapsed().as_secs(),
-        //     now.elapsed().subsec_nanos()
-        // );
-        // now = Instant::now();
-        // }
+        let movie_running = player.is_some() || recorder.is_some();
+        if e.update_args().is_some() && rewinding && !movie_running {
+            //Hold backspace to step back one frame per frame
+            match rewind.rewind(&mut chip8, 1) {
+                Ok(_) => halted = false,
+                Err(error) => println!("Could not rewind: {}", error),
+            }
+        } else if e.update_args().is_some() && !halted && !debugger.is_paused() {
+            let result = if let Some(ref mut player) = player {
+                player.run_frame(&mut chip8).map_err(|error| error.to_string())
+            } else if let Some(ref mut recorder) = recorder {
+                recorder.run_frame(&mut chip8).map_err(|error| error.to_string())
+            } else {
+                let stop = debugger.run_frame(&mut chip8);
+                for message in debugger.take_log() {
+                    println!("{}", message);
+                }
+                if let Some(stop) = stop {
+                    println!("{}", stop);
+                    show_prompt(&debugger, &chip8);
+                }
+                Ok(())
+            };
+            if let Err(error) = result {
+                println!("Execution halted: {}", error);
+                println!("{}", chip8);
+                halted = true;
+            }
+            rewind.record(&chip8);
+            //There is no audio output, so XO-CHIP's pattern buffer and pitch go unplayed
+            if chip8.sound > 0 {
+                println!("BEEP");
+            }
+        }
 
         if let Some(Button::Keyboard(key_pressed)) = e.press_args() {
             println!("Key pressed {:?}", key_pressed);
+            //The keypad belongs to the movie during playback
+            match (keypad_key(key_pressed), recorder.as_mut()) {
+                (Some(key), Some(recorder)) => recorder.key_down(&mut chip8, key),
+                (Some(key), None) if player.is_none() => chip8.key_down(key),
+                _ => {}
+            }
             match key_pressed {
-                Key::NumPad1 => {
-                    chip8.keyboard[0] = true;
-                }
-                Key::NumPad2 => {
-                    chip8.keyboard[1] = true;
-                }
-                Key::NumPad3 => {
-                    chip8.keyboard[2] = true;
-                }
-                Key::NumPad4 => {
-                    chip8.keyboard[3] = true;
-                }
-                Key::Q => {
-                    chip8.keyboard[4] = true;
-                }
-                Key::W => {
-                    chip8.keyboard[5] = true;
-                }
-                Key::E => {
-                    chip8.keyboard[6] = true;
-                }
-                Key::R => {
-                    chip8.keyboard[7] = true;
-                }
-                Key::A => {
-                    chip8.keyboard[8] = true;
-                }
-                Key::S => {
-                    chip8.keyboard[9] = true;
-                }
-                Key::D => {
-                    chip8.keyboard[10] = true;
-                }
-                Key::F => {
-                    chip8.keyboard[11] = true;
-                }
-                Key::Z => {
-                    chip8.keyboard[12] = true;
-                }
-                Key::X => {
-                    chip8.keyboard[13] = true;
-                }
-                Key::C => {
-                    chip8.keyboard[14] = true;
-                }
-                Key::V => {
-                    chip8.keyboard[15] = true;
-                }
+                Key::Backspace => {
+                    rewinding = true;
+                }
+                Key::F1 if debugger.is_paused() => debugger.resume(),
+                Key::F1 => {
+                    debugger.pause();
+                    show_prompt(&debugger, &chip8);
+                }
+                Key::F5 => match fs::write(&state_path, chip8.save_state()) {
+                    Ok(()) => println!("Saved state to {}", state_path),
+                    Err(error) => println!("Could not save state: {}", error),
+                },
+                Key::F9 if movie_running => println!("States cannot be loaded while a movie is running"),
+                Key::F9 => match fs::read(&state_path) {
+                    Ok(bytes) => match chip8.load_state(&bytes) {
+                        Ok(()) => {
+                            println!("Loaded state from {}", state_path);
+                            halted = false;
+                        }
+                        Err(error) => println!("Could not load state: {}", error),
+                    },
+                    Err(error) => println!("Could not read {}: {}", state_path, error),
+                },
                 _ => {}
             }
         }
 
         if let Some(Button::Keyboard(key_released)) = e.release_args() {
-            match key_released {
-                Key::NumPad1 => {
-                    chip8.keyboard[0] = false;
-                }
-                Key::NumPad2 => {
-                    chip8.keyboard[1] = false;
-                }
-                Key::NumPad3 => {
-                    chip8.keyboard[2] = false;
-                }
-                Key::NumPad4 => {
-                    chip8.keyboard[3] = false;
-                }
-                Key::Q => {
-                    chip8.keyboard[4] = false;
-                }
-                Key::W => {
-                    chip8.keyboard[5] = false;
-                }
-                Key::E => {
-                    chip8.keyboard[6] = false;
-                }
-                Key::R => {
-                    chip8.keyboard[7] = false;
-                }
-                Key::A => {
-                    chip8.keyboard[8] = false;
-                }
-                Key::S => {
-                    chip8.keyboard[9] = false;
-                }
-                Key::D => {
-                    chip8.keyboard[10] = false;
-                }
-                Key::F => {
-                    chip8.keyboard[11] = false;
-                }
-                Key::Z => {
-                    chip8.keyboard[12] = false;
-                }
-                Key::X => {
-                    chip8.keyboard[13] = false;
-                }
-                Key::C => {
-                    chip8.keyboard[14] = false;
-                }
-                Key::V => {
-                    chip8.keyboard[15] = false;
-                }
+            match (keypad_key(key_released), recorder.as_mut()) {
+                (Some(key), Some(recorder)) => recorder.key_up(&mut chip8, key),
+                (Some(key), None) if player.is_none() => chip8.key_up(key),
                 _ => {}
             }
+            if key_released == Key::Backspace {
+                rewinding = false;
+            }
         }
 
         if chip8.V[0xF] != 0 {
             window.draw_2d(&e, |context, graphics| {
                 clear([0.5, 0.5, 0.5, 1.0], graphics);
 
-                for y in 0..32 {
-                    for x in 0..64 {
-                        let mut color = [0.0, 0.0, 0.0, 1.0]; //BLACK
-                        if chip8.pixel_at(x, y) {
-                            color = [1.0, 1.0, 1.0, 1.0]; //WHITE
-                        }
+                //Scale whichever resolution is active to fill the window
+                let scale = WINDOW_WIDTH / chip8.width() as f64;
+                for y in 0..chip8.height() {
+                    for x in 0..chip8.width() {
+                        let color = PALETTE[usize::from(chip8.pixel_color_at(x, y) & 0x3)];
                         rectangle(
                             color,
-                            [10.0 * x as f64, 10.0 * y as f64, 10.0, 10.0],
+                            [scale * x as f64, scale * y as f64, scale, scale],
                             context.transform,
                             graphics,
                         );
@@ -173,4 +331,20 @@ fn main() {
             });
         }
     }
+
+    if let (Some(path), Some(coverage)) = (options.coverage, coverage) {
+        let coverage = coverage.borrow();
+        println!("Coverage of the ROM: {}", coverage.summary(0x200..0x200 + rom.len()));
+        if let Err(error) = fs::write(&path, coverage.to_bytes()) {
+            println!("Could not write {}: {}", path, error);
+        }
+    }
+
+    if let (Some(path), Some(recorder)) = (options.record, recorder) {
+        let movie = recorder.finish();
+        match fs::write(&path, movie.to_bytes()) {
+            Ok(()) => println!("Recorded {} frames to {}", movie.frames, path),
+            Err(error) => println!("Could not write {}: {}", path, error),
+        }
+    }
 }
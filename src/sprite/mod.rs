@@ -1,3 +1,5 @@
+use alloc::vec::Vec;
+
 pub fn get_font_set() -> Vec<[u8; 5]> {
     vec![
         [0xF0, 0x90, 0x90, 0x90, 0xF0], //0
@@ -13,7 +15,30 @@ pub fn get_font_set() -> Vec<[u8; 5]> {
         [0xF0, 0x90, 0xF0, 0x90, 0x90], //A
         [0xE0, 0x90, 0xE0, 0x90, 0xE0], //B
         [0xF0, 0x80, 0x80, 0x80, 0xF0], //C
-        [0xF0, 0x80, 0xF0, 0x80, 0xF0], //D
-        [0xF0, 0x80, 0xF0, 0x80, 0x80], //E
+        [0xE0, 0x90, 0x90, 0x90, 0xE0], //D
+        [0xF0, 0x80, 0xF0, 0x80, 0xF0], //E
+        [0xF0, 0x80, 0xF0, 0x80, 0x80], //F
+    ]
+}
+
+/// The 8x10 SUPER-CHIP font used by `FX30`.
+pub fn get_big_font_set() -> Vec<[u8; 10]> {
+    vec![
+        [0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF], //0
+        [0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF], //1
+        [0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF], //2
+        [0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF], //3
+        [0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03], //4
+        [0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF], //5
+        [0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF], //6
+        [0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18], //7
+        [0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF], //8
+        [0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF], //9
+        [0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3], //A
+        [0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC], //B
+        [0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C], //C
+        [0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC], //D
+        [0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF], //E
+        [0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0], //F
     ]
 }